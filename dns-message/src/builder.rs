@@ -1,4 +1,7 @@
-use crate::{Class, Header, Message, OpCode, Question, RCode, RData, ResourceRecord, Type};
+use crate::{
+    Class, DomainName, Edns, Header, Message, OpCode, Prerequisite, Question, RCode, RData,
+    ResourceRecord, Type, Update,
+};
 use std::default::Default;
 
 #[derive(Debug, Default)]
@@ -21,6 +24,7 @@ pub struct MessageBuilder {
     answers: Vec<ResourceRecord>,
     name_servers: Vec<ResourceRecord>,
     additional_records: Vec<ResourceRecord>,
+    edns: Option<Edns>,
 }
 
 impl MessageBuilder {
@@ -40,6 +44,7 @@ impl MessageBuilder {
                 tc: self.tc,
                 rd: self.rd,
                 ra: self.ra,
+                z: false,
                 ad: self.ad,
                 cd: self.cd,
                 rcode: self.rcode,
@@ -48,6 +53,7 @@ impl MessageBuilder {
             answers: self.answers,
             name_servers: self.name_servers,
             additional_records: self.additional_records,
+            edns: self.edns,
         }
     }
 
@@ -149,12 +155,35 @@ impl MessageBuilder {
         self.additional_records.push(ar);
         self
     }
+
+    /// Sets the [`Edns`] information, which is sent as an OPT record in the
+    /// additional records section.
+    pub fn edns(mut self, edns: Edns) -> Self {
+        self.edns = Some(edns);
+        self
+    }
+
+    /// RFC1996 - Makes the message a NOTIFY, telling the secondaries of the
+    /// zone that it has changed. The NOTIFY is authoritative, and asks for the
+    /// SOA of the zone.
+    pub fn notify<N: Into<DomainName>>(mut self, zone: N) -> Self {
+        self.opcode = OpCode::Notify;
+        self.aa = true;
+        self.question(QuestionBuilder::new().name(zone).q_type(Type::SOA).build())
+    }
+
+    /// RFC1996 - Adds the new SOA of the zone to the answer section of a
+    /// NOTIFY, as a hint that lets a secondary that already has that serial
+    /// skip the refresh.
+    pub fn soa_hint(self, soa: ResourceRecord) -> Self {
+        self.answer(soa)
+    }
 }
 
 #[derive(Debug, Default)]
 /// A builder for the [`Question`] struct.
 pub struct QuestionBuilder {
-    q_name: String,
+    q_name: DomainName,
     q_type: Type,
     q_class: Class,
 }
@@ -177,8 +206,8 @@ impl QuestionBuilder {
 
     /// Sets the domain name for the [`Question`]. Each label section must be of
     /// length 63 or less.
-    pub fn name(mut self, name: &str) -> Self {
-        self.q_name = name.to_string();
+    pub fn name<N: Into<DomainName>>(mut self, name: N) -> Self {
+        self.q_name = name.into();
         self
     }
 
@@ -198,7 +227,7 @@ impl QuestionBuilder {
 #[derive(Debug)]
 /// A builder for the [`ResourceRecord`] struct.
 pub struct ResourceRecordBuilder {
-    name: String,
+    name: DomainName,
     data: RData,
     class: Class,
     ttl: u32,
@@ -207,10 +236,10 @@ pub struct ResourceRecordBuilder {
 impl ResourceRecordBuilder {
     /// Creates a new [`ResourceRecordBuilder`] - a [`ResourceRecord`] must have
     /// a `name` and [`RData`].    
-    pub fn new(name: &str, data: RData) -> Self {
+    pub fn new<N: Into<DomainName>>(name: N, data: RData) -> Self {
         Self {
-            name: name.to_string(),
-            data: data,
+            name: name.into(),
+            data,
             class: Default::default(),
             ttl: Default::default(),
         }
@@ -240,6 +269,89 @@ impl ResourceRecordBuilder {
     }
 }
 
+#[derive(Debug)]
+/// A builder for an RFC2136 dynamic update [`Message`], which can be read back
+/// with an [`UpdateMessage`](crate::UpdateMessage).
+pub struct UpdateBuilder {
+    id: u16,
+    zone: DomainName,
+    class: Class,
+    prerequisites: Vec<Prerequisite>,
+    updates: Vec<Update>,
+    additional_records: Vec<ResourceRecord>,
+}
+
+impl UpdateBuilder {
+    /// Creates a new [`UpdateBuilder`] for the zone, with no prerequisites or
+    /// updates.
+    pub fn new<N: Into<DomainName>>(zone: N) -> Self {
+        Self {
+            id: Default::default(),
+            zone: zone.into(),
+            class: Default::default(),
+            prerequisites: Vec::new(),
+            updates: Vec::new(),
+            additional_records: Vec::new(),
+        }
+    }
+
+    /// Consumes the [`UpdateBuilder`] to produce a [`Message`], with each
+    /// prerequisite and update encoded as a record.
+    pub fn build(self) -> Message {
+        let class = self.class;
+        let mut builder = MessageBuilder::new()
+            .id(self.id)
+            .opcode(OpCode::Update)
+            .question(
+                QuestionBuilder::new()
+                    .name(self.zone)
+                    .q_type(Type::SOA)
+                    .class(class)
+                    .build(),
+            );
+        for prerequisite in self.prerequisites.iter() {
+            builder = builder.answer(prerequisite.to_record(class));
+        }
+        for update in self.updates.iter() {
+            builder = builder.name_server(update.to_record(class));
+        }
+        for record in self.additional_records {
+            builder = builder.additional_record(record);
+        }
+        builder.build()
+    }
+
+    /// Sets the ID of the message.
+    pub fn id(mut self, id: u16) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Sets the [`Class`] of the zone - the default is [`Class::IN`].
+    pub fn class(mut self, cls: Class) -> Self {
+        self.class = cls;
+        self
+    }
+
+    /// Adds a [`Prerequisite`] to the Prerequisite Section.
+    pub fn prerequisite(mut self, prerequisite: Prerequisite) -> Self {
+        self.prerequisites.push(prerequisite);
+        self
+    }
+
+    /// Adds an [`Update`] to the Update Section.
+    pub fn update(mut self, update: Update) -> Self {
+        self.updates.push(update);
+        self
+    }
+
+    /// Adds a [`ResourceRecord`] to the Additional Data Section.
+    pub fn additional_record(mut self, ar: ResourceRecord) -> Self {
+        self.additional_records.push(ar);
+        self
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -257,12 +369,9 @@ mod test {
                     .build(),
             )
             .answer(
-                ResourceRecordBuilder::new(
-                    "www.google.com",
-                    RData::CNAME("ns1.google.com".to_string()),
-                )
-                .ttl(3600)
-                .build(),
+                ResourceRecordBuilder::new("www.google.com", RData::CNAME("ns1.google.com".into()))
+                    .ttl(3600)
+                    .build(),
             )
             .build();
 
@@ -275,9 +384,35 @@ mod test {
         assert_eq!(message.answers[0].name, "www.google.com");
         assert_eq!(
             message.answers[0].data,
-            RData::CNAME("ns1.google.com".to_string())
+            RData::CNAME("ns1.google.com".into())
         );
         assert_eq!(message.answers[0].class, Class::IN);
         assert_eq!(message.answers[0].ttl, 3600);
     }
+
+    #[test]
+    fn test_notify_builder() {
+        let soa = "example.com. 3600 IN SOA ns1.example.com. hostmaster.example.com. 2024010102 3600 900 604800 300";
+        let message = MessageBuilder::new()
+            .id(1234)
+            .notify("example.com")
+            .soa_hint(soa.parse().unwrap())
+            .build();
+
+        assert_eq!(message.header.opcode, OpCode::Notify);
+        assert!(message.header.aa);
+        assert!(!message.header.qr);
+        assert_eq!(message.questions[0].q_name, "example.com");
+        assert_eq!(message.questions[0].q_type, Type::SOA);
+        assert_eq!(message.soa_hint(), Some(2024010102));
+
+        let mut buf = Vec::new();
+        message.to_bytes(&mut buf).unwrap();
+        let parsed = Message::from_bytes(&buf).unwrap();
+        assert_eq!(parsed, message);
+        assert_eq!(parsed.soa_hint(), Some(2024010102));
+
+        let message = MessageBuilder::new().notify("example.com").build();
+        assert_eq!(message.soa_hint(), None);
+    }
 }
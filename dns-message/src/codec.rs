@@ -0,0 +1,186 @@
+use crate::{Message, MessageError, Result, Writer};
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+use tracing::{instrument, trace};
+
+/// RFC1035 - the length of a message over TCP is a two byte prefix.
+const PREFIX_LENGTH: usize = 2;
+
+#[derive(Debug, Clone, Copy)]
+/// A [`Decoder`] and [`Encoder`] of [`Message`]s framed with the two byte
+/// length prefix used over TCP, as per RFC1035 and RFC7766.
+///
+/// It works with any byte stream, such as a TCP or TLS connection or a file of
+/// recorded messages, through a `tokio_util::codec::Framed`. Frames longer
+/// than the [`max_frame_length`](TcpCodec::max_frame_length) are rejected
+/// before they are read, and a frame split across reads is only decoded once
+/// all of it has arrived.
+pub struct TcpCodec {
+    max_frame_length: usize,
+}
+
+impl TcpCodec {
+    /// Creates a codec that allows frames of up to 65535 octets, the most the
+    /// length prefix can hold.
+    pub fn new() -> Self {
+        TcpCodec {
+            max_frame_length: u16::MAX as usize,
+        }
+    }
+
+    /// Sets the longest message, not counting the length prefix, that is
+    /// decoded or encoded. It's capped at 65535 octets.
+    pub fn max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length.min(u16::MAX as usize);
+        self
+    }
+}
+
+impl Default for TcpCodec {
+    fn default() -> Self {
+        TcpCodec::new()
+    }
+}
+
+impl Decoder for TcpCodec {
+    type Item = Message;
+    type Error = MessageError;
+
+    #[instrument(skip(src))]
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Message>> {
+        if src.len() < PREFIX_LENGTH {
+            return Ok(None);
+        }
+        let len = u16::from_be_bytes([src[0], src[1]]) as usize;
+        if len > self.max_frame_length {
+            return Err(MessageError::FrameLengthExceeded(
+                len,
+                self.max_frame_length,
+            ));
+        }
+        if src.len() < PREFIX_LENGTH + len {
+            // Wait for the rest of the frame.
+            src.reserve(PREFIX_LENGTH + len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(PREFIX_LENGTH);
+        let frame = src.split_to(len);
+        trace!("Decoding frame of {} bytes", len);
+        Message::from_bytes(&frame).map(Some)
+    }
+}
+
+impl Encoder<&Message> for TcpCodec {
+    type Error = MessageError;
+
+    #[instrument(skip(message, dst))]
+    fn encode(&mut self, message: &Message, dst: &mut BytesMut) -> Result<()> {
+        let start = dst.len();
+        // The length is patched once the message has been written.
+        dst.write(&[0; PREFIX_LENGTH])?;
+        let len = match message.to_bytes(dst) {
+            Ok(len) if len <= self.max_frame_length => len,
+            Ok(len) => {
+                dst.truncate(start);
+                return Err(MessageError::FrameLengthExceeded(
+                    len,
+                    self.max_frame_length,
+                ));
+            }
+            Err(e) => {
+                dst.truncate(start);
+                return Err(e);
+            }
+        };
+        dst.write_at(start, &(len as u16).to_be_bytes());
+        trace!("Encoded frame of {} bytes", len);
+        Ok(())
+    }
+}
+
+impl Encoder<Message> for TcpCodec {
+    type Error = MessageError;
+
+    fn encode(&mut self, message: Message, dst: &mut BytesMut) -> Result<()> {
+        self.encode(&message, dst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test::setup, MessageBuilder, ParseErrorKind, QuestionBuilder};
+
+    fn message(id: u16) -> Message {
+        MessageBuilder::new()
+            .id(id)
+            .rd(true)
+            .question(QuestionBuilder::new().name("www.example.com").build())
+            .build()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        setup();
+
+        let mut codec = TcpCodec::new();
+        let mut buf = BytesMut::new();
+        codec.encode(message(1), &mut buf).unwrap();
+        codec.encode(&message(2), &mut buf).unwrap();
+
+        let mut expected = Vec::new();
+        message(1).to_bytes(&mut expected).unwrap();
+        assert_eq!(&buf[..2], &(expected.len() as u16).to_be_bytes());
+        assert_eq!(&buf[2..2 + expected.len()], expected.as_slice());
+
+        // Fed one byte at a time, each message is only decoded once the
+        // whole frame has arrived.
+        let mut src = BytesMut::new();
+        let mut decoded = Vec::new();
+        for b in buf.iter() {
+            src.extend_from_slice(&[*b]);
+            if let Some(m) = codec.decode(&mut src).unwrap() {
+                decoded.push(m);
+            }
+        }
+        assert_eq!(decoded, vec![message(1), message(2)]);
+        assert!(src.is_empty());
+        assert!(codec.decode(&mut src).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_max_frame_length() {
+        setup();
+
+        let mut codec = TcpCodec::new().max_frame_length(20);
+        let mut buf = BytesMut::from(&b"existing"[..]);
+        assert!(matches!(
+            codec.encode(message(1), &mut buf),
+            Err(MessageError::FrameLengthExceeded(33, 20))
+        ));
+        assert_eq!(&buf[..], b"existing");
+
+        // Only the prefix is needed to reject a frame.
+        let mut src = BytesMut::from(&[0, 33, 0][..]);
+        assert!(matches!(
+            codec.decode(&mut src),
+            Err(MessageError::FrameLengthExceeded(33, 20))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_frame() {
+        setup();
+
+        // The bad frame is consumed, so the next one can still be read.
+        let mut codec = TcpCodec::new();
+        let mut src = BytesMut::from(&[0, 3, 0, 1, 0][..]);
+        codec.encode(message(2), &mut src).unwrap();
+        match codec.decode(&mut src) {
+            Err(MessageError::Parse(e)) => assert_eq!(e.kind, ParseErrorKind::Truncated),
+            r => panic!("Expected a parse error, got {:?}", r),
+        }
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(message(2)));
+    }
+}
@@ -0,0 +1,343 @@
+use crate::{Header, Message, OpCode, Question, ResourceRecord};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy)]
+/// Displays a [`Message`] the way `dig` prints it, created with
+/// [`Message::dig`].
+///
+/// The header, the OPT pseudo-section and every section are written on their
+/// own lines with the records in the zone file format. The
+/// [`compact`](Dig::compact) form writes the same on a single line, for logs.
+pub struct Dig<'a> {
+    message: &'a Message,
+    compact: bool,
+}
+
+impl<'a> Dig<'a> {
+    pub(crate) fn new(message: &'a Message) -> Self {
+        Dig {
+            message,
+            compact: false,
+        }
+    }
+
+    /// Writes the message on a single line.
+    pub fn compact(mut self) -> Self {
+        self.compact = true;
+        self
+    }
+
+    /// The ADDITIONAL count of the header, which includes the OPT record.
+    fn additional_count(&self) -> usize {
+        self.message.additional_records.len() + self.message.edns.is_some() as usize
+    }
+
+    /// The names of the sections in the header counts and section titles,
+    /// which RFC2136 renames in an UPDATE.
+    fn section_names(&self) -> [(&'static str, &'static str); 4] {
+        if self.message.header.opcode == OpCode::Update {
+            [
+                ("ZONE", "ZONE"),
+                ("PREREQ", "PREREQUISITE"),
+                ("UPDATE", "UPDATE"),
+                ("ADDITIONAL", "ADDITIONAL"),
+            ]
+        } else {
+            [
+                ("QUERY", "QUESTION"),
+                ("ANSWER", "ANSWER"),
+                ("AUTHORITY", "AUTHORITY"),
+                ("ADDITIONAL", "ADDITIONAL"),
+            ]
+        }
+    }
+
+    fn fmt_full(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        let message = self.message;
+        let header = &message.header;
+        let names = self.section_names();
+        writeln!(
+            f,
+            ";; ->>HEADER<<- opcode: {}, status: {}, id: {}",
+            header.opcode, header.rcode, header.id
+        )?;
+        write!(f, ";; flags:")?;
+        fmt_flags(f, header)?;
+        writeln!(
+            f,
+            "; {}: {}, {}: {}, {}: {}, {}: {}",
+            names[0].0,
+            message.questions.len(),
+            names[1].0,
+            message.answers.len(),
+            names[2].0,
+            message.name_servers.len(),
+            names[3].0,
+            self.additional_count()
+        )?;
+
+        if let Some(edns) = message.edns.as_ref() {
+            writeln!(f)?;
+            writeln!(f, ";; OPT PSEUDOSECTION:")?;
+            write!(f, "; EDNS: version: {}, flags:", edns.version)?;
+            if edns.dnssec_ok {
+                write!(f, " do")?;
+            }
+            if edns.z != 0 {
+                write!(f, "; MBZ: {:#06x}", edns.z)?;
+            }
+            writeln!(f, "; udp: {}", edns.udp_payload_size)?;
+            for option in edns.options.iter() {
+                writeln!(f, "; {}", option)?;
+            }
+        }
+
+        if !message.questions.is_empty() {
+            writeln!(f)?;
+            writeln!(f, ";; {} SECTION:", names[0].1)?;
+            for q in message.questions.iter() {
+                write!(f, ";")?;
+                fmt_question(f, q)?;
+                writeln!(f)?;
+            }
+        }
+
+        let sections = [
+            (names[1].1, &message.answers),
+            (names[2].1, &message.name_servers),
+            (names[3].1, &message.additional_records),
+        ];
+        for (name, records) in sections.iter() {
+            if records.is_empty() {
+                continue;
+            }
+            writeln!(f)?;
+            writeln!(f, ";; {} SECTION:", name)?;
+            for record in records.iter() {
+                writeln!(f, "{}", record)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn fmt_compact(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        let message = self.message;
+        let header = &message.header;
+        write!(
+            f,
+            "id:{} {} {} flags:[",
+            header.id, header.opcode, header.rcode
+        )?;
+        let flags: Vec<&str> = flags_of(header)
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| *name)
+            .collect();
+        write!(f, "{}] question:[", flags.join(" "))?;
+        for (i, q) in message.questions.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            fmt_question(f, q)?;
+        }
+        write!(f, "] answer:[")?;
+        fmt_records(f, &message.answers)?;
+        write!(f, "] authority:[")?;
+        fmt_records(f, &message.name_servers)?;
+        write!(f, "] additional:[")?;
+        fmt_records(f, &message.additional_records)?;
+        write!(f, "]")?;
+
+        if let Some(edns) = message.edns.as_ref() {
+            write!(
+                f,
+                " edns:[version {} udp {}",
+                edns.version, edns.udp_payload_size
+            )?;
+            if edns.dnssec_ok {
+                write!(f, " do")?;
+            }
+            for option in edns.options.iter() {
+                write!(f, " {}", option)?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Dig<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        if self.compact {
+            self.fmt_compact(f)
+        } else {
+            self.fmt_full(f)
+        }
+    }
+}
+
+/// The header flags in the order dig writes them.
+fn flags_of(header: &Header) -> [(bool, &'static str); 8] {
+    [
+        (header.qr, "qr"),
+        (header.aa, "aa"),
+        (header.tc, "tc"),
+        (header.rd, "rd"),
+        (header.ra, "ra"),
+        (header.z, "z"),
+        (header.ad, "ad"),
+        (header.cd, "cd"),
+    ]
+}
+
+/// Writes the flags that are set, each preceded by a space.
+fn fmt_flags(f: &mut fmt::Formatter, header: &Header) -> std::result::Result<(), fmt::Error> {
+    for (set, name) in flags_of(header).iter() {
+        if *set {
+            write!(f, " {}", name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a question as `name class type`.
+fn fmt_question(f: &mut fmt::Formatter, q: &Question) -> std::result::Result<(), fmt::Error> {
+    write!(f, "{} {} {}", q.q_name.to_fqdn(), q.q_class, q.q_type)
+}
+
+/// Writes the records separated by commas.
+fn fmt_records(
+    f: &mut fmt::Formatter,
+    records: &[ResourceRecord],
+) -> std::result::Result<(), fmt::Error> {
+    for (i, record) in records.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", record)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::setup;
+    use crate::{
+        DomainName, Edns, EdnsOption, Message, MessageBuilder, QuestionBuilder, RCode, Type,
+        Update, UpdateBuilder,
+    };
+
+    fn message() -> MessageBuilder {
+        MessageBuilder::new()
+            .id(1234)
+            .rd(true)
+            .question(QuestionBuilder::new().name("www.example.com").build())
+    }
+
+    #[test]
+    fn test_dig() {
+        setup();
+
+        let message: Message = message()
+            .qr(true)
+            .ra(true)
+            .answer(
+                "www.example.com. 300 IN CNAME example.com."
+                    .parse()
+                    .unwrap(),
+            )
+            .answer("example.com. 300 IN A 192.0.2.1".parse().unwrap())
+            .name_server("example.com. 3600 IN NS ns1.example.com.".parse().unwrap())
+            .edns(Edns {
+                dnssec_ok: true,
+                options: vec![EdnsOption::Nsid(b"ns1".to_vec())],
+                ..Default::default()
+            })
+            .build();
+        assert_eq!(
+            message.dig().to_string(),
+            ";; ->>HEADER<<- opcode: QUERY, status: NOERROR, id: 1234\n\
+             ;; flags: qr rd ra; QUERY: 1, ANSWER: 2, AUTHORITY: 1, ADDITIONAL: 1\n\
+             \n\
+             ;; OPT PSEUDOSECTION:\n\
+             ; EDNS: version: 0, flags: do; udp: 1232\n\
+             ; NSID(6E7331 \"ns1\")\n\
+             \n\
+             ;; QUESTION SECTION:\n\
+             ;www.example.com. IN A\n\
+             \n\
+             ;; ANSWER SECTION:\n\
+             www.example.com. 300 IN CNAME example.com.\n\
+             example.com. 300 IN A 192.0.2.1\n\
+             \n\
+             ;; AUTHORITY SECTION:\n\
+             example.com. 3600 IN NS ns1.example.com.\n"
+        );
+        assert_eq!(
+            message.dig().compact().to_string(),
+            "id:1234 QUERY NOERROR flags:[qr rd ra] question:[www.example.com. IN A] \
+             answer:[www.example.com. 300 IN CNAME example.com., example.com. 300 IN A 192.0.2.1] \
+             authority:[example.com. 3600 IN NS ns1.example.com.] additional:[] \
+             edns:[version 0 udp 1232 do NSID(6E7331 \"ns1\")]"
+        );
+    }
+
+    #[test]
+    fn test_dig_query() {
+        setup();
+
+        let mut message = message().rcode(RCode::NameError).build();
+        assert_eq!(
+            message.dig().to_string(),
+            ";; ->>HEADER<<- opcode: QUERY, status: NXDOMAIN, id: 1234\n\
+             ;; flags: rd; QUERY: 1, ANSWER: 0, AUTHORITY: 0, ADDITIONAL: 0\n\
+             \n\
+             ;; QUESTION SECTION:\n\
+             ;www.example.com. IN A\n"
+        );
+        assert_eq!(
+            message.dig().compact().to_string(),
+            "id:1234 QUERY NXDOMAIN flags:[rd] question:[www.example.com. IN A] \
+             answer:[] authority:[] additional:[]"
+        );
+
+        // The reserved Z flag is shown when it's set.
+        message.header.z = true;
+        assert!(message.dig().to_string().starts_with(
+            ";; ->>HEADER<<- opcode: QUERY, status: NXDOMAIN, id: 1234\n;; flags: rd z;"
+        ));
+        assert!(message
+            .dig()
+            .compact()
+            .to_string()
+            .starts_with("id:1234 QUERY NXDOMAIN flags:[rd z]"));
+    }
+
+    #[test]
+    fn test_dig_update() {
+        setup();
+
+        let message = UpdateBuilder::new("example.com")
+            .id(1234)
+            .update(Update::DeleteRRset(
+                DomainName::from("www.example.com"),
+                Type::A,
+            ))
+            .update(Update::Add(
+                "www.example.com. 300 IN A 192.0.2.1".parse().unwrap(),
+            ))
+            .build();
+        assert_eq!(
+            message.dig().to_string(),
+            ";; ->>HEADER<<- opcode: UPDATE, status: NOERROR, id: 1234\n\
+             ;; flags:; ZONE: 1, PREREQ: 0, UPDATE: 2, ADDITIONAL: 0\n\
+             \n\
+             ;; ZONE SECTION:\n\
+             ;example.com. IN SOA\n\
+             \n\
+             ;; UPDATE SECTION:\n\
+             www.example.com. 0 ANY A \\# 0\n\
+             www.example.com. 300 IN A 192.0.2.1\n"
+        );
+    }
+}
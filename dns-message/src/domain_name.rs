@@ -0,0 +1,558 @@
+use crate::{MessageError, Result, Writer};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use tracing::{instrument, trace};
+
+/// RFC1035 - labels are restricted to 63 octets or less.
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// RFC1035 - names are restricted to 255 octets or less, including the length
+/// octets and the terminating root label.
+const MAX_NAME_LENGTH: usize = 255;
+
+/// Pointers are 14 bits wide, so names past this offset can't be pointed to.
+const MAX_POINTER_OFFSET: usize = 0x3fff;
+
+#[derive(Debug, Clone, Default)]
+/// A domain name, held as the sequence of its labels.
+///
+/// Labels are stored as raw octets, so labels containing dots or arbitrary
+/// binary data survive a round trip. All names are absolute - the root label
+/// is implied and not stored.
+///
+/// Comparisons between names are ASCII case-insensitive as per RFC4343, and
+/// do not care about a trailing dot in the presentation format.
+///
+/// A [`DomainName`] can be parsed from the presentation format with
+/// [`str::parse`], which validates the label and name lengths. The [`From`]
+/// conversions from strings are lenient and leave the length checks until the
+/// name is serialized.
+pub struct DomainName {
+    labels: Vec<Vec<u8>>,
+}
+
+impl DomainName {
+    /// The root domain name (`.`).
+    pub fn root() -> Self {
+        Default::default()
+    }
+
+    /// Creates a [`DomainName`] from its labels, ordered from the leftmost
+    /// (most specific) label to the rightmost.
+    ///
+    /// Returns an error if a label is empty or longer than 63 octets, or if
+    /// the name is longer than 255 octets.
+    pub fn from_labels<I, L>(labels: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let name = DomainName {
+            labels: labels.into_iter().map(|l| l.as_ref().to_vec()).collect(),
+        };
+        name.validate()?;
+        Ok(name)
+    }
+
+    /// Creates a [`DomainName`] without validating the labels.
+    pub(crate) fn from_labels_unchecked(labels: Vec<Vec<u8>>) -> Self {
+        DomainName { labels }
+    }
+
+    /// The labels of this name, from the leftmost (most specific) label to the
+    /// rightmost. The root name has no labels.
+    pub fn labels(&self) -> &[Vec<u8>] {
+        &self.labels
+    }
+
+    /// Returns true if this is the root name.
+    pub fn is_root(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// The number of octets this name takes up on the wire when it is not
+    /// compressed.
+    pub fn wire_len(&self) -> usize {
+        self.labels.iter().map(|l| l.len() + 1).sum::<usize>() + 1
+    }
+
+    /// Returns true if this name is equal to, or is below `other` in the
+    /// domain name hierarchy. Every name is a subdomain of the root.
+    pub fn is_subdomain_of(&self, other: &DomainName) -> bool {
+        if other.labels.len() > self.labels.len() {
+            return false;
+        }
+        self.labels
+            .iter()
+            .rev()
+            .zip(other.labels.iter().rev())
+            .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+
+    /// The name with the leftmost label removed, or [`None`] for the root.
+    pub fn parent(&self) -> Option<DomainName> {
+        if self.is_root() {
+            return None;
+        }
+        Some(DomainName {
+            labels: self.labels[1..].to_vec(),
+        })
+    }
+
+    /// RFC4034 - Compares names in the canonical order. Names are sorted by
+    /// their labels from the rightmost (least specific) label, with each label
+    /// compared as a lowercased octet string, and a name sorts before the
+    /// names below it.
+    ///
+    /// This is also the [`Ord`] of a [`DomainName`].
+    pub fn canonical_cmp(&self, other: &DomainName) -> Ordering {
+        for (a, b) in self.labels.iter().rev().zip(other.labels.iter().rev()) {
+            let ordering = a
+                .iter()
+                .map(u8::to_ascii_lowercase)
+                .cmp(b.iter().map(u8::to_ascii_lowercase));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        self.labels.len().cmp(&other.labels.len())
+    }
+
+    /// Returns a copy of this name with all ASCII letters lowercased.
+    pub fn to_lowercase(&self) -> DomainName {
+        DomainName {
+            labels: self.labels.iter().map(|l| l.to_ascii_lowercase()).collect(),
+        }
+    }
+
+    /// The fully qualified presentation format of this name, that is with the
+    /// trailing dot for the root label.
+    pub fn to_fqdn(&self) -> String {
+        if self.is_root() {
+            return ".".to_string();
+        }
+        format!("{}.", self)
+    }
+
+    /// Checks the label and total lengths of the name.
+    pub(crate) fn validate(&self) -> Result<()> {
+        for label in self.labels.iter() {
+            if label.is_empty() || label.len() > MAX_LABEL_LENGTH {
+                return Err(MessageError::NameLengthExceeded(
+                    label.len(),
+                    String::from_utf8_lossy(label).to_string(),
+                ));
+            }
+        }
+        let len = self.wire_len();
+        if len > MAX_NAME_LENGTH {
+            return Err(MessageError::NameLengthExceeded(len, self.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Encodes the name into the buffer, replacing the longest suffix that has
+    /// already been written to the message with a compression pointer.
+    #[instrument(skip(buf, names))]
+    pub(crate) fn to_bytes(
+        &self,
+        buf: &mut dyn Writer,
+        names: &mut NameCompressor,
+    ) -> Result<usize> {
+        if names.disabled {
+            return self.to_bytes_uncompressed(buf);
+        }
+        self.validate()?;
+
+        let mut byte_count = 0;
+        for i in 0..self.labels.len() {
+            let suffix = &self.labels[i..];
+            if let Some(offset) = names.names.get(suffix) {
+                trace!("Compressing {} to pointer at {}", self, offset);
+                buf.write(&(0xc000 | offset).to_be_bytes())?;
+                return Ok(byte_count + 2);
+            }
+
+            let offset = buf.len() - names.base;
+            if offset <= MAX_POINTER_OFFSET {
+                names.names.insert(suffix.to_vec(), offset as u16);
+            }
+
+            let label = &self.labels[i];
+            buf.write(&[label.len() as u8])?;
+            buf.write(label)?;
+            byte_count += label.len() + 1;
+        }
+        buf.write(&[0])?;
+        byte_count += 1;
+        Ok(byte_count)
+    }
+
+    /// Encodes the name into the buffer without any compression, as required
+    /// for the names in the RDATA of types newer than RFC1035.
+    pub(crate) fn to_bytes_uncompressed(&self, buf: &mut dyn Writer) -> Result<usize> {
+        self.validate()?;
+
+        for label in self.labels.iter() {
+            buf.write(&[label.len() as u8])?;
+            buf.write(label)?;
+        }
+        buf.write(&[0])?;
+        Ok(self.wire_len())
+    }
+
+    /// Parses the presentation format of a name, unescaping `\X` and `\DDD`
+    /// sequences. When `strict` is false malformed escapes are kept as
+    /// literals and empty labels are skipped.
+    fn parse(s: &str, strict: bool) -> Result<Self> {
+        let invalid =
+            |reason: &str| MessageError::InvalidName(format!("{} in name: {}", reason, s));
+
+        if s == "." || s.is_empty() {
+            return Ok(DomainName::root());
+        }
+
+        let mut labels = Vec::new();
+        let mut label = Vec::new();
+        let bytes = s.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'.' => {
+                    if label.is_empty() {
+                        if strict {
+                            return Err(invalid("Empty label"));
+                        }
+                    } else {
+                        labels.push(std::mem::take(&mut label));
+                    }
+                    i += 1;
+                }
+                b'\\' => {
+                    let digits = bytes
+                        .get(i + 1..i + 4)
+                        .filter(|d| d.iter().all(u8::is_ascii_digit));
+                    if let Some(digits) = digits {
+                        let val = digits
+                            .iter()
+                            .fold(0u16, |acc, d| acc * 10 + (d - b'0') as u16);
+                        if val <= 255 {
+                            label.push(val as u8);
+                            i += 4;
+                            continue;
+                        }
+                        if strict {
+                            return Err(invalid("Escaped value out of range"));
+                        }
+                        label.push(b'\\');
+                        i += 1;
+                    } else if let Some(c) = bytes.get(i + 1) {
+                        label.push(*c);
+                        i += 2;
+                    } else {
+                        if strict {
+                            return Err(invalid("Trailing backslash"));
+                        }
+                        label.push(b'\\');
+                        i += 1;
+                    }
+                }
+                c => {
+                    label.push(c);
+                    i += 1;
+                }
+            }
+        }
+        if !label.is_empty() {
+            labels.push(label);
+        }
+
+        let name = DomainName { labels };
+        if strict {
+            name.validate()?;
+        }
+        Ok(name)
+    }
+}
+
+impl fmt::Display for DomainName {
+    /// Writes the escaped presentation format of the name, without the
+    /// trailing dot (see [`DomainName::to_fqdn`]). The root is written as `.`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        if self.is_root() {
+            return write!(f, ".");
+        }
+        for (i, label) in self.labels.iter().enumerate() {
+            if i != 0 {
+                write!(f, ".")?;
+            }
+            for b in label.iter() {
+                match b {
+                    b'.' | b'\\' | b'"' | b'(' | b')' | b';' | b'@' | b'$' => {
+                        write!(f, "\\{}", *b as char)?
+                    }
+                    0x21..=0x7e => write!(f, "{}", *b as char)?,
+                    _ => write!(f, "\\{:03}", b)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DomainName {
+    type Err = MessageError;
+
+    /// Parses the presentation format of the name, validating the label and
+    /// name lengths.
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s, true)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DomainName {
+    /// Serializes the fully qualified presentation format of the name.
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_fqdn())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DomainName {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<&str> for DomainName {
+    fn from(s: &str) -> Self {
+        // Lenient parsing never fails.
+        Self::parse(s, false).unwrap_or_default()
+    }
+}
+
+impl From<String> for DomainName {
+    fn from(s: String) -> Self {
+        DomainName::from(s.as_str())
+    }
+}
+
+impl PartialEq for DomainName {
+    fn eq(&self, other: &Self) -> bool {
+        self.labels.len() == other.labels.len()
+            && self
+                .labels
+                .iter()
+                .zip(other.labels.iter())
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+}
+
+impl Eq for DomainName {}
+
+impl PartialEq<str> for DomainName {
+    fn eq(&self, other: &str) -> bool {
+        DomainName::parse(other, false).is_ok_and(|other| *self == other)
+    }
+}
+
+impl PartialEq<&str> for DomainName {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl PartialOrd for DomainName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DomainName {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical_cmp(other)
+    }
+}
+
+impl Hash for DomainName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.labels.len().hash(state);
+        for label in self.labels.iter() {
+            label.len().hash(state);
+            for b in label.iter() {
+                b.to_ascii_lowercase().hash(state);
+            }
+        }
+    }
+}
+
+/// Tracks the names (and their suffixes) that have been written into a
+/// message, so that later occurrences can be replaced with RFC1035 Message
+/// Compression pointers.
+#[derive(Debug, Default)]
+pub(crate) struct NameCompressor {
+    /// The position in the buffer that the message starts at, pointers are
+    /// relative to the start of the message.
+    base: usize,
+
+    /// Suffixes are matched exactly (not case-insensitively) so that the case
+    /// of every name is preserved.
+    names: HashMap<Vec<Vec<u8>>, u16>,
+
+    /// Nothing is compressed, for rdata that has to stand on its own.
+    disabled: bool,
+}
+
+impl NameCompressor {
+    pub(crate) fn new(base: usize) -> Self {
+        Self {
+            base,
+            names: HashMap::new(),
+            disabled: false,
+        }
+    }
+
+    /// A compressor that writes every name in full.
+    pub(crate) fn disabled() -> Self {
+        Self {
+            disabled: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        let name: DomainName = "www.Example.com.".parse().unwrap();
+        assert_eq!(name.labels().len(), 3);
+        assert_eq!(name.to_string(), "www.Example.com");
+        assert_eq!(name.to_fqdn(), "www.Example.com.");
+
+        let name: DomainName = r"a\.b.c\\d.\000.\255".parse().unwrap();
+        assert_eq!(
+            name.labels(),
+            &[b"a.b".to_vec(), b"c\\d".to_vec(), vec![0], vec![255]]
+        );
+        assert_eq!(name.to_string(), r"a\.b.c\\d.\000.\255");
+
+        assert!(DomainName::root().is_root());
+        assert_eq!(DomainName::root().to_string(), ".");
+        assert_eq!(DomainName::root().to_fqdn(), ".");
+        assert_eq!("".parse::<DomainName>().unwrap(), DomainName::root());
+        assert_eq!(".".parse::<DomainName>().unwrap(), DomainName::root());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!("a..b".parse::<DomainName>().is_err());
+        assert!(r"a\256".parse::<DomainName>().is_err());
+        assert!("a\\".parse::<DomainName>().is_err());
+        assert!("a".repeat(64).parse::<DomainName>().is_err());
+        assert!("a".repeat(63).parse::<DomainName>().is_ok());
+
+        // 4 labels of 63 octets plus length octets and root is 257 octets.
+        let long = vec!["a".repeat(63); 4].join(".");
+        assert!(long.parse::<DomainName>().is_err());
+        let long = vec!["a".repeat(62); 4].join(".");
+        assert_eq!(long.parse::<DomainName>().unwrap().wire_len(), 253);
+
+        // Lenient conversion still produces a name, but it can't be written.
+        let name = DomainName::from("a".repeat(64).as_str());
+        let mut buf = Vec::new();
+        assert!(name
+            .to_bytes(&mut buf, &mut NameCompressor::new(0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_equality() {
+        let a: DomainName = "WWW.example.COM".parse().unwrap();
+        let b: DomainName = "www.EXAMPLE.com.".parse().unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a, "www.example.com.");
+        assert_ne!(a, "example.com");
+
+        use std::collections::hash_map::DefaultHasher;
+        let hash = |n: &DomainName| {
+            let mut h = DefaultHasher::new();
+            n.hash(&mut h);
+            h.finish()
+        };
+        assert_eq!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let name: DomainName = "www.example.com".parse().unwrap();
+        let zone: DomainName = "Example.Com.".parse().unwrap();
+        assert!(name.is_subdomain_of(&zone));
+        assert!(zone.is_subdomain_of(&zone));
+        assert!(name.is_subdomain_of(&DomainName::root()));
+        assert!(!zone.is_subdomain_of(&name));
+        assert!(!name.is_subdomain_of(&"ample.com".parse().unwrap()));
+
+        assert_eq!(name.parent().unwrap(), zone);
+        assert_eq!(zone.parent().unwrap().parent().unwrap(), DomainName::root());
+        assert_eq!(DomainName::root().parent(), None);
+    }
+
+    #[test]
+    fn test_canonical_order() {
+        // RFC4034 section 6.1 - the names in the canonical order.
+        let ordered = [
+            "example",
+            "a.example",
+            "yljkjljk.a.example",
+            "Z.a.example",
+            "zABC.a.EXAMPLE",
+            "z.example",
+            "\\001.z.example",
+            "*.z.example",
+            "\\200.z.example",
+        ];
+        let names: Vec<DomainName> = ordered.iter().map(|n| n.parse().unwrap()).collect();
+        let mut sorted = names.clone();
+        sorted.reverse();
+        sorted.sort();
+        assert_eq!(sorted, names);
+
+        for pair in names.windows(2) {
+            assert_eq!(pair[0].canonical_cmp(&pair[1]), Ordering::Less);
+        }
+        let a: DomainName = "WWW.example.com".parse().unwrap();
+        let b: DomainName = "www.Example.COM".parse().unwrap();
+        assert_eq!(a.canonical_cmp(&b), Ordering::Equal);
+        assert_eq!(DomainName::root().canonical_cmp(&a), Ordering::Less);
+    }
+
+    #[test]
+    fn test_binary_label_round_trip() {
+        let name = DomainName::from_labels(vec![vec![0u8, b'.', 0xff], b"com".to_vec()]).unwrap();
+        let mut buf = Vec::new();
+        let len = name
+            .to_bytes(&mut buf, &mut NameCompressor::new(0))
+            .unwrap();
+        assert_eq!(len, 9);
+        assert_eq!(buf, vec![3, 0, b'.', 0xff, 3, b'c', b'o', b'm', 0]);
+
+        let presentation = name.to_string();
+        assert_eq!(presentation, r"\000\.\255.com");
+        let parsed: DomainName = presentation.parse().unwrap();
+        assert_eq!(parsed.labels(), name.labels());
+    }
+}
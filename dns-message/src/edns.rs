@@ -0,0 +1,540 @@
+use crate::resource_record::fmt_hex;
+use crate::{MessageError, RCode, Result, Writer};
+use std::default::Default;
+use std::fmt;
+use std::net::IpAddr;
+use tracing::{instrument, trace};
+
+/// RFC6891 - the DO (DNSSEC OK) bit in the flags of the OPT record.
+const DNSSEC_OK: u16 = 0x8000;
+
+/// The OPTION-CODEs of the options that are understood.
+const NSID: u16 = 3;
+const CLIENT_SUBNET: u16 = 8;
+const COOKIE: u16 = 10;
+const TCP_KEEPALIVE: u16 = 11;
+const PADDING: u16 = 12;
+const EXTENDED_ERROR: u16 = 15;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// The EDNS(0) information carried in the OPT pseudo-record (RFC6891).
+///
+/// The OPT record is taken out of the additional records section when a
+/// [`crate::Message`] is parsed, and written back as the last additional record
+/// when it is serialized.
+///
+/// The upper 8 bits of the extended RCODE are combined with the 4 bits in the
+/// header, so the full value is available as [`crate::Header::rcode`].
+pub struct Edns {
+    /// The number of octets of the largest UDP payload that can be reassembled
+    /// and delivered in the requestor's network stack.
+    pub udp_payload_size: u16,
+
+    /// The version of the implementation, 0 for RFC6891.
+    pub version: u8,
+
+    /// RFC3225 - DNSSEC OK - indicates that the resolver is able to accept
+    /// DNSSEC security RRs.
+    pub dnssec_ok: bool,
+
+    /// The remaining 15 bits of the flags, which are currently reserved and
+    /// should be zero.
+    pub z: u16,
+
+    /// The options contained in the RDATA of the OPT record.
+    pub options: Vec<EdnsOption>,
+}
+
+impl Edns {
+    /// Creates the OPT information from the CLASS, TTL and RDATA of the OPT
+    /// record, returning it along with the upper 8 bits of the extended RCODE.
+    #[instrument(skip(rdata))]
+    pub(crate) fn from_opt(class: u16, ttl: u32, rdata: &[u8]) -> Result<(Edns, u8)> {
+        let extended_rcode = (ttl >> 24) as u8;
+        let version = (ttl >> 16) as u8;
+        let flags = ttl as u16;
+
+        let mut options = Vec::new();
+        let mut i = rdata;
+        while !i.is_empty() {
+            if i.len() < 4 {
+                return Err(MessageError::InvalidOpt("Truncated option".to_string()));
+            }
+            let code = u16::from_be_bytes([i[0], i[1]]);
+            let len = u16::from_be_bytes([i[2], i[3]]) as usize;
+            if i.len() < 4 + len {
+                return Err(MessageError::InvalidOpt(format!(
+                    "Truncated data for option {}",
+                    code
+                )));
+            }
+            options.push(EdnsOption::from_bytes(code, &i[4..4 + len])?);
+            i = &i[4 + len..];
+        }
+
+        let edns = Edns {
+            udp_payload_size: class,
+            version,
+            dnssec_ok: flags & DNSSEC_OK != 0,
+            z: flags & !DNSSEC_OK,
+            options,
+        };
+        trace!("Parsed OPT as {}", edns);
+
+        Ok((edns, extended_rcode))
+    }
+
+    /// Writes the OPT record, including the upper 8 bits of the RCODE.
+    #[instrument(skip(buf))]
+    pub(crate) fn to_bytes(&self, rcode: &RCode, buf: &mut dyn Writer) -> Result<usize> {
+        let start = buf.len();
+
+        // The owner name is always the root.
+        buf.write(&[0])?;
+        buf.write(&41u16.to_be_bytes())?;
+        buf.write(&self.udp_payload_size.to_be_bytes())?;
+
+        let mut flags = self.z & !DNSSEC_OK;
+        if self.dnssec_ok {
+            flags |= DNSSEC_OK;
+        }
+        buf.write(&[(rcode.as_u16() >> 4) as u8])?;
+        buf.write(&[self.version])?;
+        buf.write(&flags.to_be_bytes())?;
+
+        let rdlength_pos = buf.len();
+        buf.write(&[0, 0])?;
+        for option in self.options.iter() {
+            option.to_bytes(buf)?;
+        }
+        let rdlength = buf.len() - rdlength_pos - 2;
+        if rdlength > u16::MAX as usize {
+            return Err(MessageError::InvalidOpt("Options are too long".to_string()));
+        }
+        buf.write_at(rdlength_pos, &(rdlength as u16).to_be_bytes());
+
+        let byte_count = buf.len() - start;
+        trace!("Wrote {} bytes", byte_count);
+
+        Ok(byte_count)
+    }
+}
+
+impl Default for Edns {
+    /// Advertises a UDP payload size of 1232, as recommended by DNS Flag Day
+    /// 2020 to avoid IP fragmentation.
+    fn default() -> Self {
+        Edns {
+            udp_payload_size: 1232,
+            version: 0,
+            dnssec_ok: false,
+            z: 0,
+            options: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Edns {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        write!(
+            f,
+            "EDNS(version: {}, udp: {}, do: {}",
+            self.version, self.udp_payload_size, self.dnssec_ok
+        )?;
+        for option in self.options.iter() {
+            write!(f, ", {}", option)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// An option in the RDATA of the OPT record.
+pub enum EdnsOption {
+    /// RFC5001 - (3) Name Server Identifier - empty in a request, and holds
+    /// the identifier of the server in a response.
+    Nsid(Vec<u8>),
+
+    /// RFC7871 - (8) Client Subnet:
+    /// - SOURCE PREFIX-LENGTH - the leftmost number of significant bits of
+    ///   the address.
+    /// - SCOPE PREFIX-LENGTH - the leftmost number of bits of the address the
+    ///   response covers, 0 in queries.
+    /// - ADDRESS - the address, only the bits covered by the source prefix are
+    ///   sent.
+    ClientSubnet(u8, u8, IpAddr),
+
+    /// RFC7873 - (10) Cookie:
+    /// - Client Cookie - 8 octets.
+    /// - Server Cookie - between 8 and 32 octets, if known to the client.
+    Cookie([u8; 8], Option<Vec<u8>>),
+
+    /// RFC7828 - (11) TCP Keepalive - the idle timeout in units of 100
+    /// milliseconds, which is only present in responses.
+    TcpKeepalive(Option<u16>),
+
+    /// RFC7830 - (12) Padding - the octets of padding, which should be zero
+    /// but are kept as they were received.
+    Padding(Vec<u8>),
+
+    /// RFC8914 - (15) Extended DNS Error:
+    /// - INFO-CODE - the extended error code, see [`EdnsOption::ede_purpose`].
+    /// - EXTRA-TEXT - additional information for humans, UTF-8 that may be
+    ///   NUL terminated, kept as the octets that were received.
+    ExtendedError(u16, Vec<u8>),
+
+    /// An option that isn't known - the code and raw data are contained
+    /// within.
+    Unknown(u16, Vec<u8>),
+}
+
+impl EdnsOption {
+    /// The OPTION-CODE of this option.
+    pub fn code(&self) -> u16 {
+        match self {
+            EdnsOption::Nsid(_) => NSID,
+            EdnsOption::ClientSubnet(..) => CLIENT_SUBNET,
+            EdnsOption::TcpKeepalive(_) => TCP_KEEPALIVE,
+            EdnsOption::Padding(_) => PADDING,
+            EdnsOption::Cookie(..) => COOKIE,
+            EdnsOption::ExtendedError(..) => EXTENDED_ERROR,
+            EdnsOption::Unknown(code, _) => *code,
+        }
+    }
+
+    /// Decodes the OPTION-DATA of the option with the given code.
+    pub fn from_bytes(code: u16, data: &[u8]) -> Result<EdnsOption> {
+        let invalid = |reason: &str| {
+            MessageError::InvalidOpt(format!("Invalid data for option {}: {}", code, reason))
+        };
+
+        let option = match code {
+            NSID => EdnsOption::Nsid(data.to_vec()),
+            CLIENT_SUBNET => {
+                if data.len() < 4 {
+                    return Err(invalid("too short"));
+                }
+                let family = u16::from_be_bytes([data[0], data[1]]);
+                let source = data[2];
+                let scope = data[3];
+                let address = &data[4..];
+                let max_prefix = match family {
+                    1 => 32,
+                    2 => 128,
+                    _ => return Err(invalid("unknown address family")),
+                };
+                if source > max_prefix || scope > max_prefix {
+                    return Err(invalid("prefix length too long"));
+                }
+                if address.len() != (source as usize).div_ceil(8) {
+                    return Err(invalid("address length does not match the prefix"));
+                }
+                let address = if family == 1 {
+                    let mut v4 = [0u8; 4];
+                    v4[..address.len()].copy_from_slice(address);
+                    IpAddr::from(v4)
+                } else {
+                    let mut v6 = [0u8; 16];
+                    v6[..address.len()].copy_from_slice(address);
+                    IpAddr::from(v6)
+                };
+                EdnsOption::ClientSubnet(source, scope, address)
+            }
+            TCP_KEEPALIVE => match data.len() {
+                0 => EdnsOption::TcpKeepalive(None),
+                2 => EdnsOption::TcpKeepalive(Some(u16::from_be_bytes([data[0], data[1]]))),
+                _ => return Err(invalid("timeout must be 2 octets")),
+            },
+            PADDING => EdnsOption::Padding(data.to_vec()),
+            COOKIE => {
+                if data.len() != 8 && !(16..=40).contains(&data.len()) {
+                    return Err(invalid("bad cookie length"));
+                }
+                let mut client = [0u8; 8];
+                client.copy_from_slice(&data[..8]);
+                let server = if data.len() > 8 {
+                    Some(data[8..].to_vec())
+                } else {
+                    None
+                };
+                EdnsOption::Cookie(client, server)
+            }
+            EXTENDED_ERROR => {
+                if data.len() < 2 {
+                    return Err(invalid("too short"));
+                }
+                let info_code = u16::from_be_bytes([data[0], data[1]]);
+                EdnsOption::ExtendedError(info_code, data[2..].to_vec())
+            }
+            _ => EdnsOption::Unknown(code, data.to_vec()),
+        };
+        trace!("Decoded option {}", option);
+        Ok(option)
+    }
+
+    /// Writes the option, including the OPTION-CODE and OPTION-LENGTH,
+    /// returning the number of bytes written.
+    pub fn to_bytes(&self, buf: &mut dyn Writer) -> Result<usize> {
+        let start = buf.len();
+        buf.write(&self.code().to_be_bytes())?;
+        buf.write(&[0, 0])?;
+
+        match self {
+            EdnsOption::Nsid(id) => buf.write(id)?,
+            EdnsOption::ClientSubnet(source, scope, address) => {
+                let (family, octets, max_prefix) = match address {
+                    IpAddr::V4(a) => (1u16, a.octets().to_vec(), 32),
+                    IpAddr::V6(a) => (2u16, a.octets().to_vec(), 128),
+                };
+                if *source > max_prefix || *scope > max_prefix {
+                    return Err(MessageError::InvalidOpt(format!(
+                        "Client subnet prefix too long for {}",
+                        address
+                    )));
+                }
+                buf.write(&family.to_be_bytes())?;
+                buf.write(&[*source])?;
+                buf.write(&[*scope])?;
+
+                // Only send the bits covered by the source prefix, the rest
+                // must be zero.
+                let mut octets = octets[..(*source as usize).div_ceil(8)].to_vec();
+                if source % 8 != 0 {
+                    if let Some(last) = octets.last_mut() {
+                        *last &= 0xffu8 << (8 - source % 8);
+                    }
+                }
+                buf.write(&octets)?;
+            }
+            EdnsOption::TcpKeepalive(timeout) => {
+                if let Some(timeout) = timeout {
+                    buf.write(&timeout.to_be_bytes())?;
+                }
+            }
+            EdnsOption::Padding(padding) => buf.write(padding)?,
+            EdnsOption::Cookie(client, server) => {
+                buf.write(client)?;
+                if let Some(server) = server {
+                    if !(8..=32).contains(&server.len()) {
+                        return Err(MessageError::InvalidOpt(format!(
+                            "Server cookie must be between 8 and 32 octets, not {}",
+                            server.len()
+                        )));
+                    }
+                    buf.write(server)?;
+                }
+            }
+            EdnsOption::ExtendedError(info_code, text) => {
+                buf.write(&info_code.to_be_bytes())?;
+                buf.write(text)?;
+            }
+            EdnsOption::Unknown(_, data) => buf.write(data)?,
+        }
+
+        let len = buf.len() - start - 4;
+        if len > u16::MAX as usize {
+            return Err(MessageError::InvalidOpt(format!(
+                "Data for option {} is too long",
+                self.code()
+            )));
+        }
+        buf.write_at(start + 2, &(len as u16).to_be_bytes());
+
+        Ok(buf.len() - start)
+    }
+
+    /// The purpose of an Extended DNS Error INFO-CODE, as registered by
+    /// RFC8914.
+    pub fn ede_purpose(info_code: u16) -> Option<&'static str> {
+        let purpose = match info_code {
+            0 => "Other Error",
+            1 => "Unsupported DNSKEY Algorithm",
+            2 => "Unsupported DS Digest Type",
+            3 => "Stale Answer",
+            4 => "Forged Answer",
+            5 => "DNSSEC Indeterminate",
+            6 => "DNSSEC Bogus",
+            7 => "Signature Expired",
+            8 => "Signature Not Yet Valid",
+            9 => "DNSKEY Missing",
+            10 => "RRSIGs Missing",
+            11 => "No Zone Key Bit Set",
+            12 => "NSEC Missing",
+            13 => "Cached Error",
+            14 => "Not Ready",
+            15 => "Blocked",
+            16 => "Censored",
+            17 => "Filtered",
+            18 => "Prohibited",
+            19 => "Stale NXDomain Answer",
+            20 => "Not Authoritative",
+            21 => "Not Supported",
+            22 => "No Reachable Authority",
+            23 => "Network Error",
+            24 => "Invalid Data",
+            _ => return None,
+        };
+        Some(purpose)
+    }
+}
+
+impl fmt::Display for EdnsOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            EdnsOption::Nsid(id) => {
+                write!(f, "NSID(")?;
+                fmt_hex(f, id)?;
+                if !id.is_empty() && id.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+                    write!(f, " \"{}\"", String::from_utf8_lossy(id))?;
+                }
+                write!(f, ")")
+            }
+            EdnsOption::ClientSubnet(source, scope, address) => {
+                write!(f, "ECS({}/{}/{})", address, source, scope)
+            }
+            EdnsOption::TcpKeepalive(Some(timeout)) => write!(f, "KEEPALIVE({})", timeout),
+            EdnsOption::TcpKeepalive(None) => write!(f, "KEEPALIVE"),
+            EdnsOption::Padding(padding) => write!(f, "PADDING({})", padding.len()),
+            EdnsOption::Cookie(client, server) => {
+                write!(f, "COOKIE(")?;
+                fmt_hex(f, client)?;
+                if let Some(server) = server {
+                    write!(f, " ")?;
+                    fmt_hex(f, server)?;
+                }
+                write!(f, ")")
+            }
+            EdnsOption::ExtendedError(info_code, text) => {
+                write!(f, "EDE({}", info_code)?;
+                if let Some(purpose) = EdnsOption::ede_purpose(*info_code) {
+                    write!(f, " ({})", purpose)?;
+                }
+                // The EXTRA-TEXT may be NUL terminated, which isn't shown.
+                let text = text.strip_suffix(&[0]).unwrap_or(text);
+                if !text.is_empty() {
+                    write!(f, ": {:?}", String::from_utf8_lossy(text))?;
+                }
+                write!(f, ")")
+            }
+            EdnsOption::Unknown(code, data) => {
+                write!(f, "OPT{}(", code)?;
+                fmt_hex(f, data)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn round_trip(option: EdnsOption) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let len = option.to_bytes(&mut buf).unwrap();
+        assert_eq!(len, buf.len());
+        let code = u16::from_be_bytes([buf[0], buf[1]]);
+        assert_eq!(code, option.code());
+        assert_eq!(u16::from_be_bytes([buf[2], buf[3]]) as usize, len - 4);
+        assert_eq!(EdnsOption::from_bytes(code, &buf[4..]).unwrap(), option);
+        buf
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip(EdnsOption::Nsid(b"gpdns-syd".to_vec()));
+        round_trip(EdnsOption::Nsid(Vec::new()));
+        round_trip(EdnsOption::TcpKeepalive(None));
+        round_trip(EdnsOption::TcpKeepalive(Some(1200)));
+        round_trip(EdnsOption::Cookie([1, 2, 3, 4, 5, 6, 7, 8], None));
+        round_trip(EdnsOption::Cookie(
+            [1, 2, 3, 4, 5, 6, 7, 8],
+            Some(vec![9; 16]),
+        ));
+        round_trip(EdnsOption::ExtendedError(18, b"blocked by policy".to_vec()));
+        round_trip(EdnsOption::Unknown(65001, vec![0xde, 0xad]));
+
+        let buf = round_trip(EdnsOption::Padding(vec![0; 5]));
+        assert_eq!(&buf[4..], &[0; 5]);
+
+        // Options are written back as they were received, even when they're
+        // not quite what they should be.
+        round_trip(EdnsOption::Padding(vec![0, 1, 0]));
+        round_trip(EdnsOption::ExtendedError(18, b"blocked\0".to_vec()));
+        round_trip(EdnsOption::ExtendedError(18, vec![0xff, 0xfe]));
+    }
+
+    #[test]
+    fn test_client_subnet() {
+        let buf = round_trip(EdnsOption::ClientSubnet(
+            24,
+            0,
+            IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)),
+        ));
+        assert_eq!(&buf[4..], &[0, 1, 24, 0, 192, 0, 2]);
+
+        round_trip(EdnsOption::ClientSubnet(
+            56,
+            48,
+            IpAddr::V6("2001:db8:1:200::".parse::<Ipv6Addr>().unwrap()),
+        ));
+
+        // Bits outside of the source prefix are not sent.
+        let mut buf = Vec::new();
+        EdnsOption::ClientSubnet(20, 0, IpAddr::V4(Ipv4Addr::new(192, 0, 255, 255)))
+            .to_bytes(&mut buf)
+            .unwrap();
+        assert_eq!(&buf[4..], &[0, 1, 20, 0, 192, 0, 0xf0]);
+
+        // Address longer than the prefix.
+        assert!(EdnsOption::from_bytes(8, &[0, 1, 8, 0, 192, 0]).is_err());
+        // Unknown family.
+        assert!(EdnsOption::from_bytes(8, &[0, 3, 0, 0]).is_err());
+        // Prefix too long.
+        let mut buf = Vec::new();
+        assert!(
+            EdnsOption::ClientSubnet(33, 0, IpAddr::V4(Ipv4Addr::LOCALHOST))
+                .to_bytes(&mut buf)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_invalid_lengths() {
+        assert!(EdnsOption::from_bytes(10, &[1, 2, 3]).is_err());
+        assert!(EdnsOption::from_bytes(10, &[0; 12]).is_err());
+        assert!(EdnsOption::from_bytes(11, &[1]).is_err());
+        assert!(EdnsOption::from_bytes(15, &[1]).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            EdnsOption::Nsid(b"gpdns-syd".to_vec()).to_string(),
+            r#"NSID(6770646E732D737964 "gpdns-syd")"#
+        );
+        assert_eq!(
+            EdnsOption::ClientSubnet(24, 0, IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0))).to_string(),
+            "ECS(192.0.2.0/24/0)"
+        );
+        assert_eq!(
+            EdnsOption::ExtendedError(15, Vec::new()).to_string(),
+            "EDE(15 (Blocked))"
+        );
+        assert_eq!(
+            EdnsOption::ExtendedError(100, b"custom\0".to_vec()).to_string(),
+            r#"EDE(100: "custom")"#
+        );
+        assert_eq!(
+            EdnsOption::Cookie([0xab; 8], None).to_string(),
+            "COOKIE(ABABABABABABABAB)"
+        );
+        assert_eq!(
+            EdnsOption::Unknown(65001, vec![1]).to_string(),
+            "OPT65001(01)"
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Text encodings used in the presentation format of records.
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// RFC4648 - base64 encoding, with padding.
+pub(crate) fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+const BASE32HEX_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+/// RFC4648 - base32 encoding with the extended hex alphabet, without padding
+/// as used by RFC5155.
+pub(crate) fn base32hex_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity((data.len() * 8).div_ceil(5));
+    for chunk in data.chunks(5) {
+        let mut b = [0u8; 5];
+        b[..chunk.len()].copy_from_slice(chunk);
+        let n = b.iter().fold(0u64, |n, b| n << 8 | *b as u64);
+        for i in 0..(chunk.len() * 8).div_ceil(5) {
+            out.push(BASE32HEX_ALPHABET[(n >> (35 - 5 * i) & 0x1f) as usize] as char);
+        }
+    }
+    out
+}
+
+/// RFC4648 - base64 decoding, the padding is optional.
+pub(crate) fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    decode_bits(s, 6, |c| BASE64_ALPHABET.iter().position(|a| *a == c))
+}
+
+/// RFC4648 - base32 decoding with the extended hex alphabet, the padding is
+/// optional and either case is accepted.
+pub(crate) fn base32hex_decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    decode_bits(s, 5, |c| {
+        BASE32HEX_ALPHABET
+            .iter()
+            .position(|a| *a == c.to_ascii_uppercase())
+    })
+}
+
+/// Encodes the data as uppercase hexadecimal.
+#[cfg(feature = "serde")]
+pub(crate) fn hex_encode(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+/// Decodes hexadecimal in either case, which must be a whole number of
+/// octets.
+pub(crate) fn hex_decode(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    decode_bits(s, 4, |c| (c as char).to_digit(16).map(|d| d as usize))
+}
+
+/// Decodes characters that each hold `width` bits, failing if a character
+/// isn't in the alphabet or if there are left over bits that aren't zero.
+fn decode_bits<F>(s: &str, width: u32, value: F) -> Option<Vec<u8>>
+where
+    F: Fn(u8) -> Option<usize>,
+{
+    let mut out = Vec::with_capacity(s.len() * width as usize / 8);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in s.bytes() {
+        acc = acc << width | value(c)? as u32;
+        bits += width;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if bits >= width || acc != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_base64_encode() {
+        // RFC4648 test vectors.
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foo"), "Zm9v");
+        assert_eq!(base64_encode(b"foob"), "Zm9vYg==");
+        assert_eq!(base64_encode(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_base32hex_encode() {
+        // RFC4648 test vectors, without the padding.
+        assert_eq!(base32hex_encode(b""), "");
+        assert_eq!(base32hex_encode(b"f"), "CO");
+        assert_eq!(base32hex_encode(b"fo"), "CPNG");
+        assert_eq!(base32hex_encode(b"foo"), "CPNMU");
+        assert_eq!(base32hex_encode(b"foob"), "CPNMUOG");
+        assert_eq!(base32hex_encode(b"fooba"), "CPNMUOJ1");
+        assert_eq!(base32hex_encode(b"foobar"), "CPNMUOJ1E8");
+    }
+
+    #[test]
+    fn test_decode() {
+        for data in [&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar"].iter() {
+            assert_eq!(base64_decode(&base64_encode(data)).as_deref(), Some(*data));
+            assert_eq!(
+                base32hex_decode(&base32hex_encode(data)).as_deref(),
+                Some(*data)
+            );
+        }
+        assert_eq!(base64_decode("Zm9vYg"), Some(b"foob".to_vec()));
+        assert_eq!(base32hex_decode("cpnmu"), Some(b"foo".to_vec()));
+        assert_eq!(hex_decode("00fFa0"), Some(vec![0x00, 0xff, 0xa0]));
+
+        assert_eq!(base64_decode("Zm9v!"), None);
+        assert_eq!(base64_decode("Z"), None);
+        assert_eq!(base32hex_decode("CPNMW"), None);
+        assert_eq!(hex_decode("abc"), None);
+        assert_eq!(hex_decode("0g"), None);
+    }
+}
@@ -1,27 +1,325 @@
+use crate::DomainName;
 use std::error::Error;
 use std::fmt;
 
 #[derive(Debug)]
+/// The errors from parsing, building and serializing messages.
 pub enum MessageError {
+    /// Text, such as a type mnemonic or an RFC8427 member, couldn't be parsed.
     ParsingError(String),
+
+    /// A message from the wire couldn't be parsed, the [`ParseError`] says
+    /// where and why.
+    Parse(ParseError),
+
+    /// A value couldn't be encoded or decoded, such as a string that isn't
+    /// UTF-8.
     EncodingError(Box<dyn Error + Send + Sync>),
-    CircularReference(String),
+
+    /// The OpCode is reserved, and can't be written.
     ReservedOpCode,
+
+    /// The name, of the given wire length, is longer than 255 octets or has a
+    /// label longer than 63 octets.
     NameLengthExceeded(usize, String),
+
+    /// The name couldn't be parsed from its presentation format.
+    InvalidName(String),
+
+    /// A `<character-string>` of the given length is longer than 255 octets.
+    CharacterStringLengthExceeded(usize),
+
+    /// The SvcParams of an SVCB or HTTPS record aren't valid.
+    InvalidSvcParams(String),
+
+    /// The OPT record or one of its options isn't valid.
+    InvalidOpt(String),
+
+    /// The message isn't a valid RFC2136 dynamic update, the reason says why.
+    InvalidUpdate(String),
+
+    /// The CNAME records of a message loop back to the name.
+    CnameLoop(DomainName),
+
+    /// The RCODE needs more than 4 bits, which can only be written with EDNS.
+    ExtendedRCodeWithoutEdns(u16),
+
+    /// The message of the first size doesn't fit in the limit of the second,
+    /// even when truncated.
+    SizeLimitExceeded(usize, usize),
+
+    /// A TCP frame of the first length is longer than the limit of the second.
+    FrameLengthExceeded(usize, usize),
+
+    /// The buffer of the given size is full.
+    BufferTooSmall(usize),
+
+    /// A zone file couldn't be parsed at the line and column.
+    ZoneFile(usize, usize, String),
+
+    /// A file couldn't be read.
+    Io(std::io::Error),
 }
 
-impl Error for MessageError {}
+impl Error for MessageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            // These only add context to the errors they wrap, so they display
+            // the same and share the source.
+            MessageError::Parse(e) => e.source(),
+            MessageError::EncodingError(e) => e.source(),
+            MessageError::Io(e) => e.source(),
+            _ => None,
+        }
+    }
+}
 
 impl fmt::Display for MessageError {
     fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
-        write!(f, "InvalidMessageError: {}", self)?;
+        match self {
+            MessageError::ParsingError(reason) => write!(f, "{}", reason),
+            MessageError::Parse(e) => write!(f, "{}", e),
+            MessageError::EncodingError(e) => write!(f, "{}", e),
+            MessageError::ReservedOpCode => write!(f, "Reserved OpCode"),
+            MessageError::NameLengthExceeded(len, name) => {
+                write!(f, "Name of {} octets is too long: {}", len, name)
+            }
+            MessageError::InvalidName(reason) => write!(f, "{}", reason),
+            MessageError::CharacterStringLengthExceeded(len) => {
+                write!(f, "Character string of {} octets is too long", len)
+            }
+            MessageError::InvalidSvcParams(reason) => write!(f, "Invalid SvcParams: {}", reason),
+            MessageError::InvalidOpt(reason) => write!(f, "Invalid OPT record: {}", reason),
+            MessageError::InvalidUpdate(reason) => write!(f, "Invalid UPDATE: {}", reason),
+            MessageError::CnameLoop(name) => write!(f, "CNAME chain loops at {}", name),
+            MessageError::ExtendedRCodeWithoutEdns(rcode) => {
+                write!(f, "RCODE {} needs EDNS", rcode)
+            }
+            MessageError::SizeLimitExceeded(size, limit) => write!(
+                f,
+                "Message of {} octets doesn't fit in the limit of {}",
+                size, limit
+            ),
+            MessageError::FrameLengthExceeded(len, limit) => write!(
+                f,
+                "Frame of {} octets is longer than the limit of {}",
+                len, limit
+            ),
+            MessageError::BufferTooSmall(len) => {
+                write!(f, "Buffer of {} octets is too small", len)
+            }
+            MessageError::ZoneFile(line, column, reason) => {
+                write!(f, "{} at line {}, column {}", reason, line, column)
+            }
+            MessageError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl MessageError {
+    /// Fills in the parts of where a [`MessageError::Parse`] happened that
+    /// aren't already known, as the error is passed up from the part of the
+    /// message that failed. Other errors are returned as they are.
+    pub(crate) fn at(self, section: Option<Section>, index: Option<usize>, offset: usize) -> Self {
+        match self {
+            MessageError::Parse(mut e) => {
+                if e.section.is_none() {
+                    e.section = section;
+                    e.index = index;
+                }
+                e.offset.get_or_insert(offset);
+                MessageError::Parse(e)
+            }
+            e => e,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Where and why a message couldn't be parsed.
+pub struct ParseError {
+    /// The section of the message that was being read.
+    pub section: Option<Section>,
+
+    /// The index within the section of the question or record that was being
+    /// read.
+    pub index: Option<usize>,
+
+    /// The offset into the message of the octets that couldn't be parsed, or
+    /// of the start of the name or RDATA they were part of.
+    pub offset: Option<usize>,
+
+    /// Why the message couldn't be parsed.
+    pub kind: ParseErrorKind,
+
+    source: Option<Box<MessageError>>,
+}
+
+impl ParseError {
+    pub(crate) fn new(kind: ParseErrorKind) -> Self {
+        ParseError {
+            section: None,
+            index: None,
+            offset: None,
+            kind,
+            source: None,
+        }
+    }
+
+    /// Sets the offset of the octets that couldn't be parsed.
+    pub(crate) fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Sets the error that caused this one.
+    pub(crate) fn caused_by(mut self, source: MessageError) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|e| e as _)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        write!(f, "{}", self.kind)?;
+        if let Some(section) = self.section {
+            write!(f, " in the {}", section)?;
+        }
+        if let Some(index) = self.index {
+            write!(f, " at index {}", index)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
         Ok(())
     }
 }
 
-impl<E: std::fmt::Debug> From<nom::Err<E>> for MessageError {
-    fn from(error: nom::Err<E>) -> Self {
-        MessageError::ParsingError(format!("Parsing error: {}", error))
+impl From<ParseError> for MessageError {
+    fn from(error: ParseError) -> Self {
+        MessageError::Parse(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// The sections of a message.
+pub enum Section {
+    /// The fixed 12 octet header.
+    Header,
+    /// The question section.
+    Question,
+    /// The answer section.
+    Answer,
+    /// The authority section.
+    Authority,
+    /// The additional section, which holds the OPT record.
+    Additional,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            Section::Header => write!(f, "header"),
+            Section::Question => write!(f, "question section"),
+            Section::Answer => write!(f, "answer section"),
+            Section::Authority => write!(f, "authority section"),
+            Section::Additional => write!(f, "additional section"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Why a message couldn't be parsed.
+pub enum ParseErrorKind {
+    /// The message ends part way through the header, a question or a record.
+    Truncated,
+
+    /// The RDATA runs past the end of the message, or is too short for the
+    /// type of the record.
+    TruncatedRData,
+
+    /// The RDATA isn't valid for the type of the record, the source of the
+    /// error says why.
+    InvalidRData,
+
+    /// A label length octet uses one of the reserved label types, 0b01 or
+    /// 0b10 in the top two bits.
+    BadLabelLength(u8),
+
+    /// A compression pointer points past the end of the message.
+    PointerOutOfRange(u16),
+
+    /// A compression pointer leads back to a pointer that was already
+    /// followed.
+    PointerLoop(u16),
+
+    /// There is more than one OPT record, or it isn't valid. The source of the
+    /// error says why.
+    InvalidOpt,
+
+    /// The given number of octets are left over in the RDATA after the fields
+    /// of its type.
+    TrailingRData(usize),
+
+    /// The given number of octets are left over after the last record.
+    TrailingData(usize),
+
+    /// A name is longer than 255 octets.
+    NameTooLong(usize),
+
+    /// A compression pointer doesn't point to an earlier part of the message.
+    ForwardPointer(u16),
+
+    /// The reserved Z flag in the header is set.
+    ReservedFlag,
+
+    /// RFC4034 - A window of the type bitmap of an NSEC or NSEC3 record is
+    /// truncated, out of order, or has a bitmap of more than 32 octets.
+    BadTypeBitmap,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            ParseErrorKind::Truncated => write!(f, "Message is truncated"),
+            ParseErrorKind::TruncatedRData => write!(f, "RDATA is truncated"),
+            ParseErrorKind::InvalidRData => write!(f, "Invalid RDATA"),
+            ParseErrorKind::BadLabelLength(len) => write!(f, "Bad label length {:#04x}", len),
+            ParseErrorKind::PointerOutOfRange(ptr) => {
+                write!(f, "Name pointer {} is past the end of the message", ptr)
+            }
+            ParseErrorKind::PointerLoop(ptr) => write!(f, "Name pointer {} loops", ptr),
+            ParseErrorKind::InvalidOpt => write!(f, "Invalid OPT record"),
+            ParseErrorKind::TrailingRData(len) => write!(f, "{} octets after the RDATA", len),
+            ParseErrorKind::TrailingData(len) => write!(f, "{} octets after the message", len),
+            ParseErrorKind::NameTooLong(len) => write!(f, "Name of {} octets is too long", len),
+            ParseErrorKind::ForwardPointer(ptr) => {
+                write!(f, "Name pointer {} doesn't point backwards", ptr)
+            }
+            ParseErrorKind::ReservedFlag => write!(f, "Reserved Z flag is set"),
+            ParseErrorKind::BadTypeBitmap => write!(f, "Invalid type bitmap"),
+        }
+    }
+}
+
+impl From<ParseErrorKind> for MessageError {
+    fn from(kind: ParseErrorKind) -> Self {
+        MessageError::Parse(ParseError::new(kind))
+    }
+}
+
+impl<I> From<nom::Err<crate::parser::WireError<I>>> for MessageError {
+    fn from(error: nom::Err<crate::parser::WireError<I>>) -> Self {
+        match error {
+            nom::Err::Error(e) | nom::Err::Failure(e) => e.kind.into(),
+            nom::Err::Incomplete(_) => ParseErrorKind::Truncated.into(),
+        }
     }
 }
 
@@ -36,3 +334,50 @@ impl From<std::string::FromUtf8Error> for MessageError {
         MessageError::EncodingError(Box::new(error))
     }
 }
+
+impl From<std::io::Error> for MessageError {
+    fn from(error: std::io::Error) -> Self {
+        MessageError::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display_and_source() {
+        let error = MessageError::from(ParseError::new(ParseErrorKind::InvalidRData).caused_by(
+            MessageError::InvalidSvcParams("Truncated SvcParams".to_string()),
+        ))
+        .at(Some(Section::Answer), Some(2), 45);
+        assert_eq!(
+            error.to_string(),
+            "Invalid RDATA in the answer section at index 2 at offset 45"
+        );
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            "Invalid SvcParams: Truncated SvcParams"
+        );
+        assert!(error.source().unwrap().source().is_none());
+
+        // The location is only filled in once, by the innermost part that
+        // knows it.
+        let error = MessageError::from(ParseError::new(ParseErrorKind::Truncated).offset(7))
+            .at(Some(Section::Question), Some(0), 12)
+            .at(Some(Section::Answer), Some(1), 30);
+        match error {
+            MessageError::Parse(e) => {
+                assert_eq!(e.section, Some(Section::Question));
+                assert_eq!(e.index, Some(0));
+                assert_eq!(e.offset, Some(7));
+            }
+            e => panic!("Unexpected error {:?}", e),
+        }
+
+        assert_eq!(
+            MessageError::ZoneFile(3, 5, "Unterminated string".to_string()).to_string(),
+            "Unterminated string at line 3, column 5"
+        );
+    }
+}
@@ -1,8 +1,10 @@
-use crate::{Message, MessageError, Result};
+use crate::{parser, Message, MessageError, Result, Writer};
 use std::default::Default;
+use std::fmt;
 use tracing::{instrument, trace};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The DNS Message Header as per RFC1035 and RFC2535.
 pub struct Header {
     /// RFC1035 - A 16 bit identifier assigned by the program that generates any
@@ -38,6 +40,12 @@ pub struct Header {
     /// server.
     pub ra: bool,
 
+    /// RFC1035 - Reserved for future use, this bit must be zero in all queries
+    /// and responses. Unless a message is parsed strictly it's kept, so the
+    /// message is written back unchanged.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub z: bool,
+
     /// RFC2535 - The AD (authentic data) bit indicates in a response that all
     /// the data included in the answer and authority portion of the response
     /// has been authenticated by the server according to the policies of that
@@ -54,11 +62,20 @@ pub struct Header {
 }
 
 impl Header {
+    /// Reads only the header from the start of a message, such as to answer a
+    /// message whose questions or records can't be parsed.
+    ///
+    /// Without the OPT record the RCODE is only the lower 4 bits.
+    #[instrument(skip(input))]
+    pub fn from_bytes(input: &[u8]) -> Result<Header> {
+        let (header, _) = parser::read_header_counts(input)?;
+        Ok(header)
+    }
+
     #[instrument(skip(buf))]
-    pub(crate) fn to_bytes(&self, message: &Message, buf: &mut Vec<u8>) -> Result<usize> {
+    pub(crate) fn to_bytes(&self, message: &Message, buf: &mut dyn Writer) -> Result<usize> {
         let mut pair = self.id.to_be_bytes();
-        buf.push(pair[0]);
-        buf.push(pair[1]);
+        buf.write(&pair)?;
 
         let mut val = 0u8;
         if self.qr {
@@ -74,32 +91,38 @@ impl Header {
         if self.rd {
             val |= 1;
         }
-        buf.push(val);
+        buf.write(&[val])?;
         val = 0;
-        if self.rd {
+        if self.ra {
             val |= 1 << 7;
         }
+        if self.z {
+            val |= 1 << 6;
+        }
         if self.ad {
             val |= 1 << 5;
         }
         if self.cd {
             val |= 1 << 4;
         }
-        val |= self.rcode.as_u8();
-        buf.push(val);
+        // Only the lower 4 bits of the RCODE are in the header, the upper 8
+        // bits are in the OPT record.
+        let rcode = self.rcode.as_u16();
+        if rcode > 0xf && message.edns.is_none() {
+            return Err(MessageError::ExtendedRCodeWithoutEdns(rcode));
+        }
+        val |= (rcode & 0xf) as u8;
+        buf.write(&[val])?;
 
         pair = (message.questions.len() as u16).to_be_bytes();
-        buf.push(pair[0]);
-        buf.push(pair[1]);
+        buf.write(&pair)?;
         pair = (message.answers.len() as u16).to_be_bytes();
-        buf.push(pair[0]);
-        buf.push(pair[1]);
+        buf.write(&pair)?;
         pair = (message.name_servers.len() as u16).to_be_bytes();
-        buf.push(pair[0]);
-        buf.push(pair[1]);
-        pair = (message.additional_records.len() as u16).to_be_bytes();
-        buf.push(pair[0]);
-        buf.push(pair[1]);
+        buf.write(&pair)?;
+        pair =
+            (message.additional_records.len() as u16 + message.edns.is_some() as u16).to_be_bytes();
+        buf.write(&pair)?;
 
         trace!("Wrote 12 bytes");
 
@@ -107,11 +130,13 @@ impl Header {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A four bit field that specifies kind of query in this message.  This value
 /// is set by the originator of a query and copied into the response.
 pub enum OpCode {
     /// A standard query.
+    #[default]
     Query,
 
     /// An inverse query.
@@ -120,6 +145,12 @@ pub enum OpCode {
     /// A server status request.
     Status,
 
+    /// RFC1996 - (4) a notification that a zone has changed.
+    Notify,
+
+    /// RFC2136 - (5) a dynamic update, see [`crate::UpdateMessage`].
+    Update,
+
     /// An unknown OpCode (contained within).
     Unknown(u8),
 }
@@ -131,6 +162,8 @@ impl OpCode {
             OpCode::Query => Ok(0),
             OpCode::IQuery => Ok(1),
             OpCode::Status => Ok(2),
+            OpCode::Notify => Ok(4),
+            OpCode::Update => Ok(5),
             OpCode::Unknown(opcode) => {
                 if *opcode > 0xf {
                     // OpCodes can only be 4 bits wide.
@@ -143,16 +176,41 @@ impl OpCode {
     }
 }
 
-impl Default for OpCode {
-    fn default() -> Self {
-        OpCode::Query
+impl From<u8> for OpCode {
+    fn from(val: u8) -> Self {
+        match val {
+            0 => OpCode::Query,
+            1 => OpCode::IQuery,
+            2 => OpCode::Status,
+            4 => OpCode::Notify,
+            5 => OpCode::Update,
+            _ => OpCode::Unknown(val),
+        }
+    }
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            OpCode::Query => write!(f, "QUERY"),
+            OpCode::IQuery => write!(f, "IQUERY"),
+            OpCode::Status => write!(f, "STATUS"),
+            OpCode::Notify => write!(f, "NOTIFY"),
+            OpCode::Update => write!(f, "UPDATE"),
+            OpCode::Unknown(opcode) => write!(f, "RESERVED{}", opcode),
+        }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Response code - this 4 bit field is set as part of responses.
+///
+/// RFC6891 - when EDNS is in use the RCODE is extended to 12 bits, with the
+/// upper 8 bits held in the OPT record.
 pub enum RCode {
     /// No error condition.
+    #[default]
     NoError,
 
     /// Format error - The name server was unable to interpret the query.
@@ -178,13 +236,34 @@ pub enum RCode {
     /// data.
     Refused,
 
+    /// RFC2136 - (6) Some name that ought not to exist, does exist.
+    YXDomain,
+
+    /// RFC2136 - (7) Some RRset that ought not to exist, does exist.
+    YXRRSet,
+
+    /// RFC2136 - (8) Some RRset that ought to exist, does not exist.
+    NXRRSet,
+
+    /// RFC2136 - (9) The server is not authoritative for the zone named in the
+    /// Zone Section.
+    NotAuth,
+
+    /// RFC2136 - (10) A name used in the Prerequisite or Update Section is not
+    /// within the zone denoted by the Zone Section.
+    NotZone,
+
+    /// RFC6891 - (16) Bad OPT Version - the responder does not implement the
+    /// EDNS version of the request.
+    BadVers,
+
     /// The response code was unknown (contained within).
-    Unknown(u8),
+    Unknown(u16),
 }
 
 impl RCode {
     #[instrument]
-    pub(crate) fn as_u8(&self) -> u8 {
+    pub(crate) fn as_u16(&self) -> u16 {
         match self {
             RCode::NoError => 0,
             RCode::FormatError => 1,
@@ -192,13 +271,55 @@ impl RCode {
             RCode::NameError => 3,
             RCode::NotImplemented => 4,
             RCode::Refused => 5,
+            RCode::YXDomain => 6,
+            RCode::YXRRSet => 7,
+            RCode::NXRRSet => 8,
+            RCode::NotAuth => 9,
+            RCode::NotZone => 10,
+            RCode::BadVers => 16,
             RCode::Unknown(i) => *i,
         }
     }
 }
 
-impl Default for RCode {
-    fn default() -> Self {
-        RCode::NoError
+impl fmt::Display for RCode {
+    /// Writes the mnemonic of the response code, as used by dig.
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            RCode::NoError => write!(f, "NOERROR"),
+            RCode::FormatError => write!(f, "FORMERR"),
+            RCode::ServerFailure => write!(f, "SERVFAIL"),
+            RCode::NameError => write!(f, "NXDOMAIN"),
+            RCode::NotImplemented => write!(f, "NOTIMP"),
+            RCode::Refused => write!(f, "REFUSED"),
+            RCode::YXDomain => write!(f, "YXDOMAIN"),
+            RCode::YXRRSet => write!(f, "YXRRSET"),
+            RCode::NXRRSet => write!(f, "NXRRSET"),
+            RCode::NotAuth => write!(f, "NOTAUTH"),
+            RCode::NotZone => write!(f, "NOTZONE"),
+            RCode::BadVers => write!(f, "BADVERS"),
+            RCode::Unknown(rcode) => write!(f, "RCODE{}", rcode),
+        }
+    }
+}
+
+impl From<u16> for RCode {
+    #[instrument]
+    fn from(val: u16) -> Self {
+        match val {
+            0 => RCode::NoError,
+            1 => RCode::FormatError,
+            2 => RCode::ServerFailure,
+            3 => RCode::NameError,
+            4 => RCode::NotImplemented,
+            5 => RCode::Refused,
+            6 => RCode::YXDomain,
+            7 => RCode::YXRRSet,
+            8 => RCode::NXRRSet,
+            9 => RCode::NotAuth,
+            10 => RCode::NotZone,
+            16 => RCode::BadVers,
+            _ => RCode::Unknown(val),
+        }
     }
 }
@@ -7,50 +7,76 @@
 //!
 //! A [`Message`], [`Question`] and [`ResourceRecord`] can be built either
 //! manually, or with the [`MessageBuilder`], [`QuestionBuilder`] and
-//! [`ResourceRecordBuilder`] respectively.
+//! [`ResourceRecordBuilder`] respectively. A server starts the response to a
+//! query with [`Message::response_for`] or [`Message::error_response`].
+//!
+//! Domain names throughout the messages are held as [`DomainName`]s, which
+//! compare case-insensitively and sort in the RFC4034 canonical order. Records
+//! are grouped into an [`RRset`] by their name, class and type.
+//!
+//! Where only part of a message is needed, a [`MessageRef`] reads it lazily
+//! from the borrowed buffer without allocating.
+//!
+//! Errors are [`MessageError`]s, and a message that can't be parsed says which
+//! section, record and offset failed with a [`ParseError`].
+//!
+//! RFC2136 dynamic updates are built with an [`UpdateBuilder`], and their
+//! prerequisites and updates read with an [`UpdateMessage`].
+//!
+//! Records can be read from the zone file format with a [`ZoneParser`], and
+//! whole messages printed the way `dig` does with [`Message::dig`].
+//!
+//! With the `serde` feature the types can be serialized, and an
+//! `Rfc8427Message` converts messages to and from the RFC8427 JSON
+//! representation. With the `tokio-util` feature a `TcpCodec` reads and writes
+//! messages framed with the two byte length prefix used over TCP.
 mod builder;
+#[cfg(feature = "tokio-util")]
+mod codec;
+mod dig;
+mod domain_name;
+mod edns;
+mod encoding;
 mod error;
 mod header;
 mod message;
+mod message_ref;
 mod parser;
 mod question;
 mod resource_record;
+#[cfg(feature = "serde")]
+mod rfc8427;
+mod rrset;
+mod svcb;
+mod update;
+mod writer;
+mod zone;
 
-use error::MessageError;
-use tracing::instrument;
+use domain_name::NameCompressor;
 
-pub use builder::{MessageBuilder, QuestionBuilder, ResourceRecordBuilder};
+pub use builder::{MessageBuilder, QuestionBuilder, ResourceRecordBuilder, UpdateBuilder};
+#[cfg(feature = "tokio-util")]
+pub use codec::TcpCodec;
+pub use dig::Dig;
+pub use domain_name::DomainName;
+pub use edns::{Edns, EdnsOption};
+pub use error::{MessageError, ParseError, ParseErrorKind, Section};
 pub use header::{Header, OpCode, RCode};
 pub use message::Message;
+pub use message_ref::{Labels, MessageRef, NameRef, QuestionRef, Questions, RecordRef, Records};
+pub use parser::ParseOptions;
 pub use question::{Class, Question, Type};
 pub use resource_record::{RData, ResourceRecord};
+#[cfg(feature = "serde")]
+pub use rfc8427::{Rfc8427Message, Rfc8427Record};
+pub use rrset::RRset;
+pub use svcb::{SvcParam, SvcParams};
+pub use update::{Prerequisite, Update, UpdateMessage};
+pub use writer::{SliceWriter, Writer};
+pub use zone::ZoneParser;
 
 type Result<T> = std::result::Result<T, MessageError>;
 
-#[instrument(skip(buf))]
-pub(crate) fn encode_str(s: &str, buf: &mut Vec<u8>) -> Result<usize> {
-    let mut byte_count = 0;
-    let name_parts = s.split(".");
-    for name in name_parts {
-        if name.len() > 63 {
-            return Err(MessageError::NameLengthExceeded(
-                name.len(),
-                name.to_string(),
-            ));
-        }
-        let len = name.len() as u8;
-        buf.push(len);
-        byte_count += 1;
-        for b in name.bytes() {
-            buf.push(b);
-            byte_count += 1;
-        }
-    }
-    buf.push(0);
-    byte_count += 1;
-    Ok(byte_count)
-}
-
 #[cfg(test)]
 mod test {
     use std::sync::Once;
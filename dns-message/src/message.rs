@@ -1,8 +1,16 @@
-use crate::{parser, Header, Question, ResourceRecord, Result};
+use crate::{
+    parser, Dig, DomainName, Edns, Header, MessageError, NameCompressor, OpCode, ParseError,
+    ParseOptions, Question, RCode, RData, RRset, ResourceRecord, Result, Type, Writer,
+};
+use std::collections::HashSet;
 use std::fmt;
 use tracing::{instrument, trace};
 
+/// RFC1035 - the maximum size of a UDP message without EDNS.
+pub(crate) const UDP_PAYLOAD_SIZE: usize = 512;
+
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The DNS Message.
 ///
 /// This represents both the request and response to/from a DNS server, and can
@@ -22,6 +30,10 @@ pub struct Message {
 
     /// [`ResourceRecord`]s holding additional infomation.
     pub additional_records: Vec<ResourceRecord>,
+
+    /// The EDNS(0) information from the OPT pseudo-record, which is not
+    /// included in the `additional_records`.
+    pub edns: Option<Edns>,
 }
 
 impl Message {
@@ -29,42 +41,286 @@ impl Message {
     ///
     /// This includes the dereferencing of rfc1035 Message Compression pointers,
     /// and collapsing the names into strings.
+    ///
+    /// A message that can't be parsed fails with a [`MessageError::Parse`],
+    /// which says the section, record and offset that couldn't be read. The
+    /// message is parsed with the default [`ParseOptions`], so trailing data
+    /// or the Z flag are logged rather than rejected. Use
+    /// [`Message::from_bytes_with`] to parse strictly.
     #[instrument(skip(input))]
     pub fn from_bytes<'a>(input: &[u8]) -> Result<Message> {
-        let (_, message) = parser::read_message(input)?;
+        let (message, _) = parser::read_message(input, ParseOptions::default())?;
 
         trace!("Read input as: {}", message);
 
         Ok(message)
     }
 
+    /// Reads the message as [`Message::from_bytes`] does, but as strictly as
+    /// the [`ParseOptions`] say. Along with the message are warnings for
+    /// anything that the options allowed.
+    #[instrument(skip(input))]
+    pub fn from_bytes_with(
+        input: &[u8],
+        options: ParseOptions,
+    ) -> Result<(Message, Vec<ParseError>)> {
+        let (message, warnings) = parser::read_message(input, options)?;
+
+        trace!("Read input as: {}", message);
+
+        Ok((message, warnings))
+    }
+
     /// Serializes the Message to bytes into the provided buffer, returning the
     /// number of bytes written to the buffer.
+    ///
+    /// Names are written using rfc1035 Message Compression, where a name (or a
+    /// suffix of it) that has already been written is replaced by a pointer.
+    #[instrument(skip(buf))]
+    pub fn to_bytes(&self, buf: &mut dyn Writer) -> Result<usize> {
+        self.to_bytes_with_limit(buf, usize::MAX)
+    }
+
+    /// Serializes the Message to bytes into the provided buffer in at most
+    /// `max_size` bytes, returning the number of bytes written to the buffer.
+    ///
+    /// Whole records are dropped from the end of the message until it fits,
+    /// that is from the additional records and then the name servers. If any
+    /// answers have to be dropped the TC bit is set in the written header.
+    /// The OPT record is always kept, and the header and questions must fit.
+    ///
+    /// The limit for a UDP response is given by
+    /// [`Message::max_payload_size`] of the request.
+    ///
+    /// On an error nothing is left written to the buffer.
     #[instrument(skip(buf))]
-    pub fn to_bytes(&self, buf: &mut Vec<u8>) -> Result<usize> {
-        let mut byte_count = self.header.to_bytes(&self, buf)?;
+    pub fn to_bytes_with_limit(&self, buf: &mut dyn Writer, max_size: usize) -> Result<usize> {
+        let start = buf.len();
+        let result = self.write_with_limit(buf, start, max_size);
+        if result.is_err() {
+            buf.truncate(start);
+        }
+        result
+    }
+
+    /// Writes the message for [`Message::to_bytes_with_limit`], which cleans
+    /// up after an error.
+    fn write_with_limit(
+        &self,
+        buf: &mut dyn Writer,
+        start: usize,
+        max_size: usize,
+    ) -> Result<usize> {
+        let mut names = NameCompressor::new(start);
+        self.header.to_bytes(self, buf)?;
         for q in self.questions.iter() {
-            byte_count += q.to_bytes(buf)?;
+            q.to_bytes(buf, &mut names)?;
         }
-        for a in self.answers.iter() {
-            byte_count += a.to_bytes(buf)?;
+
+        // The OPT record is written at the end, but it's measured in place
+        // first so that space can be kept for it.
+        let opt_len = match &self.edns {
+            Some(edns) => {
+                let opt_start = buf.len();
+                let len = edns.to_bytes(&self.header.rcode, buf)?;
+                buf.truncate(opt_start);
+                len
+            }
+            None => 0,
+        };
+        let limit = max_size.saturating_sub(opt_len);
+        if buf.len() - start > limit {
+            let size = buf.len() - start + opt_len;
+            return Err(MessageError::SizeLimitExceeded(size, max_size));
         }
-        for n in self.name_servers.iter() {
-            byte_count += n.to_bytes(buf)?;
+
+        // The number of records written for each section.
+        let mut counts = [0u16; 3];
+        let sections = [&self.answers, &self.name_servers, &self.additional_records];
+        'sections: for (section, records) in sections.iter().enumerate() {
+            for record in records.iter() {
+                let record_start = buf.len();
+                record.to_bytes(buf, &mut names)?;
+                if buf.len() - start > limit {
+                    trace!(
+                        "Truncating at record {} of section {}",
+                        counts[section],
+                        section
+                    );
+                    buf.truncate(record_start);
+                    if section == 0 {
+                        // TC is the second lowest bit of the third byte.
+                        let flags = buf.as_bytes()[start + 2] | 0b0000_0010;
+                        buf.write_at(start + 2, &[flags]);
+                    }
+                    break 'sections;
+                }
+                counts[section] += 1;
+            }
         }
-        for ar in self.additional_records.iter() {
-            byte_count += ar.to_bytes(buf)?;
+        if let Some(edns) = &self.edns {
+            edns.to_bytes(&self.header.rcode, buf)?;
         }
 
-        if byte_count > 512 {
-            // TODO set the TR bit to true.
-            // Should we also truncate the buffer?
+        // Patch the counts of what was actually written.
+        counts[2] += self.edns.is_some() as u16;
+        for (i, count) in counts.iter().enumerate() {
+            let pos = start + 6 + i * 2;
+            buf.write_at(pos, &count.to_be_bytes());
         }
 
+        let byte_count = buf.len() - start;
         trace!("Wrote {} bytes", byte_count);
 
         Ok(byte_count)
     }
+
+    /// RFC1035 - Creates the response to the query, without any records, so
+    /// the answers can be added to it.
+    ///
+    /// The response has the ID and OPCODE of the query and echoes its
+    /// questions, whether there are none, one or several. The RD bit is copied,
+    /// as is the CD bit as per RFC4035, and every other flag is clear with an
+    /// RCODE of NOERROR.
+    ///
+    /// RFC6891 - A query with EDNS gets a response with EDNS, which echoes the
+    /// version and UDP payload size, and the DO bit as per RFC3225. None of the
+    /// options are copied.
+    pub fn response_for(query: &Message) -> Message {
+        let header = Header {
+            id: query.header.id,
+            qr: true,
+            opcode: query.header.opcode,
+            aa: false,
+            tc: false,
+            rd: query.header.rd,
+            ra: false,
+            z: false,
+            ad: false,
+            cd: query.header.cd,
+            rcode: RCode::NoError,
+        };
+        let edns = query.edns.as_ref().map(|edns| Edns {
+            udp_payload_size: edns.udp_payload_size,
+            version: edns.version,
+            dnssec_ok: edns.dnssec_ok,
+            ..Edns::default()
+        });
+
+        Message {
+            header,
+            questions: query.questions.clone(),
+            answers: Vec::new(),
+            name_servers: Vec::new(),
+            additional_records: Vec::new(),
+            edns,
+        }
+    }
+
+    /// Creates the response to the query as [`Message::response_for`] does,
+    /// with the RCODE of an error such as [`RCode::FormatError`] or
+    /// [`RCode::ServerFailure`].
+    ///
+    /// An extended RCODE, such as [`RCode::BadVers`], can only be written when
+    /// the query had EDNS.
+    pub fn error_response(query: &Message, rcode: RCode) -> Message {
+        let mut response = Message::response_for(query);
+        response.header.rcode = rcode;
+        response
+    }
+
+    /// The records of the answer section with the name and type, the answers
+    /// to a question for them without following any CNAMEs.
+    pub fn answers_for<N: Into<DomainName>>(
+        &self,
+        name: N,
+        rtype: Type,
+    ) -> impl Iterator<Item = &ResourceRecord> + '_ {
+        let name = name.into();
+        self.answers
+            .iter()
+            .filter(move |r| Type::from(r.data.as_u16()) == rtype && r.name == name)
+    }
+
+    /// The records of the answer section grouped into [`RRset`]s, in the order
+    /// each set first appears. See [`RRset::group`].
+    pub fn rrsets(&self) -> Result<Vec<RRset>> {
+        RRset::group(self.answers.iter())
+    }
+
+    /// Follows the CNAME records of the answer section from the name, and
+    /// returns the name at the end of the chain, whose records answer the
+    /// question. That's the name itself when it has no CNAME.
+    ///
+    /// A chain that leads back to a name already followed fails with a
+    /// [`MessageError::CnameLoop`].
+    pub fn follow_cname_chain<N: Into<DomainName>>(&self, qname: N) -> Result<DomainName> {
+        let mut name = qname.into();
+        let mut seen = HashSet::new();
+        loop {
+            let target = self.answers.iter().find_map(|r| match &r.data {
+                RData::CNAME(target) if r.name == name => Some(target),
+                _ => None,
+            });
+            let target = match target {
+                Some(target) => target.clone(),
+                None => return Ok(name),
+            };
+            trace!("Following CNAME from {} to {}", name, target);
+            if !seen.insert(name.clone()) || seen.contains(&target) {
+                return Err(MessageError::CnameLoop(target));
+            }
+            name = target;
+        }
+    }
+
+    /// The lowest TTL of the records in the answer, authority and additional
+    /// sections, which is how long the whole message can be cached, or
+    /// [`None`] if there are no records.
+    ///
+    /// RFC2308 - the TTL of an SOA record in the authority section is capped
+    /// by its MINIMUM field, so that a negative response is cached for the
+    /// negative TTL of the zone. An SOA in the other sections keeps its TTL.
+    pub fn min_ttl(&self) -> Option<u32> {
+        let authority = self.name_servers.iter().map(|r| match r.data {
+            RData::SOA(_, _, _, _, _, _, minimum) => r.ttl.min(minimum),
+            _ => r.ttl,
+        });
+        self.answers
+            .iter()
+            .chain(self.additional_records.iter())
+            .map(|r| r.ttl)
+            .chain(authority)
+            .min()
+    }
+
+    /// The largest UDP message that the sender of this message can receive,
+    /// the EDNS UDP payload size if present (but never less than 512), or 512
+    /// as per RFC1035.
+    pub fn max_payload_size(&self) -> usize {
+        match &self.edns {
+            Some(edns) => (edns.udp_payload_size as usize).max(UDP_PAYLOAD_SIZE),
+            None => UDP_PAYLOAD_SIZE,
+        }
+    }
+
+    /// RFC1996 - The serial of the SOA hint in the answer section of a
+    /// NOTIFY, the new version of the zone being notified.
+    pub fn soa_hint(&self) -> Option<u32> {
+        if self.header.opcode != OpCode::Notify {
+            return None;
+        }
+        let zone = &self.questions.first()?.q_name;
+        self.answers.iter().find_map(|record| match &record.data {
+            RData::SOA(_, _, serial, ..) if &record.name == zone => Some(*serial),
+            _ => None,
+        })
+    }
+
+    /// Displays the whole message the way `dig` does, see [`Dig`].
+    pub fn dig(&self) -> Dig<'_> {
+        Dig::new(self)
+    }
 }
 
 impl fmt::Display for Message {
@@ -142,4 +398,876 @@ mod test {
 
         assert_eq!(message, message2);
     }
+
+    #[test]
+    pub fn test_header_round_trip() {
+        setup();
+
+        use crate::{Message, OpCode, ParseOptions};
+
+        // Every combination of flags, opcode and RCODE is written back as it
+        // was read, including the reserved Z flag and unknown opcodes.
+        for flags in 0..=u16::MAX {
+            let [high, low] = flags.to_be_bytes();
+            let input = [0x12, 0x34, high, low, 0, 0, 0, 0, 0, 0, 0, 0];
+            let (message, _) = Message::from_bytes_with(&input, ParseOptions::lenient()).unwrap();
+            assert_eq!(message.header.ra, low & 0x80 != 0);
+            assert_eq!(message.header.z, low & 0x40 != 0);
+            let opcode = high >> 3 & 0xf;
+            if !matches!(opcode, 0..=2 | 4 | 5) {
+                assert_eq!(message.header.opcode, OpCode::Unknown(opcode));
+            }
+
+            let mut buf = Vec::new();
+            assert_eq!(message.to_bytes(&mut buf).unwrap(), 12);
+            assert_eq!(buf, input, "flags {:#06x}", flags);
+
+            // Strict parsing only rejects the Z flag, which is otherwise kept.
+            match Message::from_bytes_with(&input, ParseOptions::strict()) {
+                Ok((strict, _)) => assert_eq!(strict, message),
+                Err(_) => assert!(message.header.z),
+            }
+            assert_eq!(Message::from_bytes(&input).unwrap(), message);
+        }
+    }
+
+    #[test]
+    pub fn test_to_bytes_compression() {
+        setup();
+
+        use crate::{Message, MessageBuilder, QuestionBuilder, RData, ResourceRecordBuilder, Type};
+
+        let message = MessageBuilder::new()
+            .id(4321)
+            .qr(true)
+            .question(
+                QuestionBuilder::new()
+                    .name("www.example.com")
+                    .q_type(Type::CNAME)
+                    .build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new(
+                    "www.example.com",
+                    RData::CNAME("web.example.com".into()),
+                )
+                .ttl(300)
+                .build(),
+            )
+            .name_server(
+                ResourceRecordBuilder::new("example.com", RData::NS("ns1.example.com".into()))
+                    .ttl(300)
+                    .build(),
+            )
+            .name_server(
+                ResourceRecordBuilder::new(
+                    "example.com",
+                    RData::SOA(
+                        "ns1.example.com".into(),
+                        "hostmaster.example.com".into(),
+                        1,
+                        2,
+                        3,
+                        4,
+                        5,
+                    ),
+                )
+                .ttl(300)
+                .build(),
+            )
+            .build();
+
+        let mut buf = Vec::new();
+        let len = message.to_bytes(&mut buf).unwrap();
+        assert_eq!(len, buf.len());
+
+        // The answer name is the same as the question name at offset 12.
+        let answer = 12 + 17 + 4;
+        assert_eq!(&buf[answer..answer + 2], &[0xc0, 12]);
+        // The CNAME rdata shares the "example.com" suffix at offset 16.
+        assert_eq!(
+            &buf[answer + 12..answer + 18],
+            &[3, b'w', b'e', b'b', 0xc0, 16]
+        );
+
+        let message2 = Message::from_bytes(&buf).unwrap();
+        assert_eq!(message, message2);
+    }
+
+    #[test]
+    pub fn test_to_bytes_compression_with_offset_buffer() {
+        setup();
+
+        use crate::{Message, MessageBuilder, RData, ResourceRecordBuilder};
+        use std::net::Ipv4Addr;
+
+        let mut builder = MessageBuilder::new().qr(true);
+        for i in 0..20 {
+            builder = builder.answer(
+                ResourceRecordBuilder::new(
+                    "a-fairly-long-label.example.com",
+                    RData::A(Ipv4Addr::new(192, 0, 2, i)),
+                )
+                .build(),
+            );
+        }
+        let message = builder.build();
+
+        // Pointers are relative to the start of the message, not the buffer.
+        let mut buf = vec![0xff; 7];
+        let len = message.to_bytes(&mut buf).unwrap();
+        assert!(len < 512);
+
+        let message2 = Message::from_bytes(&buf[7..]).unwrap();
+        assert_eq!(message, message2);
+    }
+
+    #[test]
+    pub fn test_to_bytes_from_bytes_rfc1035_types() {
+        setup();
+
+        use crate::{Message, MessageBuilder, RData, ResourceRecordBuilder};
+        use std::net::Ipv4Addr;
+
+        let data = vec![
+            RData::MD("md.example.com".into()),
+            RData::MF("mf.example.com".into()),
+            RData::MB("mb.example.com".into()),
+            RData::MG("mg.example.com".into()),
+            RData::MR("mr.example.com".into()),
+            RData::NULL(vec![0, 1, 2, 255]),
+            RData::WKS(Ipv4Addr::new(192, 0, 2, 1), 6, vec![0, 0, 0, 0x40, 0, 0x80]),
+            RData::PTR("ptr.example.com".into()),
+            RData::HINFO(b"x86_64".to_vec(), b"Linux \"5\"".to_vec()),
+            RData::MINFO("admin.example.com".into(), "errors.example.com".into()),
+            RData::MX(10, "mail.example.com".into()),
+        ];
+
+        let mut builder = MessageBuilder::new().id(99).qr(true);
+        for d in data {
+            builder = builder.answer(ResourceRecordBuilder::new("example.com", d).ttl(60).build());
+        }
+        let message = builder.build();
+
+        let mut buf = Vec::new();
+        let len = message.to_bytes(&mut buf).unwrap();
+        let message2 = Message::from_bytes(&buf[0..len]).unwrap();
+        assert_eq!(message, message2);
+
+        assert_eq!(
+            message2.answers[6].data.to_string(),
+            "WKS(192.0.2.1, 6, 25, 40)"
+        );
+        assert_eq!(
+            message2.answers[8].data.to_string(),
+            r#"HINFO("x86_64", "Linux \"5\"")"#
+        );
+        assert_eq!(
+            message2.answers[10].data.to_string(),
+            "MX(10, mail.example.com)"
+        );
+    }
+
+    #[test]
+    pub fn test_to_bytes_from_bytes_txt() {
+        setup();
+
+        use crate::{Message, MessageBuilder, RData, ResourceRecordBuilder};
+
+        let dkim = format!("v=DKIM1; k=rsa; p={}", "A".repeat(400));
+        let message = MessageBuilder::new()
+            .qr(true)
+            .answer(
+                ResourceRecordBuilder::new(
+                    "example.com",
+                    RData::TXT(vec![b"hello \"world\"".to_vec(), vec![0xff, b'\\', 0x7f]]),
+                )
+                .build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new("sel._domainkey.example.com", RData::txt(&dkim)).build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new(
+                    "example.com",
+                    RData::SPF(vec![b"v=spf1 -all".to_vec()]),
+                )
+                .build(),
+            )
+            .build();
+
+        let mut buf = Vec::new();
+        let len = message.to_bytes(&mut buf).unwrap();
+        let message2 = Message::from_bytes(&buf[0..len]).unwrap();
+        assert_eq!(message, message2);
+
+        assert_eq!(
+            message2.answers[0].data.to_string(),
+            r#"TXT("hello \"world\"" "\255\\\127")"#
+        );
+        match &message2.answers[1].data {
+            RData::TXT(strings) => {
+                assert_eq!(strings.len(), 2);
+                assert_eq!(strings[0].len(), 255);
+            }
+            d => panic!("Unexpected rdata {}", d),
+        }
+        assert_eq!(message2.answers[1].data.text(), Some(dkim));
+        assert_eq!(
+            message2.answers[2].data.text(),
+            Some("v=spf1 -all".to_string())
+        );
+    }
+
+    #[test]
+    pub fn test_to_bytes_txt_splits_long_strings() {
+        setup();
+
+        use crate::{Message, MessageBuilder, RData, ResourceRecordBuilder};
+
+        let message = MessageBuilder::new()
+            .answer(
+                ResourceRecordBuilder::new("example.com", RData::TXT(vec![vec![b'a'; 300]]))
+                    .build(),
+            )
+            .build();
+
+        let mut buf = Vec::new();
+        message.to_bytes(&mut buf).unwrap();
+        let message2 = Message::from_bytes(&buf).unwrap();
+        assert_eq!(
+            message2.answers[0].data,
+            RData::TXT(vec![vec![b'a'; 255], vec![b'a'; 45]])
+        );
+
+        // The presentation format splits the string in the same way, so it
+        // can be read back.
+        let text = message.answers[0].to_string();
+        assert_eq!(
+            text,
+            format!(
+                "example.com. 0 IN TXT \"{}\" \"{}\"",
+                "a".repeat(255),
+                "a".repeat(45)
+            )
+        );
+        assert_eq!(
+            text.parse::<crate::ResourceRecord>().unwrap(),
+            message2.answers[0]
+        );
+    }
+
+    #[test]
+    pub fn test_to_bytes_from_bytes_modern_types() {
+        setup();
+
+        use crate::{Message, MessageBuilder, RData, ResourceRecordBuilder};
+
+        let data = vec![
+            // 52 22 23.000 N 4 53 32.000 E -2.00m 1m 10000m 10m
+            RData::LOC(0, 0x12, 0x16, 0x13, 2_336_026_648, 2_165_095_648, 9_999_800),
+            RData::SRV(10, 60, 5060, "sip.example.com".into()),
+            RData::NAPTR(
+                100,
+                10,
+                b"U".to_vec(),
+                b"E2U+sip".to_vec(),
+                b"!^.*$!sip:info@example.com!".to_vec(),
+                ".".into(),
+            ),
+            RData::DNAME("example.net".into()),
+            RData::SSHFP(4, 2, vec![0xde, 0xad, 0xbe, 0xef]),
+            RData::TLSA(3, 1, 1, vec![0x01, 0x02, 0xab]),
+            RData::URI(10, 1, b"ftp://ftp1.example.com/public".to_vec()),
+            RData::CAA(0, b"issue".to_vec(), b"letsencrypt.org".to_vec()),
+        ];
+
+        let mut builder = MessageBuilder::new().id(7).qr(true);
+        for d in data {
+            builder = builder.answer(ResourceRecordBuilder::new("example.com", d).build());
+        }
+        let message = builder.build();
+
+        let mut buf = Vec::new();
+        let len = message.to_bytes(&mut buf).unwrap();
+        let message2 = Message::from_bytes(&buf[0..len]).unwrap();
+        assert_eq!(message, message2);
+
+        let display: Vec<String> = message2
+            .answers
+            .iter()
+            .map(|a| a.data.to_string())
+            .collect();
+        assert_eq!(
+            display,
+            vec![
+                "LOC(52 22 23.000 N 4 53 32.000 E -2.00m 1m 10000m 10m)",
+                "SRV(10, 60, 5060, sip.example.com)",
+                r#"NAPTR(100, 10, "U", "E2U+sip", "!^.*$!sip:info@example.com!", .)"#,
+                "DNAME(example.net)",
+                "SSHFP(4, 2, DEADBEEF)",
+                "TLSA(3, 1, 1, 0102AB)",
+                r#"URI(10, 1, "ftp://ftp1.example.com/public")"#,
+                r#"CAA(0, issue, "letsencrypt.org")"#,
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_to_bytes_srv_dname_not_compressed() {
+        setup();
+
+        use crate::{MessageBuilder, RData, ResourceRecordBuilder};
+
+        let message = MessageBuilder::new()
+            .answer(
+                ResourceRecordBuilder::new(
+                    "_sip._udp.example.com",
+                    RData::SRV(0, 0, 5060, "example.com".into()),
+                )
+                .build(),
+            )
+            .build();
+
+        let mut buf = Vec::new();
+        message.to_bytes(&mut buf).unwrap();
+        // The SRV target is written out in full after the 6 octet fixed fields.
+        assert_eq!(
+            &buf[buf.len() - 13..],
+            &[7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0]
+        );
+    }
+
+    #[test]
+    pub fn test_to_bytes_from_bytes_svcb_https() {
+        setup();
+
+        use crate::{Message, MessageBuilder, RData, ResourceRecordBuilder, SvcParam, SvcParams};
+        use std::net::Ipv4Addr;
+
+        let params: SvcParams = vec![
+            SvcParam::Alpn(vec![b"h3".to_vec(), b"h2".to_vec()]),
+            SvcParam::Ipv4Hint(vec![
+                Ipv4Addr::new(192, 0, 2, 1),
+                Ipv4Addr::new(192, 0, 2, 2),
+            ]),
+        ]
+        .into_iter()
+        .collect();
+
+        let message = MessageBuilder::new()
+            .qr(true)
+            .answer(
+                ResourceRecordBuilder::new("example.com", RData::HTTPS(1, ".".into(), params))
+                    .ttl(300)
+                    .build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new(
+                    "_dns.example.com",
+                    RData::SVCB(0, "svc.example.net".into(), SvcParams::new()),
+                )
+                .build(),
+            )
+            .build();
+
+        let mut buf = Vec::new();
+        let len = message.to_bytes(&mut buf).unwrap();
+        let message2 = Message::from_bytes(&buf[0..len]).unwrap();
+        assert_eq!(message, message2);
+
+        assert_eq!(
+            message2.answers[0].data.to_string(),
+            r#"HTTPS(1, . alpn="h3,h2" ipv4hint=192.0.2.1,192.0.2.2)"#
+        );
+        assert_eq!(
+            message2.answers[1].data.to_string(),
+            "SVCB(0, svc.example.net)"
+        );
+    }
+
+    #[test]
+    pub fn test_to_bytes_from_bytes_dnssec() {
+        setup();
+
+        use crate::{Message, MessageBuilder, RData, ResourceRecordBuilder, Type};
+
+        let message = MessageBuilder::new()
+            .qr(true)
+            .answer(
+                ResourceRecordBuilder::new(
+                    "example.com",
+                    RData::DNSKEY(257, 3, 8, vec![3, 1, 0, 1, 0xab, 0xcd]),
+                )
+                .build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new(
+                    "example.com",
+                    RData::RRSIG(
+                        Type::DNSKEY,
+                        8,
+                        2,
+                        3600,
+                        1_704_067_200,
+                        1_701_388_800,
+                        12345,
+                        "example.com".into(),
+                        vec![1, 2, 3, 4],
+                    ),
+                )
+                .build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new(
+                    "example.com",
+                    RData::DS(12345, 8, 2, vec![0xde, 0xad, 0xbe, 0xef]),
+                )
+                .build(),
+            )
+            .answer(ResourceRecordBuilder::new("example.com", RData::CDS(0, 0, 0, vec![0])).build())
+            .answer(
+                ResourceRecordBuilder::new("example.com", RData::CDNSKEY(0, 3, 0, vec![0])).build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new(
+                    "alfa.example.com",
+                    RData::NSEC(
+                        "host.example.com".into(),
+                        vec![
+                            Type::A,
+                            Type::MX,
+                            Type::RRSIG,
+                            Type::NSEC,
+                            Type::Unknown(1234),
+                        ],
+                    ),
+                )
+                .build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new(
+                    "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.example.com",
+                    RData::NSEC3(
+                        1,
+                        1,
+                        12,
+                        vec![0xaa, 0xbb, 0xcc, 0xdd],
+                        vec![0x12; 20],
+                        vec![Type::A, Type::RRSIG],
+                    ),
+                )
+                .build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new("example.com", RData::NSEC3PARAM(1, 0, 0, vec![]))
+                    .build(),
+            )
+            .build();
+
+        let mut buf = Vec::new();
+        let len = message.to_bytes(&mut buf).unwrap();
+        let message2 = Message::from_bytes(&buf[0..len]).unwrap();
+        assert_eq!(message, message2);
+
+        // The signer and next domain names are never compressed.
+        let signer = b"\x07example\x03com\x00\x01\x02\x03\x04";
+        assert!(buf.windows(signer.len()).any(|w| w == signer));
+        // RFC4034 4.3 - the NSEC example.
+        let mut nsec = b"\x04host\x07example\x03com\x00".to_vec();
+        nsec.extend_from_slice(&[0x00, 0x06, 0x40, 0x01, 0x00, 0x00, 0x00, 0x03, 0x04, 0x1b]);
+        nsec.extend_from_slice(&[0; 26]);
+        nsec.push(0x20);
+        assert!(buf.windows(nsec.len()).any(|w| w == nsec));
+
+        assert_eq!(
+            message2.answers[0].data.to_string(),
+            "DNSKEY(257, 3, 8, AwEAAavN)"
+        );
+        assert_eq!(
+            message2.answers[1].data.to_string(),
+            "RRSIG(DNSKEY, 8, 2, 3600, 20240101000000, 20231201000000, 12345, example.com, AQIDBA==)"
+        );
+        assert_eq!(
+            message2.answers[2].data.to_string(),
+            "DS(12345, 8, 2, DEADBEEF)"
+        );
+        assert_eq!(
+            message2.answers[5].data.to_string(),
+            "NSEC(host.example.com A MX RRSIG NSEC TYPE1234)"
+        );
+        assert_eq!(
+            message2.answers[6].data.to_string(),
+            "NSEC3(1, 1, 12, AABBCCDD, 289144GI289144GI289144GI289144GI A RRSIG)"
+        );
+        assert_eq!(
+            message2.answers[7].data.to_string(),
+            "NSEC3PARAM(1, 0, 0, -)"
+        );
+    }
+
+    #[test]
+    pub fn test_to_bytes_from_bytes_edns() {
+        setup();
+
+        use crate::{Edns, EdnsOption, Message, MessageBuilder, QuestionBuilder, RCode, Type};
+
+        let message = MessageBuilder::new()
+            .qr(true)
+            .rcode(RCode::BadVers)
+            .question(
+                QuestionBuilder::new()
+                    .name("example.com")
+                    .q_type(Type::A)
+                    .build(),
+            )
+            .edns(Edns {
+                udp_payload_size: 4096,
+                dnssec_ok: true,
+                options: vec![
+                    EdnsOption::Cookie([1, 2, 3, 4, 5, 6, 7, 8], Some(vec![9; 8])),
+                    EdnsOption::ExtendedError(1, b"RSASHA1".to_vec()),
+                ],
+                ..Edns::default()
+            })
+            .build();
+
+        let mut buf = Vec::new();
+        let len = message.to_bytes(&mut buf).unwrap();
+
+        // The lower 4 bits of BADVERS (16) are zero in the header, and the
+        // OPT record is counted in ARCOUNT.
+        assert_eq!(buf[3] & 0xf, 0);
+        assert_eq!(&buf[10..12], &[0, 1]);
+
+        let message2 = Message::from_bytes(&buf[0..len]).unwrap();
+        assert_eq!(message, message2);
+        assert_eq!(message2.header.rcode, RCode::BadVers);
+        assert!(message2.additional_records.is_empty());
+    }
+
+    #[test]
+    pub fn test_to_bytes_extended_rcode_without_edns() {
+        setup();
+
+        use crate::{MessageBuilder, RCode};
+
+        let message = MessageBuilder::new().rcode(RCode::BadVers).build();
+        let mut buf = Vec::new();
+        assert!(message.to_bytes(&mut buf).is_err());
+    }
+
+    #[test]
+    pub fn test_to_bytes_with_limit() {
+        setup();
+
+        use crate::{Edns, Message, MessageBuilder, QuestionBuilder, RData, ResourceRecordBuilder};
+        use std::net::Ipv4Addr;
+
+        let a = |i| {
+            ResourceRecordBuilder::new(
+                format!("host{}.example.com", i),
+                RData::A(Ipv4Addr::new(192, 0, 2, i)),
+            )
+            .build()
+        };
+        let mut builder = MessageBuilder::new()
+            .qr(true)
+            .question(QuestionBuilder::new().name("example.com").build());
+        for i in 0..10 {
+            builder = builder
+                .answer(a(i))
+                .name_server(a(i + 10))
+                .additional_record(a(i + 20));
+        }
+        let message = builder.build();
+
+        let mut buf = Vec::new();
+        let full_len = message.to_bytes(&mut buf).unwrap();
+        assert!(full_len > 512);
+
+        // Only the additional records are dropped.
+        let mut buf = Vec::new();
+        let len = message.to_bytes_with_limit(&mut buf, full_len - 1).unwrap();
+        assert!(len < full_len);
+        let message2 = Message::from_bytes(&buf).unwrap();
+        assert!(!message2.header.tc);
+        assert_eq!(message2.answers.len(), 10);
+        assert_eq!(message2.name_servers.len(), 10);
+        assert_eq!(message2.additional_records.len(), 9);
+
+        // Then the name servers, and the answers with the TC bit set.
+        let mut buf = Vec::new();
+        let len = message.to_bytes_with_limit(&mut buf, 150).unwrap();
+        assert!(len <= 150);
+        let message2 = Message::from_bytes(&buf).unwrap();
+        assert!(message2.header.tc);
+        assert!(message2.answers.len() < 10);
+        assert!(message2.name_servers.is_empty());
+        assert!(message2.additional_records.is_empty());
+        assert_eq!(
+            message2.answers[..],
+            message.answers[..message2.answers.len()]
+        );
+
+        // The OPT record is kept.
+        let mut message = message;
+        message.edns = Some(Edns::default());
+        let mut buf = Vec::new();
+        let len = message.to_bytes_with_limit(&mut buf, 512).unwrap();
+        assert!(len <= 512);
+        let message2 = Message::from_bytes(&buf).unwrap();
+        assert!(!message2.header.tc);
+        assert_eq!(message2.answers.len(), 10);
+        assert!(message2.additional_records.len() < 10);
+        assert_eq!(message2.edns, message.edns);
+
+        // The header and question must fit.
+        let mut buf = vec![1, 2, 3];
+        assert!(message.to_bytes_with_limit(&mut buf, 20).is_err());
+        assert_eq!(buf, vec![1, 2, 3]);
+    }
+
+    #[test]
+    pub fn test_max_payload_size() {
+        setup();
+
+        use crate::{Edns, MessageBuilder};
+
+        assert_eq!(MessageBuilder::new().build().max_payload_size(), 512);
+        let edns = |udp_payload_size| Edns {
+            udp_payload_size,
+            ..Edns::default()
+        };
+        assert_eq!(
+            MessageBuilder::new()
+                .edns(edns(4096))
+                .build()
+                .max_payload_size(),
+            4096
+        );
+        assert_eq!(
+            MessageBuilder::new()
+                .edns(edns(100))
+                .build()
+                .max_payload_size(),
+            512
+        );
+    }
+
+    #[test]
+    pub fn test_to_bytes_writers() {
+        setup();
+
+        use crate::{Edns, Message, MessageBuilder, QuestionBuilder, SliceWriter, Writer};
+        use bytes::BytesMut;
+
+        let message = MessageBuilder::new()
+            .id(99)
+            .question(QuestionBuilder::new().name("www.example.com").build())
+            .answer("www.example.com. 60 IN A 192.0.2.1".parse().unwrap())
+            .answer(
+                "www.example.com. 60 IN TXT \"\" \"v=spf1 -all\""
+                    .parse()
+                    .unwrap(),
+            )
+            .edns(Edns::default())
+            .build();
+        let mut vec = Vec::new();
+        let len = message.to_bytes(&mut vec).unwrap();
+
+        let mut bytes = BytesMut::new();
+        assert_eq!(message.to_bytes(&mut bytes).unwrap(), len);
+        assert_eq!(&bytes[..], &vec[..]);
+
+        let mut buf = [0u8; 512];
+        let mut writer = SliceWriter::new(&mut buf);
+        assert_eq!(message.to_bytes(&mut writer).unwrap(), len);
+        assert_eq!(writer.as_bytes(), &vec[..]);
+        assert_eq!(Message::from_bytes(writer.into_written()).unwrap(), message);
+
+        // A buffer that is too small is an error rather than a panic, and
+        // nothing is left written to it.
+        for size in 0..len {
+            let mut buf = vec![0u8; size];
+            let mut writer = SliceWriter::new(&mut buf);
+            assert!(message.to_bytes(&mut writer).is_err());
+            assert!(writer.is_empty());
+            assert!(message.to_bytes_with_limit(&mut writer, 512).is_err());
+            assert!(writer.is_empty());
+        }
+    }
+
+    #[test]
+    pub fn test_section_helpers() {
+        setup();
+
+        use crate::{DomainName, MessageBuilder, MessageError, RData, Type};
+        use std::net::Ipv4Addr;
+
+        let message = MessageBuilder::new()
+            .qr(true)
+            .answer(
+                "www.example.com. 300 IN CNAME web.example.com."
+                    .parse()
+                    .unwrap(),
+            )
+            .answer(
+                "web.example.com. 300 IN CNAME Cdn.example.net."
+                    .parse()
+                    .unwrap(),
+            )
+            .answer("cdn.example.net. 60 IN A 192.0.2.2".parse().unwrap())
+            .answer("cdn.example.net. 30 IN A 192.0.2.1".parse().unwrap())
+            .answer("cdn.example.net. 60 IN AAAA 2001:db8::1".parse().unwrap())
+            .name_server("example.net. 600 IN NS ns1.example.net.".parse().unwrap())
+            .build();
+
+        let target = message.follow_cname_chain("WWW.example.com").unwrap();
+        assert_eq!(target, "cdn.example.net");
+        let addresses: Vec<&RData> = message
+            .answers_for(target, Type::A)
+            .map(|r| &r.data)
+            .collect();
+        assert_eq!(
+            addresses,
+            vec![
+                &RData::A(Ipv4Addr::new(192, 0, 2, 2)),
+                &RData::A(Ipv4Addr::new(192, 0, 2, 1))
+            ]
+        );
+        assert_eq!(message.answers_for("www.example.com", Type::A).count(), 0);
+        assert_eq!(
+            message.follow_cname_chain("other.example.com").unwrap(),
+            "other.example.com"
+        );
+
+        let rrsets = message.rrsets().unwrap();
+        assert_eq!(rrsets.len(), 4);
+        assert_eq!(rrsets[2].name(), "cdn.example.net");
+        assert_eq!(rrsets[2].rtype(), Type::A);
+        assert_eq!(rrsets[2].ttl(), 30);
+        assert_eq!(rrsets[2].data().len(), 2);
+        assert_eq!(message.min_ttl(), Some(30));
+
+        // A chain that loops back, including a CNAME to itself.
+        let message = MessageBuilder::new()
+            .answer("a.example.com. 60 IN CNAME b.example.com.".parse().unwrap())
+            .answer("b.example.com. 60 IN CNAME A.example.com.".parse().unwrap())
+            .answer("c.example.com. 60 IN CNAME c.example.com.".parse().unwrap())
+            .build();
+        match message.follow_cname_chain("a.example.com") {
+            Err(MessageError::CnameLoop(name)) => assert_eq!(name, "a.example.com"),
+            r => panic!("Expected a CNAME loop, got {:?}", r),
+        }
+        assert!(matches!(
+            message.follow_cname_chain(DomainName::from("c.example.com")),
+            Err(MessageError::CnameLoop(_))
+        ));
+
+        // The SOA of a negative response is capped by its MINIMUM.
+        let message = MessageBuilder::new()
+            .name_server(
+                "example.com. 3600 IN SOA ns1.example.com. hostmaster.example.com. 1 3600 900 604800 300"
+                    .parse()
+                    .unwrap(),
+            )
+            .build();
+        assert_eq!(message.min_ttl(), Some(300));
+        assert_eq!(MessageBuilder::new().build().min_ttl(), None);
+
+        // An SOA that answers a query for it isn't capped.
+        let message = MessageBuilder::new()
+            .answer(
+                "example.com. 3600 IN SOA ns1.example.com. hostmaster.example.com. 1 3600 900 604800 5"
+                    .parse()
+                    .unwrap(),
+            )
+            .build();
+        assert_eq!(message.min_ttl(), Some(3600));
+    }
+
+    #[test]
+    pub fn test_response_for() {
+        setup();
+
+        use crate::{
+            Edns, EdnsOption, Header, Message, MessageBuilder, OpCode, QuestionBuilder, RCode, Type,
+        };
+
+        let query = MessageBuilder::new()
+            .id(0x1234)
+            .opcode(OpCode::Query)
+            .rd(true)
+            .cd(true)
+            .ad(true)
+            .question(QuestionBuilder::new().name("www.example.com").build())
+            .edns(Edns {
+                udp_payload_size: 4096,
+                dnssec_ok: true,
+                options: vec![EdnsOption::Cookie([1, 2, 3, 4, 5, 6, 7, 8], None)],
+                ..Edns::default()
+            })
+            .build();
+
+        let response = Message::response_for(&query);
+        let header = response.header;
+        assert_eq!(header.id, 0x1234);
+        assert!(header.qr && header.rd && header.cd);
+        assert!(!header.aa && !header.tc && !header.ra && !header.ad);
+        assert_eq!(header.opcode, OpCode::Query);
+        assert_eq!(header.rcode, RCode::NoError);
+        assert_eq!(response.questions, query.questions);
+        assert!(response.answers.is_empty());
+        assert_eq!(
+            response.edns,
+            Some(Edns {
+                udp_payload_size: 4096,
+                dnssec_ok: true,
+                ..Edns::default()
+            })
+        );
+
+        let response = Message::error_response(&query, RCode::ServerFailure);
+        assert_eq!(response.header.rcode, RCode::ServerFailure);
+        assert_eq!(response.questions, query.questions);
+
+        // No EDNS in the query, so none in the response.
+        let mut query = MessageBuilder::new()
+            .id(7)
+            .opcode(OpCode::Notify)
+            .question(
+                QuestionBuilder::new()
+                    .name("example.com")
+                    .q_type(Type::SOA)
+                    .build(),
+            )
+            .question(
+                QuestionBuilder::new()
+                    .name("example.net")
+                    .q_type(Type::SOA)
+                    .build(),
+            )
+            .build();
+        let response = Message::error_response(&query, RCode::FormatError);
+        assert_eq!(response.header.opcode, OpCode::Notify);
+        assert!(!response.header.rd);
+        assert_eq!(response.questions.len(), 2);
+        assert_eq!(response.edns, None);
+        let mut buf = Vec::new();
+        response.to_bytes(&mut buf).unwrap();
+        assert_eq!(Message::from_bytes(&buf).unwrap(), response);
+
+        query.questions.clear();
+        let response = Message::response_for(&query);
+        assert!(response.questions.is_empty());
+
+        // A message whose question can't be read still has a header to answer.
+        let input = [0xab, 0xcd, 0x01, 0x10, 0, 1, 0, 0, 0, 0, 0, 0, 3, b'w'];
+        assert!(Message::from_bytes(&input).is_err());
+        let header = Header::from_bytes(&input).unwrap();
+        assert_eq!(header.id, 0xabcd);
+        assert!(header.rd && header.cd && !header.qr);
+        assert!(Header::from_bytes(&input[..11]).is_err());
+    }
 }
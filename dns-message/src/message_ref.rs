@@ -0,0 +1,629 @@
+use crate::message::UDP_PAYLOAD_SIZE;
+use crate::{
+    parser, Class, DomainName, Edns, Header, Message, ParseError, ParseErrorKind, ParseOptions,
+    Question, RData, ResourceRecord, Result, Section, Type,
+};
+use std::fmt;
+use tracing::{instrument, trace};
+
+/// The maximum number of compression pointers followed while reading a
+/// single name, a name of at most 255 octets can't need more than this.
+const MAX_POINTERS: usize = 127;
+
+#[derive(Debug, Clone, Copy)]
+/// A DNS message that borrows the buffer it was read from.
+///
+/// Only the header is decoded up front, along with the offsets of each
+/// section. The questions and records are decoded lazily as they are
+/// iterated, and names are only decoded when asked for, so reading the ID or
+/// the first question doesn't allocate.
+///
+/// Unlike [`Message`] the OPT record is not taken out of the additional
+/// records, and the RCODE in the header is only the lower 4 bits. Use
+/// [`MessageRef::edns`] to read the OPT record, or [`MessageRef::to_message`]
+/// to convert to an owned [`Message`].
+pub struct MessageRef<'a> {
+    input: &'a [u8],
+    header: Header,
+    counts: [u16; 4],
+    /// The offset of the start of each section.
+    offsets: [usize; 4],
+}
+
+impl<'a> MessageRef<'a> {
+    /// Reads the header of the message, and checks that every question and
+    /// record is within the input.
+    ///
+    /// Names are not decoded, so compression pointers are only checked when
+    /// the names are read.
+    #[instrument(skip(input))]
+    pub fn from_bytes(input: &'a [u8]) -> Result<MessageRef<'a>> {
+        let (header, counts) = parser::read_header_counts(input)?;
+
+        let sections = [
+            Section::Question,
+            Section::Answer,
+            Section::Authority,
+            Section::Additional,
+        ];
+        let mut offsets = [0; 4];
+        let mut offset = 12;
+        for (i, count) in counts.iter().enumerate() {
+            offsets[i] = offset;
+            for index in 0..*count as usize {
+                let next = if i == 0 {
+                    read_question_at(input, offset).map(|(_, next)| next)
+                } else {
+                    read_record_at(input, offset).map(|(_, next)| next)
+                };
+                offset = next.map_err(|e| e.at(Some(sections[i]), Some(index), offset))?;
+            }
+        }
+        if offset != input.len() {
+            trace!("Ignoring {} bytes after the message", input.len() - offset);
+        }
+
+        Ok(MessageRef {
+            input,
+            header,
+            counts,
+            offsets,
+        })
+    }
+
+    /// The [`Header`] of the message.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// The ID of the message.
+    pub fn id(&self) -> u16 {
+        self.header.id
+    }
+
+    /// The buffer the message was read from.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.input
+    }
+
+    /// Iterates over the questions.
+    pub fn questions(&self) -> Questions<'a> {
+        Questions {
+            input: self.input,
+            offset: self.offsets[0],
+            remaining: self.counts[0],
+        }
+    }
+
+    /// Iterates over the answers.
+    pub fn answers(&self) -> Records<'a> {
+        self.records(1)
+    }
+
+    /// Iterates over the records pointing toward an authority.
+    pub fn name_servers(&self) -> Records<'a> {
+        self.records(2)
+    }
+
+    /// Iterates over the additional records, including any OPT record.
+    pub fn additional_records(&self) -> Records<'a> {
+        self.records(3)
+    }
+
+    fn records(&self, section: usize) -> Records<'a> {
+        Records {
+            input: self.input,
+            offset: self.offsets[section],
+            remaining: self.counts[section],
+        }
+    }
+
+    /// The EDNS information from the first OPT record in the additional
+    /// records, if there is one.
+    pub fn edns(&self) -> Result<Option<Edns>> {
+        match self.additional_records().find(|r| r.rtype() == Type::OPT) {
+            Some(opt) => {
+                let (edns, _) = Edns::from_opt(opt.class().into(), opt.ttl(), opt.rdata())?;
+                Ok(Some(edns))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The largest UDP message that the sender of this message can receive,
+    /// see [`Message::max_payload_size`].
+    pub fn max_payload_size(&self) -> usize {
+        match self.edns() {
+            Ok(Some(edns)) => (edns.udp_payload_size as usize).max(UDP_PAYLOAD_SIZE),
+            _ => UDP_PAYLOAD_SIZE,
+        }
+    }
+
+    /// Decodes the whole message into an owned [`Message`].
+    ///
+    /// Like [`MessageRef::from_bytes`] it allows whatever it can, so it's
+    /// parsed with [`ParseOptions::lenient`] and the warnings are logged.
+    #[instrument(skip(self))]
+    pub fn to_message(&self) -> Result<Message> {
+        // The parser logs each of the warnings.
+        let (message, _) = Message::from_bytes_with(self.input, ParseOptions::lenient())?;
+        Ok(message)
+    }
+}
+
+impl fmt::Display for MessageRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), fmt::Error> {
+        write!(f, "MessageRef(id:{}) - Query [", self.header.id)?;
+        for (i, q) in self.questions().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}({})", q.name(), q.q_type())?;
+        }
+        write!(f, "]")
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An iterator over the questions of a [`MessageRef`].
+pub struct Questions<'a> {
+    input: &'a [u8],
+    offset: usize,
+    remaining: u16,
+}
+
+impl<'a> Iterator for Questions<'a> {
+    type Item = QuestionRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // The questions were checked when the MessageRef was created.
+        let (question, offset) = read_question_at(self.input, self.offset).ok()?;
+        self.offset = offset;
+        self.remaining -= 1;
+        Some(question)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An iterator over the records of one section of a [`MessageRef`].
+pub struct Records<'a> {
+    input: &'a [u8],
+    offset: usize,
+    remaining: u16,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = RecordRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // The records were checked when the MessageRef was created.
+        let (record, offset) = read_record_at(self.input, self.offset).ok()?;
+        self.offset = offset;
+        self.remaining -= 1;
+        Some(record)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// A question borrowed from a [`MessageRef`].
+pub struct QuestionRef<'a> {
+    name: NameRef<'a>,
+    q_type: Type,
+    q_class: Class,
+}
+
+impl<'a> QuestionRef<'a> {
+    /// The name being queried, which is decoded on demand.
+    pub fn name(&self) -> NameRef<'a> {
+        self.name
+    }
+
+    /// The type of the query.
+    pub fn q_type(&self) -> Type {
+        self.q_type
+    }
+
+    /// The class of the query.
+    pub fn q_class(&self) -> Class {
+        self.q_class
+    }
+
+    /// Decodes the question into an owned [`Question`].
+    pub fn to_question(&self) -> Result<Question> {
+        Ok(Question {
+            q_name: self.name.to_name()?,
+            q_type: self.q_type,
+            q_class: self.q_class,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// A resource record borrowed from a [`MessageRef`].
+pub struct RecordRef<'a> {
+    input: &'a [u8],
+    name: NameRef<'a>,
+    rtype: Type,
+    class: Class,
+    ttl: u32,
+    rdata: &'a [u8],
+}
+
+impl<'a> RecordRef<'a> {
+    /// The owner name of the record, which is decoded on demand.
+    pub fn name(&self) -> NameRef<'a> {
+        self.name
+    }
+
+    /// The type of the record.
+    pub fn rtype(&self) -> Type {
+        self.rtype
+    }
+
+    /// The class of the record.
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// The TTL of the record.
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /// The raw rdata of the record, any names within it may be compressed.
+    pub fn rdata(&self) -> &'a [u8] {
+        self.rdata
+    }
+
+    /// Decodes the rdata into the typed [`RData`].
+    pub fn data(&self) -> Result<RData> {
+        if parser::is_empty_meta_rdata(self.class, self.rdata) {
+            return Ok(RData::Raw(self.rtype.into(), Vec::new()));
+        }
+        parser::read_rdata(self.input, self.rtype, self.rdata)
+    }
+
+    /// Decodes the record into an owned [`ResourceRecord`].
+    pub fn to_record(&self) -> Result<ResourceRecord> {
+        Ok(ResourceRecord {
+            name: self.name.to_name()?,
+            data: self.data()?,
+            class: self.class,
+            ttl: self.ttl,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// A possibly compressed name borrowed from a [`MessageRef`].
+pub struct NameRef<'a> {
+    input: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NameRef<'a> {
+    /// Iterates over the labels of the name, following compression pointers
+    /// as they are reached.
+    ///
+    /// A pointer out of range, a loop of pointers or a reserved label type
+    /// ends the iteration with an error.
+    pub fn labels(&self) -> Labels<'a> {
+        Labels {
+            input: self.input,
+            offset: Some(self.offset),
+            pointers: 0,
+        }
+    }
+
+    /// Decodes the name into an owned [`DomainName`].
+    pub fn to_name(&self) -> Result<DomainName> {
+        parser::read_name_at(self.input, self.offset)
+    }
+}
+
+impl PartialEq<DomainName> for NameRef<'_> {
+    /// Compares the names case-insensitively without decoding into a
+    /// [`DomainName`], a name that can't be decoded is never equal.
+    fn eq(&self, other: &DomainName) -> bool {
+        let mut labels = self.labels();
+        for label in other.labels() {
+            match labels.next() {
+                Some(Ok(l)) if l.eq_ignore_ascii_case(label) => {}
+                _ => return false,
+            }
+        }
+        labels.next().is_none()
+    }
+}
+
+impl fmt::Display for NameRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> std::result::Result<(), fmt::Error> {
+        match self.to_name() {
+            Ok(name) => write!(f, "{}", name),
+            Err(_) => write!(f, "<invalid name>"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// An iterator over the labels of a [`NameRef`].
+pub struct Labels<'a> {
+    input: &'a [u8],
+    /// The offset of the next length octet, `None` once the name is done.
+    offset: Option<usize>,
+    pointers: usize,
+}
+
+impl<'a> Iterator for Labels<'a> {
+    type Item = Result<&'a [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let offset = self.offset?;
+            let len = match self.input.get(offset) {
+                Some(len) => *len as usize,
+                None => return Some(self.error(ParseErrorKind::Truncated, offset)),
+            };
+            match len >> 6 {
+                0b00 if len == 0 => {
+                    self.offset = None;
+                    return None;
+                }
+                0b00 => {
+                    let label = match self.input.get(offset + 1..offset + 1 + len) {
+                        Some(label) => label,
+                        None => return Some(self.error(ParseErrorKind::Truncated, offset)),
+                    };
+                    self.offset = Some(offset + 1 + len);
+                    return Some(Ok(label));
+                }
+                0b11 => {
+                    let low = match self.input.get(offset + 1) {
+                        Some(low) => *low as usize,
+                        None => return Some(self.error(ParseErrorKind::Truncated, offset)),
+                    };
+                    let ptr = ((len & 0x3f) << 8 | low) as u16;
+                    self.pointers += 1;
+                    if self.pointers > MAX_POINTERS {
+                        return Some(self.error(ParseErrorKind::PointerLoop(ptr), offset));
+                    }
+                    if ptr as usize >= self.input.len() {
+                        return Some(self.error(ParseErrorKind::PointerOutOfRange(ptr), offset));
+                    }
+                    self.offset = Some(ptr as usize);
+                }
+                _ => {
+                    let kind = ParseErrorKind::BadLabelLength(len as u8);
+                    return Some(self.error(kind, offset));
+                }
+            }
+        }
+    }
+}
+
+impl Labels<'_> {
+    fn error(&mut self, kind: ParseErrorKind, offset: usize) -> Result<&'static [u8]> {
+        self.offset = None;
+        Err(ParseError::new(kind).offset(offset).into())
+    }
+}
+
+/// Reads the question at the offset, returning it and the offset after it.
+fn read_question_at(input: &[u8], offset: usize) -> Result<(QuestionRef<'_>, usize)> {
+    let name = NameRef { input, offset };
+    let offset = skip_name(input, offset)?;
+    let fixed = input
+        .get(offset..offset + 4)
+        .ok_or_else(|| truncated(ParseErrorKind::Truncated, offset))?;
+    let question = QuestionRef {
+        name,
+        q_type: Type::from(u16::from_be_bytes([fixed[0], fixed[1]])),
+        q_class: Class::from(u16::from_be_bytes([fixed[2], fixed[3]])),
+    };
+    Ok((question, offset + 4))
+}
+
+/// Reads the record at the offset, returning it and the offset after it.
+fn read_record_at(input: &[u8], offset: usize) -> Result<(RecordRef<'_>, usize)> {
+    let name = NameRef { input, offset };
+    let offset = skip_name(input, offset)?;
+    let fixed = input
+        .get(offset..offset + 10)
+        .ok_or_else(|| truncated(ParseErrorKind::Truncated, offset))?;
+    let rdlength = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+    let rdata = input
+        .get(offset + 10..offset + 10 + rdlength)
+        .ok_or_else(|| truncated(ParseErrorKind::TruncatedRData, offset + 10))?;
+    let record = RecordRef {
+        input,
+        name,
+        rtype: Type::from(u16::from_be_bytes([fixed[0], fixed[1]])),
+        class: Class::from(u16::from_be_bytes([fixed[2], fixed[3]])),
+        ttl: u32::from_be_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]),
+        rdata,
+    };
+    Ok((record, offset + 10 + rdlength))
+}
+
+/// Returns the offset after the name at the offset, without following any
+/// compression pointers.
+fn skip_name(input: &[u8], mut offset: usize) -> Result<usize> {
+    loop {
+        let len = *input
+            .get(offset)
+            .ok_or_else(|| truncated(ParseErrorKind::Truncated, offset))?;
+        match len >> 6 {
+            0b00 if len == 0 => return Ok(offset + 1),
+            0b00 => offset += 1 + len as usize,
+            0b11 => return Ok(offset + 2),
+            _ => return Err(truncated(ParseErrorKind::BadLabelLength(len), offset)),
+        }
+    }
+}
+
+/// The error for the part of the message at the offset that couldn't be read.
+fn truncated(kind: ParseErrorKind, offset: usize) -> crate::MessageError {
+    ParseError::new(kind).offset(offset).into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::setup;
+    use crate::{Edns, MessageBuilder, MessageError, QuestionBuilder, ResourceRecordBuilder};
+    use std::net::Ipv4Addr;
+
+    fn message() -> Message {
+        MessageBuilder::new()
+            .id(4321)
+            .qr(true)
+            .question(
+                QuestionBuilder::new()
+                    .name("www.example.com")
+                    .q_type(Type::CNAME)
+                    .build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new(
+                    "www.example.com",
+                    RData::CNAME("web.example.com".into()),
+                )
+                .ttl(60)
+                .build(),
+            )
+            .answer(
+                ResourceRecordBuilder::new(
+                    "web.example.com",
+                    RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+                )
+                .build(),
+            )
+            .name_server(
+                ResourceRecordBuilder::new("example.com", RData::NS("ns.example.com".into()))
+                    .build(),
+            )
+            .edns(Edns::default())
+            .build()
+    }
+
+    #[test]
+    fn test_lazy_read() {
+        setup();
+        let message = message();
+        let mut buf = Vec::new();
+        message.to_bytes(&mut buf).unwrap();
+
+        let message_ref = MessageRef::from_bytes(&buf).unwrap();
+        assert_eq!(message_ref.id(), 4321);
+        assert_eq!(message_ref.header(), &message.header);
+
+        let question = message_ref.questions().next().unwrap();
+        assert_eq!(question.q_type(), Type::CNAME);
+        assert!(question.name() == DomainName::from("WWW.example.com"));
+        assert!(question.name() != DomainName::from("example.com"));
+        assert_eq!(question.to_question().unwrap(), message.questions[0]);
+
+        let answers: Vec<RecordRef> = message_ref.answers().collect();
+        assert_eq!(answers.len(), 2);
+        // The owner of the second answer is compressed against the first.
+        assert_eq!(answers[1].name().to_string(), "web.example.com");
+        assert_eq!(answers[0].ttl(), 60);
+        assert_eq!(answers[0].to_record().unwrap(), message.answers[0]);
+        assert_eq!(answers[1].data().unwrap(), message.answers[1].data);
+
+        assert_eq!(message_ref.name_servers().count(), 1);
+        // The OPT record is still in the additional records.
+        assert_eq!(message_ref.additional_records().count(), 1);
+        assert_eq!(message_ref.edns().unwrap(), message.edns);
+        assert_eq!(message_ref.max_payload_size(), message.max_payload_size());
+
+        assert_eq!(message_ref.to_message().unwrap(), message);
+    }
+
+    #[test]
+    fn test_truncated() {
+        setup();
+        let mut buf = Vec::new();
+        message().to_bytes(&mut buf).unwrap();
+        for len in 0..buf.len() {
+            assert!(MessageRef::from_bytes(&buf[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_to_message_keeps_z() {
+        setup();
+        // A proxy decoding a message with the reserved Z flag set writes it
+        // back unchanged.
+        let mut message = message();
+        message.header.z = true;
+        let mut buf = Vec::new();
+        message.to_bytes(&mut buf).unwrap();
+        assert_eq!(buf[3] & 0x40, 0x40);
+
+        let decoded = MessageRef::from_bytes(&buf).unwrap().to_message().unwrap();
+        assert!(decoded.header.z);
+        let mut written = Vec::new();
+        decoded.to_bytes(&mut written).unwrap();
+        assert_eq!(written, buf);
+    }
+
+    #[test]
+    fn test_pointer_loop() {
+        setup();
+        // A question whose name is a pointer to itself.
+        let input: &[u8] = &[0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 12, 0, 1, 0, 1];
+        let message_ref = MessageRef::from_bytes(input).unwrap();
+        let question = message_ref.questions().next().unwrap();
+        assert!(question.name().labels().any(|l| l.is_err()));
+        assert!(question.name().to_name().is_err());
+        assert!(question.name() != DomainName::from("example.com"));
+
+        // A pointer past the end of the message.
+        let input: &[u8] = &[0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 99, 0, 1, 0, 1];
+        let message_ref = MessageRef::from_bytes(input).unwrap();
+        let question = message_ref.questions().next().unwrap();
+        assert!(question.name().labels().any(|l| l.is_err()));
+        match question.name().labels().next() {
+            Some(Err(MessageError::Parse(e))) => {
+                assert_eq!(e.kind, ParseErrorKind::PointerOutOfRange(99));
+                assert_eq!(e.offset, Some(12));
+            }
+            l => panic!("Unexpected label {:?}", l),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        setup();
+        // The second answer's RDLENGTH runs past the end of the message.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, // header
+            0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4, // A
+            0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, // A
+        ];
+        match MessageRef::from_bytes(input) {
+            Err(MessageError::Parse(e)) => {
+                assert_eq!(e.section, Some(Section::Answer));
+                assert_eq!(e.index, Some(1));
+                assert_eq!(e.offset, Some(38));
+                assert_eq!(e.kind, ParseErrorKind::TruncatedRData);
+            }
+            r => panic!("Unexpected result {:?}", r),
+        }
+    }
+}
@@ -1,12 +1,123 @@
-use crate::error::MessageError;
-use crate::{Class, Header, Message, OpCode, Question, RCode, RData, ResourceRecord, Result, Type};
+use crate::error::{MessageError, ParseError, ParseErrorKind, Section};
+use crate::{
+    Class, DomainName, Edns, Header, Message, OpCode, Question, RCode, RData, ResourceRecord,
+    Result, SvcParams, Type,
+};
 use nom::bits::complete::take as take_bits;
 use nom::bytes::complete::take as take_bytes;
 use nom::combinator::map_res;
+use nom::error::ErrorKind;
+use nom::ErrorConvert;
 use nom::IResult;
+use nom::Offset;
 use std::collections::HashSet;
 use std::net::{Ipv4Addr, Ipv6Addr};
-use tracing::{error, instrument, trace};
+use tracing::{error, instrument, trace, warn};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// How strictly a message is checked as it's parsed, see
+/// [`Message::from_bytes_with`].
+///
+/// Strict parsing rejects a message with trailing data after the last record,
+/// RDATA that is too long for its type, a name longer than 255 octets, a
+/// compression pointer that doesn't point back to an earlier part of the
+/// message, or the reserved Z flag set.
+///
+/// Lenient parsing allows all of those with a warning, and salvages what it
+/// can from worse problems. RDATA that can't be read is kept as
+/// [`RData::Raw`], a record or question whose name can't be read is skipped,
+/// and a message that ends part way through a record keeps the records
+/// before it. The header must always be complete.
+///
+/// The default, which [`Message::from_bytes`] uses, allows what strict parsing
+/// rejects with a warning, but fails on a message that can't be read rather
+/// than salvaging it.
+pub struct ParseOptions {
+    strict: bool,
+    salvage: bool,
+}
+
+impl ParseOptions {
+    /// Rejects anything that isn't valid.
+    pub fn strict() -> Self {
+        ParseOptions {
+            strict: true,
+            salvage: false,
+        }
+    }
+
+    /// Salvages as much of the message as possible, with warnings for what
+    /// wasn't valid.
+    pub fn lenient() -> Self {
+        ParseOptions {
+            strict: false,
+            salvage: true,
+        }
+    }
+
+    /// Returns true for strict parsing.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+}
+
+/// Applies the [`ParseOptions`] to the problems found while parsing, which
+/// fail in strict mode and are otherwise kept as warnings.
+struct Checks {
+    options: ParseOptions,
+    /// Where in the message the parser is, for the errors that don't know.
+    section: Option<Section>,
+    index: Option<usize>,
+    warnings: Vec<ParseError>,
+}
+
+impl Checks {
+    fn new(options: ParseOptions) -> Self {
+        Checks {
+            options,
+            section: None,
+            index: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn at(&mut self, section: Option<Section>, index: Option<usize>) {
+        self.section = section;
+        self.index = index;
+    }
+
+    /// Returns the error in strict mode, or keeps it as a warning otherwise.
+    /// Only [`MessageError::Parse`] errors can be kept.
+    fn tolerate(&mut self, error: MessageError) -> Result<()> {
+        let strict = self.options.strict;
+        self.keep(error, strict)
+    }
+
+    /// Keeps the error of a part of the message that couldn't be read as a
+    /// warning in lenient mode, so the rest can be salvaged.
+    fn salvage(&mut self, error: MessageError) -> Result<()> {
+        let salvage = self.options.salvage;
+        self.keep(error, !salvage)
+    }
+
+    fn keep(&mut self, error: MessageError, fail: bool) -> Result<()> {
+        match error {
+            MessageError::Parse(mut e) => {
+                if e.section.is_none() {
+                    e.section = self.section;
+                    e.index = self.index;
+                }
+                if fail {
+                    return Err(e.into());
+                }
+                warn!("Parsed a message with: {}", e);
+                self.warnings.push(e);
+                Ok(())
+            }
+            e => Err(e),
+        }
+    }
+}
 
 #[derive(Debug)]
 struct RawHeader {
@@ -25,18 +136,76 @@ struct RawQuestion {
 }
 
 #[derive(Debug)]
-struct RawResourceRecord {
+struct RawResourceRecord<'a> {
     name: Vec<Name>,
     rtype: Type,
     class: Class,
     ttl: u32,
-    rdata: Vec<u8>,
+    rdata: &'a [u8],
+}
+
+/// The error from the nom parsers, the input is where the parser failed.
+#[derive(Debug)]
+pub(crate) struct WireError<I> {
+    input: I,
+    pub(crate) kind: ParseErrorKind,
+}
+
+/// The result of the nom parsers.
+type WireResult<'a, T> = IResult<&'a [u8], T, WireError<&'a [u8]>>;
+
+impl<I> nom::error::ParseError<I> for WireError<I> {
+    /// Every parser reads a fixed number of octets, so the only way for one
+    /// to fail by itself is for the input to end.
+    fn from_error_kind(input: I, _kind: ErrorKind) -> Self {
+        WireError {
+            input,
+            kind: ParseErrorKind::Truncated,
+        }
+    }
+
+    fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I, E> nom::error::FromExternalError<I, E> for WireError<I> {
+    fn from_external_error(input: I, kind: ErrorKind, _e: E) -> Self {
+        nom::error::ParseError::from_error_kind(input, kind)
+    }
+}
+
+impl<I> ErrorConvert<WireError<I>> for WireError<(I, usize)> {
+    fn convert(self) -> WireError<I> {
+        WireError {
+            input: self.input.0,
+            kind: self.kind,
+        }
+    }
+}
+
+/// Converts an error from the nom parsers into a [`ParseError`] at the offset
+/// that the parser failed, the input of the error must be the end of the
+/// message.
+fn wire_error(
+    message: &[u8],
+    error: nom::Err<WireError<&[u8]>>,
+    section: Option<Section>,
+    index: Option<usize>,
+) -> MessageError {
+    let (kind, offset) = match error {
+        nom::Err::Error(e) | nom::Err::Failure(e) => (e.kind, message.len() - e.input.len()),
+        nom::Err::Incomplete(_) => (ParseErrorKind::Truncated, message.len()),
+    };
+    MessageError::from(ParseError::new(kind).offset(offset)).at(section, index, offset)
 }
 
 #[derive(Debug, Clone)]
 enum Name {
-    Name(String),
-    Pointer(u16),
+    Label(Vec<u8>),
+    /// A pointer along with the number of octets from the pointer to the end
+    /// of the message, which is where it is when counted from the end.
+    Pointer(u16, usize),
     ResolvedPtr(Vec<Name>),
 }
 
@@ -46,78 +215,408 @@ impl From<RawHeader> for Header {
     }
 }
 
-impl From<RawQuestion> for Question {
-    #[instrument]
-    fn from(iq: RawQuestion) -> Self {
-        Question {
-            q_name: flatten_to_string(&iq.qname),
-            q_type: iq.qtype,
-            q_class: iq.qclass,
+/// Reads the typed [`RData`] from the rdata of a record, names in the rdata
+/// are dereferenced against the original input.
+///
+/// The rdata must be a part of the input, and is parsed strictly.
+///
+/// Errors are [`ParseErrorKind::TruncatedRData`] if the rdata is too short for
+/// the type, [`ParseErrorKind::TrailingRData`] if it's too long, or
+/// [`ParseErrorKind::InvalidRData`] caused by the reason it's not valid,
+/// without the offset of the rdata.
+#[instrument(skip(input, rdata))]
+pub(crate) fn read_rdata(input: &[u8], rtype: Type, rdata: &[u8]) -> Result<RData> {
+    read_checked_rdata(
+        input,
+        rtype,
+        rdata,
+        &mut Checks::new(ParseOptions::strict()),
+    )
+}
+
+/// RFC2136 - the prerequisites and updates of a dynamic update with a class of
+/// ANY or NONE may have no RDATA whatever their type, which is kept as an
+/// empty [`RData::Raw`] rather than being too short for the type.
+pub(crate) fn is_empty_meta_rdata(class: Class, rdata: &[u8]) -> bool {
+    rdata.is_empty() && matches!(class, Class::STAR | Class::NONE)
+}
+
+fn read_checked_rdata(
+    input: &[u8],
+    rtype: Type,
+    rdata: &[u8],
+    checks: &mut Checks,
+) -> Result<RData> {
+    let (data, rest) = read_typed_rdata(input, rtype, rdata, checks).map_err(|e| match e {
+        MessageError::Parse(e) if e.kind == ParseErrorKind::Truncated => {
+            ParseErrorKind::TruncatedRData.into()
         }
+        e @ MessageError::Parse(_) => e,
+        e => ParseError::new(ParseErrorKind::InvalidRData)
+            .caused_by(e)
+            .into(),
+    })?;
+    if !rest.is_empty() {
+        let kind = ParseErrorKind::TrailingRData(rest.len());
+        checks.tolerate(ParseError::new(kind).offset(input.offset(rest)).into())?;
     }
+    Ok(data)
 }
 
-/// We can't implement the From trait here as we need a reference to the
-/// original input in order to dereference the name pointers.
-#[instrument(skip(input))]
-fn from_irr(input: &[u8], irr: RawResourceRecord) -> Result<ResourceRecord> {
-    let rdata = match irr.rtype {
-        Type::A => RData::A(Ipv4Addr::new(
-            irr.rdata[0],
-            irr.rdata[1],
-            irr.rdata[2],
-            irr.rdata[3],
-        )),
+/// Reads the rdata, returning it along with anything left over after the
+/// fields of the type.
+fn read_typed_rdata<'a>(
+    input: &[u8],
+    rtype: Type,
+    rdata: &'a [u8],
+    checks: &mut Checks,
+) -> Result<(RData, &'a [u8])> {
+    // Types that end with a field taking up the rest of the rdata leave
+    // nothing over.
+    let mut rest: &[u8] = &[];
+    let data = match rtype {
+        Type::A => {
+            let (i, address) = read_u32(rdata)?;
+            rest = i;
+            RData::A(Ipv4Addr::from(address))
+        }
         Type::NS => {
-            let (_, mut names) = read_names(&irr.rdata)?;
-            resolve_names(input, &mut names, &mut HashSet::new())?;
-            let name = flatten_to_string(&names);
+            let (i, name) = read_rdata_name(input, rdata, checks)?;
+            rest = i;
             RData::NS(name)
         }
+        Type::MD => {
+            let (i, name) = read_rdata_name(input, rdata, checks)?;
+            rest = i;
+            RData::MD(name)
+        }
+        Type::MF => {
+            let (i, name) = read_rdata_name(input, rdata, checks)?;
+            rest = i;
+            RData::MF(name)
+        }
         Type::CNAME => {
-            let (_, mut names) = read_names(&irr.rdata)?;
-            resolve_names(input, &mut names, &mut HashSet::new())?;
-            let name = flatten_to_string(&names);
+            let (i, name) = read_rdata_name(input, rdata, checks)?;
+            rest = i;
             RData::CNAME(name)
         }
         Type::SOA => {
-            let (i, mut mnames) = read_names(&irr.rdata)?;
-            resolve_names(input, &mut mnames, &mut HashSet::new())?;
-            let mname = flatten_to_string(&mnames);
-
-            let (i, mut rnames) = read_names(i)?;
-            resolve_names(input, &mut rnames, &mut HashSet::new())?;
-            let rname = flatten_to_string(&rnames);
+            let (i, mname) = read_rdata_name(input, rdata, checks)?;
+            let (i, rname) = read_rdata_name(input, i, checks)?;
 
             let (i, serial) = read_u32(i)?;
             let (i, refresh) = read_u32(i)?;
             let (i, retry) = read_u32(i)?;
             let (i, expire) = read_u32(i)?;
-            let (_, minimum) = read_u32(i)?;
+            let (i, minimum) = read_u32(i)?;
+            rest = i;
 
             RData::SOA(mname, rname, serial, refresh, retry, expire, minimum)
         }
-        Type::TXT => RData::TXT(String::from_utf8(irr.rdata)?),
+        Type::MB => {
+            let (i, name) = read_rdata_name(input, rdata, checks)?;
+            rest = i;
+            RData::MB(name)
+        }
+        Type::MG => {
+            let (i, name) = read_rdata_name(input, rdata, checks)?;
+            rest = i;
+            RData::MG(name)
+        }
+        Type::MR => {
+            let (i, name) = read_rdata_name(input, rdata, checks)?;
+            rest = i;
+            RData::MR(name)
+        }
+        Type::NULL => RData::NULL(rdata.to_vec()),
+        Type::WKS => {
+            let (i, address) = read_u32(rdata)?;
+            let (bitmap, protocol) = read_u8(i)?;
+            RData::WKS(Ipv4Addr::from(address), protocol, bitmap.to_vec())
+        }
+        Type::PTR => {
+            let (i, name) = read_rdata_name(input, rdata, checks)?;
+            rest = i;
+            RData::PTR(name)
+        }
+        Type::HINFO => {
+            let (i, cpu) = read_character_string(rdata)?;
+            let (i, os) = read_character_string(i)?;
+            rest = i;
+            RData::HINFO(cpu, os)
+        }
+        Type::MINFO => {
+            let (i, rmailbx) = read_rdata_name(input, rdata, checks)?;
+            let (i, emailbx) = read_rdata_name(input, i, checks)?;
+            rest = i;
+            RData::MINFO(rmailbx, emailbx)
+        }
+        Type::MX => {
+            let (i, preference) = read_u16(rdata)?;
+            let (i, exchange) = read_rdata_name(input, i, checks)?;
+            rest = i;
+            RData::MX(preference, exchange)
+        }
+        Type::TXT => RData::TXT(read_character_strings(rdata)?.1),
         Type::AAAA => {
+            let (i, address) = take_bytes(16usize)(rdata)?;
+            rest = i;
             let mut v6: [u8; 16] = [0; 16];
-            v6.copy_from_slice(&input[0..16]);
+            v6.copy_from_slice(address);
             RData::AAAA(Ipv6Addr::from(v6))
         }
-        _ => RData::Raw(irr.rtype.into(), irr.rdata),
+        Type::LOC => {
+            let (i, version) = read_u8(rdata)?;
+            let (i, size) = read_u8(i)?;
+            let (i, horiz_pre) = read_u8(i)?;
+            let (i, vert_pre) = read_u8(i)?;
+            let (i, latitude) = read_u32(i)?;
+            let (i, longitude) = read_u32(i)?;
+            let (i, altitude) = read_u32(i)?;
+            rest = i;
+            RData::LOC(
+                version, size, horiz_pre, vert_pre, latitude, longitude, altitude,
+            )
+        }
+        Type::SRV => {
+            let (i, priority) = read_u16(rdata)?;
+            let (i, weight) = read_u16(i)?;
+            let (i, port) = read_u16(i)?;
+            let (i, target) = read_rdata_name(input, i, checks)?;
+            rest = i;
+            RData::SRV(priority, weight, port, target)
+        }
+        Type::NAPTR => {
+            let (i, order) = read_u16(rdata)?;
+            let (i, preference) = read_u16(i)?;
+            let (i, flags) = read_character_string(i)?;
+            let (i, services) = read_character_string(i)?;
+            let (i, regexp) = read_character_string(i)?;
+            let (i, replacement) = read_rdata_name(input, i, checks)?;
+            rest = i;
+            RData::NAPTR(order, preference, flags, services, regexp, replacement)
+        }
+        Type::DNAME => {
+            let (i, name) = read_rdata_name(input, rdata, checks)?;
+            rest = i;
+            RData::DNAME(name)
+        }
+        Type::DS | Type::CDS => {
+            let (i, key_tag) = read_u16(rdata)?;
+            let (i, algorithm) = read_u8(i)?;
+            let (digest, digest_type) = read_u8(i)?;
+            if rtype == Type::DS {
+                RData::DS(key_tag, algorithm, digest_type, digest.to_vec())
+            } else {
+                RData::CDS(key_tag, algorithm, digest_type, digest.to_vec())
+            }
+        }
+        Type::RRSIG => {
+            let (i, type_covered) = read_u16(rdata)?;
+            let (i, algorithm) = read_u8(i)?;
+            let (i, labels) = read_u8(i)?;
+            let (i, original_ttl) = read_u32(i)?;
+            let (i, expiration) = read_u32(i)?;
+            let (i, inception) = read_u32(i)?;
+            let (i, key_tag) = read_u16(i)?;
+            let (signature, signer) = read_rdata_name(input, i, checks)?;
+            RData::RRSIG(
+                Type::from(type_covered),
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                signer,
+                signature.to_vec(),
+            )
+        }
+        Type::NSEC => {
+            let (i, next) = read_rdata_name(input, rdata, checks)?;
+            RData::NSEC(next, read_type_bitmap(input, i)?)
+        }
+        Type::DNSKEY | Type::CDNSKEY => {
+            let (i, flags) = read_u16(rdata)?;
+            let (i, protocol) = read_u8(i)?;
+            let (key, algorithm) = read_u8(i)?;
+            if rtype == Type::DNSKEY {
+                RData::DNSKEY(flags, protocol, algorithm, key.to_vec())
+            } else {
+                RData::CDNSKEY(flags, protocol, algorithm, key.to_vec())
+            }
+        }
+        Type::NSEC3 => {
+            let (i, hash_algorithm) = read_u8(rdata)?;
+            let (i, flags) = read_u8(i)?;
+            let (i, iterations) = read_u16(i)?;
+            let (i, salt) = read_character_string(i)?;
+            let (i, next) = read_character_string(i)?;
+            let types = read_type_bitmap(input, i)?;
+            RData::NSEC3(hash_algorithm, flags, iterations, salt, next, types)
+        }
+        Type::NSEC3PARAM => {
+            let (i, hash_algorithm) = read_u8(rdata)?;
+            let (i, flags) = read_u8(i)?;
+            let (i, iterations) = read_u16(i)?;
+            let (i, salt) = read_character_string(i)?;
+            rest = i;
+            RData::NSEC3PARAM(hash_algorithm, flags, iterations, salt)
+        }
+        Type::SSHFP => {
+            let (i, algorithm) = read_u8(rdata)?;
+            let (fingerprint, fp_type) = read_u8(i)?;
+            RData::SSHFP(algorithm, fp_type, fingerprint.to_vec())
+        }
+        Type::TLSA => {
+            let (i, usage) = read_u8(rdata)?;
+            let (i, selector) = read_u8(i)?;
+            let (data, matching_type) = read_u8(i)?;
+            RData::TLSA(usage, selector, matching_type, data.to_vec())
+        }
+        Type::SVCB | Type::HTTPS => {
+            let (i, priority) = read_u16(rdata)?;
+            let (i, target) = read_rdata_name(input, i, checks)?;
+            let params = SvcParams::from_bytes(i)?;
+            if rtype == Type::SVCB {
+                RData::SVCB(priority, target, params)
+            } else {
+                RData::HTTPS(priority, target, params)
+            }
+        }
+        Type::SPF => RData::SPF(read_character_strings(rdata)?.1),
+        Type::URI => {
+            let (i, priority) = read_u16(rdata)?;
+            let (target, weight) = read_u16(i)?;
+            RData::URI(priority, weight, target.to_vec())
+        }
+        Type::CAA => {
+            let (i, flags) = read_u8(rdata)?;
+            let (value, tag) = read_character_string(i)?;
+            RData::CAA(flags, tag, value.to_vec())
+        }
+        _ => RData::Raw(rtype.into(), rdata.to_vec()),
     };
 
-    trace!("Parsed rdata as {}", rdata);
+    trace!("Parsed rdata as {}", data);
+
+    Ok((data, rest))
+}
+
+/// Reads the name at the offset in the input, dereferencing any compression
+/// pointers.
+#[instrument(skip(input))]
+pub(crate) fn read_name_at(input: &[u8], offset: usize) -> Result<DomainName> {
+    let (_, names) = read_names(&input[offset..]).map_err(|e| wire_error(input, e, None, None))?;
+    read_domain_name(input, names, &mut Checks::new(ParseOptions::strict()))
+        .map_err(|e| e.at(None, None, offset))
+}
+
+/// Reads the header, returning it along with the number of entries in the
+/// question, answer, authority and additional sections.
+#[instrument(skip(input))]
+pub(crate) fn read_header_counts(input: &[u8]) -> Result<(Header, [u16; 4])> {
+    let (_, raw) =
+        read_header(input).map_err(|e| wire_error(input, e, Some(Section::Header), None))?;
+    Ok((
+        raw.header,
+        [raw.qd_count, raw.an_count, raw.ns_count, raw.ar_count],
+    ))
+}
+
+/// Reads a name from the rdata, dereferencing any compression pointers
+/// against the original input.
+#[instrument(skip(input, rdata, checks))]
+fn read_rdata_name<'a>(
+    input: &[u8],
+    rdata: &'a [u8],
+    checks: &mut Checks,
+) -> Result<(&'a [u8], DomainName)> {
+    let (i, mut names) = read_names(rdata)?;
+
+    // The pointer was counted from the end of the rdata, rather than the end
+    // of the message.
+    let after_rdata = input.len() - input.offset(rdata) - rdata.len();
+    for name in names.iter_mut() {
+        if let Name::Pointer(_, remaining) = name {
+            *remaining += after_rdata;
+        }
+    }
+    Ok((i, read_domain_name(input, names, checks)?))
+}
+
+/// Resolves the compression pointers in the names read from the input, and
+/// checks the name isn't longer than 255 octets.
+fn read_domain_name(input: &[u8], mut names: Vec<Name>, checks: &mut Checks) -> Result<DomainName> {
+    resolve_names(input, &mut names, &mut HashSet::new(), checks)?;
+    let name = flatten_to_name(&names);
+    if name.wire_len() > 255 {
+        checks.tolerate(ParseErrorKind::NameTooLong(name.wire_len()).into())?;
+    }
+    Ok(name)
+}
+
+/// Reads an RFC4034 type bitmap until the rdata is consumed. Errors are
+/// [`ParseErrorKind::BadTypeBitmap`] at the offset of the window that isn't
+/// valid.
+#[instrument(skip(input, rdata))]
+fn read_type_bitmap(input: &[u8], rdata: &[u8]) -> Result<Vec<Type>> {
+    trace!("reading type bitmap");
+    let mut types = Vec::new();
+    let mut i = rdata;
+    let mut last_window = None;
+    while !i.is_empty() {
+        let bad_window = || ParseError::new(ParseErrorKind::BadTypeBitmap).offset(input.offset(i));
+        // Each window is a number and a length, in increasing order of
+        // number, with a bitmap of 1 to 32 octets.
+        if i.len() < 2 {
+            return Err(bad_window().into());
+        }
+        let window = i[0];
+        let len = i[1] as usize;
+        if last_window.is_some_and(|w| w >= window) || len == 0 || len > 32 || i.len() < 2 + len {
+            return Err(bad_window().into());
+        }
+        for (octet, bits) in i[2..2 + len].iter().enumerate() {
+            for bit in 0..8 {
+                if bits & (0x80 >> bit) != 0 {
+                    let t = (window as u16) << 8 | (octet * 8 + bit) as u16;
+                    types.push(Type::from(t));
+                }
+            }
+        }
+        last_window = Some(window);
+        i = &i[2 + len..];
+    }
+    Ok(types)
+}
+
+/// Reads an RFC1035 <character-string> - a length octet followed by that
+/// number of octets.
+#[instrument(skip(input))]
+fn read_character_string(input: &[u8]) -> WireResult<'_, Vec<u8>> {
+    trace!("reading character-string");
+    nom::combinator::map(
+        nom::multi::length_data(nom::number::complete::be_u8),
+        |s: &[u8]| s.to_vec(),
+    )(input)
+}
+
+/// Reads <character-string>s until the input is consumed.
+#[instrument(skip(input))]
+fn read_character_strings(input: &[u8]) -> WireResult<'_, Vec<Vec<u8>>> {
+    trace!("reading character-strings");
+    nom::combinator::all_consuming(nom::multi::many0(read_character_string))(input)
+}
 
-    Ok(ResourceRecord {
-        name: flatten_to_string(&irr.name),
-        data: rdata,
-        class: irr.class,
-        ttl: irr.ttl,
-    })
+#[instrument(skip(input))]
+fn read_u8(input: &[u8]) -> WireResult<'_, u8> {
+    trace!("reading u8");
+    nom::number::complete::be_u8(input)
 }
 
 #[instrument(skip(input))]
-fn read_u16(input: &[u8]) -> IResult<&[u8], u16> {
+fn read_u16(input: &[u8]) -> WireResult<'_, u16> {
     trace!("reading u16");
     nom::combinator::map(nom::bytes::complete::take(2usize), |input: &[u8]| {
         let b = [input[0], input[1]];
@@ -126,7 +625,7 @@ fn read_u16(input: &[u8]) -> IResult<&[u8], u16> {
 }
 
 #[instrument(skip(input))]
-fn read_u32(input: &[u8]) -> IResult<&[u8], u32> {
+fn read_u32(input: &[u8]) -> WireResult<'_, u32> {
     trace!("reading u32");
     nom::combinator::map(nom::bytes::complete::take(4usize), |input: &[u8]| {
         let b = [input[0], input[1], input[2], input[3]];
@@ -135,9 +634,8 @@ fn read_u32(input: &[u8]) -> IResult<&[u8], u32> {
 }
 
 #[instrument(skip(input))]
-fn read_header(input: &[u8]) -> IResult<&[u8], RawHeader> {
+fn read_header(input: &[u8]) -> WireResult<'_, RawHeader> {
     use nom::bits::bits;
-    use nom::bits::complete::tag as tag_bits;
     use nom::combinator::map;
 
     map_res(take_bytes(12usize), |input| -> Result<RawHeader> {
@@ -145,33 +643,20 @@ fn read_header(input: &[u8]) -> IResult<&[u8], RawHeader> {
         let (input, id) = read_u16(input)?;
 
         trace!("reading flags");
-        let (input, (qr, opcode, aa, tc, rd, ra, ad, cd, rcode)) =
-            bits::<_, _, nom::error::Error<_>, nom::error::Error<_>, _>(|i| {
+        let (input, (qr, opcode, aa, tc, rd, ra, z, ad, cd, rcode)) =
+            bits::<_, _, WireError<_>, WireError<_>, _>(|i| {
                 let is_one = |s: u8| s == 1;
                 let (i, qr) = map(take_bits(1usize), is_one)(i)?;
-                let (i, opcode) = map(take_bits(4usize), |s: u8| match s {
-                    0 => OpCode::Query,
-                    1 => OpCode::IQuery,
-                    2 => OpCode::Status,
-                    n => OpCode::Unknown(n),
-                })(i)?;
+                let (i, opcode) = map(take_bits(4usize), |s: u8| OpCode::from(s))(i)?;
                 let (i, aa) = map(take_bits(1usize), is_one)(i)?;
                 let (i, tc) = map(take_bits(1usize), is_one)(i)?;
                 let (i, rd) = map(take_bits(1usize), is_one)(i)?;
                 let (i, ra) = map(take_bits(1usize), is_one)(i)?;
-                let (i, _) = tag_bits(0, 1usize)(i)?;
+                let (i, z) = map(take_bits(1usize), is_one)(i)?;
                 let (i, ad) = map(take_bits(1usize), is_one)(i)?;
                 let (i, cd) = map(take_bits(1usize), is_one)(i)?;
-                let (i, rcode) = match map(take_bits(4usize), |s: u8| s)(i)? {
-                    (i, 0) => (i, RCode::NoError),
-                    (i, 1) => (i, RCode::FormatError),
-                    (i, 2) => (i, RCode::ServerFailure),
-                    (i, 3) => (i, RCode::NameError),
-                    (i, 4) => (i, RCode::NotImplemented),
-                    (i, 5) => (i, RCode::Refused),
-                    (i, x) => (i, RCode::Unknown(x)),
-                };
-                Ok(((i), (qr, opcode, aa, tc, rd, ra, ad, cd, rcode)))
+                let (i, rcode) = map(take_bits(4usize), |s: u16| RCode::from(s))(i)?;
+                Ok(((i), (qr, opcode, aa, tc, rd, ra, z, ad, cd, rcode)))
             })(input)?;
 
         let (input, qd_count) = read_u16(input)?;
@@ -188,6 +673,7 @@ fn read_header(input: &[u8]) -> IResult<&[u8], RawHeader> {
                 tc,
                 rd,
                 ra,
+                z,
                 ad,
                 cd,
                 rcode,
@@ -201,7 +687,7 @@ fn read_header(input: &[u8]) -> IResult<&[u8], RawHeader> {
 }
 
 #[instrument(skip(input))]
-fn read_names(input: &[u8]) -> IResult<&[u8], Vec<Name>> {
+fn read_names(input: &[u8]) -> WireResult<'_, Vec<Name>> {
     trace!("reading names");
     use nom::bits::bits;
 
@@ -213,11 +699,12 @@ fn read_names(input: &[u8]) -> IResult<&[u8], Vec<Name>> {
     enum NameRecord {
         Offset(u16),
         Length(u8),
+        Reserved(u8),
     }
 
     loop {
         // Read the length, or the offset if using compression.
-        let (i, name_record) = bits::<_, _, nom::error::Error<_>, nom::error::Error<_>, _>(|i| {
+        let (i, name_record) = bits::<_, _, WireError<_>, WireError<_>, _>(|i| {
             let (i, flags): (_, u8) = take_bits(2usize)(i)?;
             if flags == 0b11 {
                 // This is a compressed offset
@@ -227,7 +714,11 @@ fn read_names(input: &[u8]) -> IResult<&[u8], Vec<Name>> {
             } else {
                 let mut len: u8 = flags << 6;
                 let (i, l): (_, u8) = take_bits(6usize)(i)?;
-                len = len | l;
+                len |= l;
+                if flags != 0b00 {
+                    // The 0b01 and 0b10 label types are reserved.
+                    return Ok((i, NameRecord::Reserved(len)));
+                }
                 trace!("Name of length {} found", len);
                 Ok((i, NameRecord::Length(len)))
             }
@@ -235,7 +726,7 @@ fn read_names(input: &[u8]) -> IResult<&[u8], Vec<Name>> {
 
         match name_record {
             NameRecord::Offset(offset) => {
-                qname.push(Name::Pointer(offset));
+                qname.push(Name::Pointer(offset, input.len()));
                 input = i;
                 break;
             }
@@ -246,19 +737,23 @@ fn read_names(input: &[u8]) -> IResult<&[u8], Vec<Name>> {
                     break;
                 }
 
-                let (i, name) = map_res(take_bytes(length), |i| -> Result<Name> {
-                    Ok(Name::Name(std::str::from_utf8(i)?.to_string()))
-                })(i)?;
-                qname.push(name);
+                let (i, label) = take_bytes(length)(i)?;
+                qname.push(Name::Label(label.to_vec()));
                 input = i;
             }
+            NameRecord::Reserved(length) => {
+                return Err(nom::Err::Failure(WireError {
+                    input,
+                    kind: ParseErrorKind::BadLabelLength(length),
+                }));
+            }
         }
     }
     Ok((input, qname))
 }
 
 #[instrument(skip(input))]
-fn read_question(input: &[u8]) -> IResult<&[u8], RawQuestion> {
+fn read_question(input: &[u8]) -> WireResult<'_, RawQuestion> {
     trace!("reading question");
     let (input, qname) = read_names(input)?;
 
@@ -282,7 +777,7 @@ fn read_question(input: &[u8]) -> IResult<&[u8], RawQuestion> {
 }
 
 #[instrument(skip(input))]
-fn read_resource_record(input: &[u8]) -> IResult<&[u8], RawResourceRecord> {
+fn read_resource_record(input: &[u8]) -> WireResult<'_, RawResourceRecord<'_>> {
     trace!("reading resource record");
     let (input, name) = read_names(input)?;
     let (input, rtype) = {
@@ -298,9 +793,13 @@ fn read_resource_record(input: &[u8]) -> IResult<&[u8], RawResourceRecord> {
 
     trace!("Found rdata of length: {}", rdlength);
 
-    let (input, rdata) = take_bytes(rdlength)(input)?;
+    let (input, rdata) = take_bytes(rdlength)(input).map_err(|e| {
+        e.map(|e: WireError<_>| WireError {
+            kind: ParseErrorKind::TruncatedRData,
+            ..e
+        })
+    })?;
     trace!("rdata: {:?}", rdata);
-    let rdata = Vec::from(rdata);
     Ok((
         input,
         RawResourceRecord {
@@ -313,144 +812,227 @@ fn read_resource_record(input: &[u8]) -> IResult<&[u8], RawResourceRecord> {
     ))
 }
 
+/// Reads the whole message, along with the warnings for the problems that the
+/// options allowed. Errors say which section, question or record and offset
+/// the message couldn't be parsed at.
 #[instrument(skip(input))]
-pub(crate) fn read_message(input: &[u8]) -> IResult<&[u8], Message> {
+pub(crate) fn read_message(
+    input: &[u8],
+    options: ParseOptions,
+) -> Result<(Message, Vec<ParseError>)> {
     trace!("reading message");
-    // TODO - There has to be a better way to consume all of the input than this...
-    map_res(take_bytes(input.len()), as_message)(input)
-}
-
-#[instrument(skip(input))]
-fn as_message<'a>(input: &[u8]) -> Result<Message> {
     let original_input = input;
-    let (mut input, header) = read_header(input)?;
+    let offset_of = |i: &[u8]| original_input.len() - i.len();
+    let mut checks = Checks::new(options);
+
+    let (mut input, raw_header) = read_header(input)
+        .map_err(|e| wire_error(original_input, e, Some(Section::Header), None))?;
+    if raw_header.header.z {
+        checks.at(Some(Section::Header), None);
+        checks.tolerate(
+            ParseError::new(ParseErrorKind::ReservedFlag)
+                .offset(2)
+                .into(),
+        )?;
+    }
+
+    // In lenient mode whatever was read before the message ends part way
+    // through a question or record is kept.
+    let mut complete = true;
 
     let mut questions = Vec::new();
-    for _ in 0..header.qd_count {
-        let (i, question) = read_question(input)?;
+    for index in 0..raw_header.qd_count as usize {
+        checks.at(Some(Section::Question), Some(index));
+        let offset = offset_of(input);
+        let (i, question) = match read_question(input) {
+            Ok(r) => r,
+            Err(e) => {
+                checks.salvage(wire_error(original_input, e, None, None))?;
+                complete = false;
+                break;
+            }
+        };
         input = i;
-        questions.push(question);
+        match read_domain_name(original_input, question.qname, &mut checks) {
+            Ok(q_name) => questions.push(Question {
+                q_name,
+                q_type: question.qtype,
+                q_class: question.qclass,
+            }),
+            Err(e) => checks.salvage(e.at(None, None, offset))?,
+        }
     }
 
-    let mut answers = Vec::new();
-    for _ in 0..header.an_count {
-        let (i, record) = read_resource_record(input)?;
-        input = i;
-        answers.push(record);
+    let mut sections = [
+        (Section::Answer, raw_header.an_count, Vec::new()),
+        (Section::Authority, raw_header.ns_count, Vec::new()),
+        (Section::Additional, raw_header.ar_count, Vec::new()),
+    ];
+    let mut edns = None;
+    'sections: for (section, count, records) in sections.iter_mut() {
+        for index in 0..*count as usize {
+            if !complete {
+                break 'sections;
+            }
+            checks.at(Some(*section), Some(index));
+            let offset = offset_of(input);
+            let (i, record) = match read_resource_record(input) {
+                Ok(r) => r,
+                Err(e) => {
+                    checks.salvage(wire_error(original_input, e, None, None))?;
+                    complete = false;
+                    break 'sections;
+                }
+            };
+            input = i;
+            let rdata_offset = offset_of(input) - record.rdata.len();
+
+            if record.rtype == Type::OPT && *section == Section::Additional {
+                match read_opt(&record, edns.is_some()) {
+                    Ok(opt) => edns = Some(opt),
+                    Err(e) => {
+                        let kind = ParseErrorKind::InvalidOpt;
+                        let e = ParseError::new(kind).caused_by(e).offset(offset);
+                        checks.salvage(e.into())?;
+                    }
+                }
+                continue;
+            }
+
+            let name = match read_domain_name(original_input, record.name, &mut checks) {
+                Ok(name) => name,
+                Err(e) => {
+                    checks.salvage(e.at(None, None, offset))?;
+                    continue;
+                }
+            };
+            let data = if is_empty_meta_rdata(record.class, record.rdata) {
+                RData::Raw(record.rtype.into(), Vec::new())
+            } else {
+                match read_checked_rdata(original_input, record.rtype, record.rdata, &mut checks) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        checks.salvage(e.at(None, None, rdata_offset))?;
+                        RData::Raw(record.rtype.into(), record.rdata.to_vec())
+                    }
+                }
+            };
+            records.push(ResourceRecord {
+                name,
+                data,
+                class: record.class,
+                ttl: record.ttl,
+            });
+        }
     }
+    let [(_, _, answers), (_, _, name_servers), (_, _, additional_records)] = sections;
 
-    let mut name_servers = Vec::new();
-    for _ in 0..header.ns_count {
-        let (i, record) = read_resource_record(input)?;
-        input = i;
-        name_servers.push(record);
+    if complete && !input.is_empty() {
+        checks.at(None, None);
+        let kind = ParseErrorKind::TrailingData(input.len());
+        checks.tolerate(ParseError::new(kind).offset(offset_of(input)).into())?;
     }
 
-    let mut additional_records = Vec::new();
-    for _ in 0..header.ar_count {
-        let (i, record) = read_resource_record(input)?;
-        input = i;
-        additional_records.push(record);
+    let mut header: Header = raw_header.into();
+    if let Some((_, extended_rcode)) = &edns {
+        header.rcode = RCode::from((*extended_rcode as u16) << 4 | header.rcode.as_u16());
     }
 
-    trace!("resolving name pointers");
+    let message = Message {
+        header,
+        questions,
+        answers,
+        name_servers,
+        additional_records,
+        edns: edns.map(|(edns, _)| edns),
+    };
+    Ok((message, checks.warnings))
+}
 
-    // Resolve the Name::Pointer records.
-    for q in questions.iter_mut() {
-        resolve_names(original_input, &mut q.qname, &mut HashSet::new())?;
-    }
-    for a in answers.iter_mut() {
-        resolve_names(original_input, &mut a.name, &mut HashSet::new())?;
-    }
-    for a in name_servers.iter_mut() {
-        resolve_names(original_input, &mut a.name, &mut HashSet::new())?;
+/// Reads the EDNS information from an OPT record, along with the upper bits of
+/// the extended RCODE.
+fn read_opt(record: &RawResourceRecord<'_>, seen_opt: bool) -> Result<(Edns, u8)> {
+    // RFC6891 - there must be at most one OPT record, owned by the root.
+    if seen_opt {
+        return Err(MessageError::InvalidOpt(
+            "More than one OPT record".to_string(),
+        ));
     }
-    for a in additional_records.iter_mut() {
-        resolve_names(original_input, &mut a.name, &mut HashSet::new())?;
+    if !record.name.is_empty() {
+        return Err(MessageError::InvalidOpt(
+            "OPT record not owned by the root".to_string(),
+        ));
     }
-
-    Ok(Message {
-        header: header.into(),
-        questions: questions.drain(..).map(Question::from).collect(),
-        answers: answers
-            .drain(..)
-            .map(|irr| from_irr(&original_input, irr))
-            .collect::<Result<Vec<ResourceRecord>>>()?,
-        name_servers: name_servers
-            .drain(..)
-            .map(|irr| from_irr(&original_input, irr))
-            .collect::<Result<Vec<ResourceRecord>>>()?,
-        additional_records: additional_records
-            .drain(..)
-            .map(|irr| from_irr(&original_input, irr))
-            .collect::<Result<Vec<ResourceRecord>>>()?,
-    })
+    Edns::from_opt(record.class.into(), record.ttl, record.rdata)
 }
 
 /// Resolves all Name::Pointer records to either Name::Name's or
 /// Name::ResolvedPtr's - should be given an empty HashSet as this is used to
 /// track seen pointers to avoid loops.
-#[instrument(skip(input))]
+///
+/// RFC1035 - pointers point to a prior occurrence of the name, so a pointer
+/// forward (or to itself) is only allowed in lenient mode.
+#[instrument(skip(input, checks))]
 fn resolve_names<'a>(
     input: &[u8],
     names: &mut Vec<Name>,
     seen_ptrs: &mut HashSet<u16>,
+    checks: &mut Checks,
 ) -> Result<()> {
     for n in names.iter_mut() {
-        match n {
-            Name::Pointer(ptr) => {
-                if seen_ptrs.contains(ptr) {
-                    return Err(MessageError::CircularReference(
-                        format!(
-                            "Circular reference - detected a pointer we have seen already: {}",
-                            *ptr
-                        )
-                        .to_string(),
-                    ));
-                }
-                seen_ptrs.insert(*ptr);
-                let (_, mut names) = read_names(&input[*ptr as usize..input.len()])?;
-                resolve_names(input, &mut names, seen_ptrs)?;
-
-                *n = Name::ResolvedPtr(names);
+        if let Name::Pointer(ptr, remaining) = n {
+            if *ptr as usize >= input.len() {
+                return Err(ParseErrorKind::PointerOutOfRange(*ptr).into());
+            }
+            if seen_ptrs.contains(ptr) {
+                return Err(ParseErrorKind::PointerLoop(*ptr).into());
+            }
+            seen_ptrs.insert(*ptr);
+            let position = input.len() - *remaining;
+            if *ptr as usize >= position {
+                let kind = ParseErrorKind::ForwardPointer(*ptr);
+                checks.tolerate(ParseError::new(kind).offset(position).into())?;
             }
-            _ => {}
+            let (_, mut names) = read_names(&input[*ptr as usize..input.len()])
+                .map_err(|e| wire_error(input, e, None, None))?;
+            resolve_names(input, &mut names, seen_ptrs, checks)?;
+
+            *n = Name::ResolvedPtr(names);
         }
     }
     Ok(())
 }
 
 #[instrument]
-fn flatten_to_string(names: &Vec<Name>) -> String {
-    let mut name = String::new();
+fn flatten_to_name(names: &[Name]) -> DomainName {
+    let mut labels = Vec::new();
+    collect_labels(names, &mut labels);
+    // Labels read from the wire are at most 63 octets, the total length is
+    // checked by read_domain_name.
+    DomainName::from_labels_unchecked(labels)
+}
+
+fn collect_labels(names: &[Name], labels: &mut Vec<Vec<u8>>) {
     for n in names.iter() {
         match n {
-            Name::Name(part) => {
-                name.push_str(part);
-                name.push('.');
-            }
-            Name::ResolvedPtr(names) => {
-                let s = flatten_to_string(names);
-                name.push_str(&s);
-            }
-            Name::Pointer(_i) => {
-                // TODO - Fix this so that we resolve all pointers.
-                // This however should not happen now that we recursively resolve the names.
+            Name::Label(label) => labels.push(label.clone()),
+            Name::ResolvedPtr(names) => collect_labels(names, labels),
+            Name::Pointer(..) => {
+                // This should not happen now that we recursively resolve the names.
                 error!("WARNING - FOUND UNRESOLVED POINTER....SKIPPING");
             }
         }
     }
-    // Remove the trailing '.'
-    if name.chars().last() == Some('.') {
-        name.pop();
-    }
-    name
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{test::setup, Class, OpCode, RCode, RData, Type};
+    use crate::{
+        test::setup, Class, Edns, EdnsOption, MessageBuilder, MessageRef, OpCode, QuestionBuilder,
+        RCode, RData, Type,
+    };
+    use std::error::Error;
     use std::net::Ipv4Addr;
 
     #[test]
@@ -495,15 +1077,39 @@ mod test {
         assert_eq!(message.questions.len(), 1);
         assert_eq!(message.answers.len(), 0);
         assert_eq!(message.name_servers.len(), 0);
-        assert_eq!(message.additional_records.len(), 1);
+        assert_eq!(message.additional_records.len(), 0);
 
         // Question
         assert_eq!(message.questions[0].q_name, "www.google.com");
         assert_eq!(message.questions[0].q_type, Type::A);
         assert_eq!(message.questions[0].q_class, Class::IN);
+
+        // EDNS
+        let edns = message.edns.as_ref().unwrap();
+        assert_eq!(edns.udp_payload_size, 4096);
+        assert_eq!(edns.version, 0);
+        assert!(!edns.dnssec_ok);
+        assert_eq!(
+            edns.options,
+            vec![EdnsOption::Cookie(
+                [107, 120, 163, 147, 238, 31, 231, 235],
+                None
+            )]
+        );
         println!("{}", message);
     }
 
+    #[test]
+    fn test_parse_multiple_opt() {
+        setup();
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, // header
+            0, 0, 41, 16, 0, 0, 0, 0, 0, 0, 0, // OPT
+            0, 0, 41, 16, 0, 0, 0, 0, 0, 0, 0, // OPT
+        ];
+        assert!(Message::from_bytes(input).is_err());
+    }
+
     #[test]
     fn test_parse_answer() {
         setup();
@@ -647,7 +1253,7 @@ mod test {
         assert_eq!(message.answers[0].ttl, 1504);
         assert_eq!(
             message.answers[0].data,
-            RData::CNAME(String::from("www.microsoft.com-c-3.edgekey.net"))
+            RData::CNAME(DomainName::from("www.microsoft.com-c-3.edgekey.net"))
         );
 
         // Answer 2
@@ -656,7 +1262,7 @@ mod test {
         assert_eq!(message.answers[1].ttl, 4526);
         assert_eq!(
             message.answers[1].data,
-            RData::CNAME(String::from(
+            RData::CNAME(DomainName::from(
                 "www.microsoft.com-c-3.edgekey.net.globalredir.akadns.net"
             ))
         );
@@ -670,7 +1276,7 @@ mod test {
         assert_eq!(message.answers[2].ttl, 870);
         assert_eq!(
             message.answers[2].data,
-            RData::CNAME(String::from("e13678.dspb.akamaiedge.net"))
+            RData::CNAME(DomainName::from("e13678.dspb.akamaiedge.net"))
         );
 
         // Answer 4
@@ -734,7 +1340,7 @@ mod test {
         assert_eq!(message.answers[0].ttl, 3012);
         assert_eq!(
             message.answers[0].data,
-            RData::CNAME(String::from("www.microsoft.com-c-3.edgekey.net"))
+            RData::CNAME(DomainName::from("www.microsoft.com-c-3.edgekey.net"))
         );
 
         // Answer 2
@@ -743,7 +1349,7 @@ mod test {
         assert_eq!(message.answers[1].ttl, 16153);
         assert_eq!(
             message.answers[1].data,
-            RData::CNAME(String::from(
+            RData::CNAME(DomainName::from(
                 "www.microsoft.com-c-3.edgekey.net.globalredir.akadns.net"
             ))
         );
@@ -757,7 +1363,7 @@ mod test {
         assert_eq!(message.answers[2].ttl, 858);
         assert_eq!(
             message.answers[2].data,
-            RData::CNAME(String::from("e13678.dscb.akamaiedge.net"))
+            RData::CNAME(DomainName::from("e13678.dscb.akamaiedge.net"))
         );
 
         // Answer 4
@@ -784,7 +1390,7 @@ mod test {
             0, 0, 0, 1, 0, 0, 84, 96, 0, 0, 14, 16, 0, 3, 244, 128, 0, 0, 1, 44,
         ];
 
-        let message = Message::from_bytes(&input).unwrap();
+        let message = Message::from_bytes(input).unwrap();
 
         let mut buf = Vec::with_capacity(1024);
         let len = message.to_bytes(&mut buf).unwrap();
@@ -793,4 +1399,485 @@ mod test {
 
         assert_eq!(message, message2);
     }
+
+    #[test]
+    fn test_parse_mx_ptr() {
+        setup();
+
+        let input: &[u8] = &[
+            0, 42, 129, 128, 0, 1, 0, 2, 0, 0, 0, 0, // header
+            7, 101, 120, 97, 109, 112, 108, 101, 3, 99, 111, 109, 0, // example.com
+            0, 15, 0, 1, // MX IN
+            192, 12, // Name - Pointer @ 12
+            0, 15, 0, 1, 0, 0, 14, 16, // MX IN 3600
+            0, 9, // rdlength - 9
+            0, 10, 4, 109, 97, 105, 108, 192, 12, // 10 mail + Pointer @ 12
+            192, 12, // Name - Pointer @ 12
+            0, 12, 0, 1, 0, 0, 14, 16, // PTR IN 3600
+            0, 6, // rdlength - 6
+            3, 119, 119, 119, 192, 12, // www + Pointer @ 12
+        ];
+
+        let message = Message::from_bytes(input).unwrap();
+        assert_eq!(message.answers.len(), 2);
+        assert_eq!(
+            message.answers[0].data,
+            RData::MX(10, DomainName::from("mail.example.com"))
+        );
+        assert_eq!(
+            message.answers[1].data,
+            RData::PTR(DomainName::from("www.example.com"))
+        );
+
+        // Both rdata names are compressed again when serialized.
+        let mut buf = Vec::new();
+        let len = message.to_bytes(&mut buf).unwrap();
+        assert_eq!(len, input.len());
+        assert_eq!(Message::from_bytes(&buf).unwrap(), message);
+    }
+
+    #[test]
+    fn test_parse_srv_dname_pointers() {
+        setup();
+
+        let input: &[u8] = &[
+            0, 43, 129, 128, 0, 0, 0, 2, 0, 0, 0, 0, // header
+            4, 95, 115, 105, 112, 4, 95, 116, 99, 112, 7, 101, 120, 97, 109, 112, 108, 101, 3, 99,
+            111, 109, 0, // _sip._tcp.example.com
+            0, 33, 0, 1, 0, 0, 0, 60, // SRV IN 60
+            0, 11, // rdlength - 11
+            0, 1, 0, 2, 19, 196, // 1 2 5060
+            2, 115, 49, 192, 22, // s1 + Pointer @ 22
+            192, 22, // Name - Pointer @ 22
+            0, 39, 0, 1, 0, 0, 0, 60, // DNAME IN 60
+            0, 2, // rdlength - 2
+            192, 12, // Pointer @ 12
+        ];
+
+        let message = Message::from_bytes(input).unwrap();
+        assert_eq!(
+            message.answers[0].data,
+            RData::SRV(1, 2, 5060, DomainName::from("s1.example.com"))
+        );
+        assert_eq!(message.answers[1].name, "example.com");
+        assert_eq!(
+            message.answers[1].data,
+            RData::DNAME(DomainName::from("_sip._tcp.example.com"))
+        );
+    }
+
+    fn parse_error(input: &[u8]) -> ParseError {
+        match Message::from_bytes_with(input, ParseOptions::strict()) {
+            Err(MessageError::Parse(e)) => e,
+            r => panic!("Expected a parse error, got {:?}", r),
+        }
+    }
+
+    fn assert_parse_error(
+        input: &[u8],
+        section: Section,
+        index: Option<usize>,
+        offset: usize,
+        kind: ParseErrorKind,
+    ) {
+        let e = parse_error(input);
+        assert_eq!(
+            (e.section, e.index, e.offset, e.kind),
+            (Some(section), index, Some(offset), kind)
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        setup();
+
+        assert_parse_error(
+            &[0, 1, 0],
+            Section::Header,
+            None,
+            0,
+            ParseErrorKind::Truncated,
+        );
+
+        // A question with a reserved label type.
+        let input: &[u8] = &[0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0x41, 0, 0, 1, 0, 1];
+        assert_parse_error(
+            input,
+            Section::Question,
+            Some(0),
+            12,
+            ParseErrorKind::BadLabelLength(0x41),
+        );
+
+        // A question that ends part way through the class.
+        let input: &[u8] = &[0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0];
+        assert_parse_error(
+            input,
+            Section::Question,
+            Some(0),
+            15,
+            ParseErrorKind::Truncated,
+        );
+
+        // A question whose name is a pointer to itself, which loops when
+        // forward pointers are allowed.
+        let input: &[u8] = &[0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 12, 0, 1, 0, 1];
+        assert_parse_error(
+            input,
+            Section::Question,
+            Some(0),
+            12,
+            ParseErrorKind::ForwardPointer(12),
+        );
+        let (message, warnings) = Message::from_bytes_with(input, ParseOptions::lenient()).unwrap();
+        assert!(message.questions.is_empty());
+        assert_eq!(
+            warnings.iter().map(|w| w.kind).collect::<Vec<_>>(),
+            vec![
+                ParseErrorKind::ForwardPointer(12),
+                ParseErrorKind::PointerLoop(12)
+            ]
+        );
+
+        // The RDLENGTH runs past the end of the message.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, // header
+            0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, // A
+        ];
+        assert_parse_error(
+            input,
+            Section::Answer,
+            Some(0),
+            23,
+            ParseErrorKind::TruncatedRData,
+        );
+
+        // An NSEC record whose type bitmap has an empty window.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, // header
+            0, 0, 47, 0, 1, 0, 0, 0, 60, 0, 3, 0, 0, 0, // NSEC
+        ];
+        assert_parse_error(
+            input,
+            Section::Answer,
+            Some(0),
+            24,
+            ParseErrorKind::BadTypeBitmap,
+        );
+
+        // The second answer's name points past the end of the message.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, // header
+            0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4, // A
+            0xc0, 99, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4, // A
+        ];
+        assert_parse_error(
+            input,
+            Section::Answer,
+            Some(1),
+            27,
+            ParseErrorKind::PointerOutOfRange(99),
+        );
+
+        // An MX record whose RDATA is too short for the preference.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, // header
+            0, 0, 15, 0, 1, 0, 0, 0, 60, 0, 1, 0, // MX
+        ];
+        assert_parse_error(
+            input,
+            Section::Authority,
+            Some(0),
+            23,
+            ParseErrorKind::TruncatedRData,
+        );
+
+        // An HTTPS record with a SvcParamKey but no length, the source says
+        // why the RDATA isn't valid.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, // header
+            0, 0, 65, 0, 1, 0, 0, 0, 60, 0, 5, 0, 1, 0, 0, 1, // HTTPS
+        ];
+        assert_parse_error(
+            input,
+            Section::Answer,
+            Some(0),
+            23,
+            ParseErrorKind::InvalidRData,
+        );
+        let e = parse_error(input);
+        assert_eq!(
+            e.to_string(),
+            "Invalid RDATA in the answer section at index 0 at offset 23"
+        );
+        assert!(matches!(
+            e.source().unwrap().downcast_ref::<MessageError>(),
+            Some(MessageError::InvalidSvcParams(_))
+        ));
+
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, // header
+            0, 0, 41, 16, 0, 0, 0, 0, 0, 0, 0, // OPT
+            0, 0, 41, 16, 0, 0, 0, 0, 0, 0, 0, // OPT
+        ];
+        assert_parse_error(
+            input,
+            Section::Additional,
+            Some(1),
+            23,
+            ParseErrorKind::InvalidOpt,
+        );
+    }
+
+    fn warning_kinds(input: &[u8]) -> Vec<ParseErrorKind> {
+        let (_, warnings) = Message::from_bytes_with(input, ParseOptions::lenient()).unwrap();
+        warnings.iter().map(|w| w.kind).collect()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        setup();
+
+        // An A record whose RDATA is too short, which used to panic.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, // header
+            0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 2, 1, 2, // A
+        ];
+        assert_parse_error(
+            input,
+            Section::Answer,
+            Some(0),
+            23,
+            ParseErrorKind::TruncatedRData,
+        );
+        let (message, warnings) = Message::from_bytes_with(input, ParseOptions::lenient()).unwrap();
+        assert_eq!(message.answers[0].data, RData::Raw(1, vec![1, 2]));
+        assert_eq!(warnings[0].kind, ParseErrorKind::TruncatedRData);
+        assert_eq!(warnings[0].section, Some(Section::Answer));
+        // Only lenient parsing salvages it.
+        assert!(Message::from_bytes(input).is_err());
+
+        // An A record with an octet left over is kept in lenient mode.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, // header
+            0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 5, 1, 2, 3, 4, 5, // A
+        ];
+        assert_parse_error(
+            input,
+            Section::Answer,
+            Some(0),
+            27,
+            ParseErrorKind::TrailingRData(1),
+        );
+        let (message, _) = Message::from_bytes_with(input, ParseOptions::lenient()).unwrap();
+        assert_eq!(message.answers[0].data, RData::A(Ipv4Addr::new(1, 2, 3, 4)));
+        assert_eq!(warning_kinds(input), vec![ParseErrorKind::TrailingRData(1)]);
+
+        // The AAAA address comes from the RDATA, not the start of the message.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, // header
+            0, 0, 28, 0, 1, 0, 0, 0, 60, 0, 16, // AAAA
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, // 2001:db8::1
+        ];
+        let message = Message::from_bytes(input).unwrap();
+        assert_eq!(
+            message.answers[0].data,
+            RData::AAAA("2001:db8::1".parse().unwrap())
+        );
+
+        // Octets after the last record.
+        let mut trailing = input.to_vec();
+        trailing.extend_from_slice(&[0, 0]);
+        let e = parse_error(&trailing);
+        assert_eq!(
+            (e.section, e.offset, e.kind),
+            (None, Some(39), ParseErrorKind::TrailingData(2))
+        );
+        assert_eq!(
+            warning_kinds(&trailing),
+            vec![ParseErrorKind::TrailingData(2)]
+        );
+        // By default what strict parsing rejects is allowed.
+        assert_eq!(Message::from_bytes(&trailing).unwrap(), message);
+
+        // The reserved Z flag.
+        let input: &[u8] = &[0, 1, 0, 0x40, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_parse_error(
+            input,
+            Section::Header,
+            None,
+            2,
+            ParseErrorKind::ReservedFlag,
+        );
+        assert_eq!(warning_kinds(input), vec![ParseErrorKind::ReservedFlag]);
+        assert!(Message::from_bytes(input).unwrap().header.z);
+
+        // A name of 4 labels of 63 octets is 257 octets long.
+        let mut input = vec![0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        for _ in 0..4 {
+            input.push(63);
+            input.extend_from_slice(&[b'a'; 63]);
+        }
+        input.extend_from_slice(&[0, 0, 1, 0, 1]);
+        assert_parse_error(
+            &input,
+            Section::Question,
+            Some(0),
+            12,
+            ParseErrorKind::NameTooLong(257),
+        );
+        assert_eq!(
+            warning_kinds(&input),
+            vec![ParseErrorKind::NameTooLong(257)]
+        );
+
+        // A name that points forwards to a later name.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, // header
+            0xc0, 18, 0, 1, 0, 1, // Pointer @ 18
+            1, b'a', 0, 0, 1, 0, 1, // a
+        ];
+        assert_parse_error(
+            input,
+            Section::Question,
+            Some(0),
+            12,
+            ParseErrorKind::ForwardPointer(18),
+        );
+        let (message, warnings) = Message::from_bytes_with(input, ParseOptions::lenient()).unwrap();
+        assert_eq!(message.questions.len(), 2);
+        assert_eq!(message.questions[0].q_name, "a");
+        assert_eq!(warnings[0].kind, ParseErrorKind::ForwardPointer(18));
+
+        // A message that ends part way through its second record keeps the
+        // first one.
+        let input: &[u8] = &[
+            0, 1, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, // header
+            0, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 1, 2, 3, 4, // A
+            0, 0, 1, 0, // A
+        ];
+        let (message, warnings) = Message::from_bytes_with(input, ParseOptions::lenient()).unwrap();
+        assert_eq!(message.answers.len(), 1);
+        assert_eq!(
+            (warnings[0].section, warnings[0].index, warnings[0].kind),
+            (Some(Section::Answer), Some(1), ParseErrorKind::Truncated)
+        );
+    }
+
+    /// A message with a record of each type, compressed names and an OPT
+    /// record, to mutate in the fuzz-style tests.
+    fn fuzz_seed() -> Vec<u8> {
+        let records = [
+            "www.example.com. 300 IN A 192.0.2.1",
+            "www.example.com. 300 IN AAAA 2001:db8::1",
+            "example.com. 3600 IN SOA ns1.example.com. hostmaster.example.com. 2024010101 3600 900 604800 300",
+            "example.com. 60 IN NS ns1.example.com.",
+            "example.com. 60 IN MX 10 mail.example.com.",
+            "x.example.com. 60 IN TXT \"hello\" \"world\"",
+            "_sip._udp.example.com. 60 IN SRV 10 20 5060 sip.example.com.",
+            "x.example.com. 60 IN NAPTR 100 10 \"U\" \"E2U+sip\" \"!^.*$!sip:info@example.com!\" .",
+            "x.example.com. 60 IN RRSIG A 5 3 86400 20030322173103 20030220173103 2642 example.com. AQID",
+            "x.example.com. 60 IN NSEC host.example.com. A MX RRSIG NSEC TYPE1234",
+            "x.example.com. 60 IN HTTPS 1 . alpn=\"h2,h3\" port=8443 ipv4hint=192.0.2.1",
+            "x.example.com. 60 IN LOC 52 22 23.000 N 4 53 32.000 E -2.00m 0m 10000m 10m",
+            "x.example.com. 60 IN CAA 0 issue \"ca.example.net\"",
+        ];
+        let mut builder = MessageBuilder::new()
+            .id(7)
+            .qr(true)
+            .question(
+                QuestionBuilder::new()
+                    .name("www.example.com")
+                    .q_type(Type::A)
+                    .build(),
+            )
+            .edns(Edns::default());
+        for s in records.iter() {
+            builder = builder.answer(s.parse().unwrap());
+        }
+        let mut buf = Vec::new();
+        builder.build().to_bytes(&mut buf).unwrap();
+        buf
+    }
+
+    /// Parses the input in every mode and the lazy view, which must never
+    /// panic. When strict parsing succeeds lenient parsing must give the
+    /// same message without warnings, and when the default parsing succeeds
+    /// lenient parsing must give the same message.
+    fn parse_all(input: &[u8]) {
+        let strict = Message::from_bytes_with(input, ParseOptions::strict());
+        let default = Message::from_bytes(input);
+        let lenient = Message::from_bytes_with(input, ParseOptions::lenient());
+        if let Ok((message, _)) = &strict {
+            let (salvaged, warnings) = lenient.as_ref().unwrap();
+            assert_eq!(salvaged, message);
+            assert!(warnings.is_empty(), "{:?}", warnings);
+        }
+        if let Ok(message) = &default {
+            assert_eq!(&lenient.as_ref().unwrap().0, message);
+        }
+        if let Ok((message, _)) = lenient {
+            let _ = message.to_string();
+            let _ = message.to_bytes(&mut Vec::new());
+        }
+        if let Ok(message) = MessageRef::from_bytes(input) {
+            let _ = message.to_message();
+            for record in message.answers() {
+                let _ = record.to_record();
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_truncated_fuzz() {
+        setup();
+
+        let seed = fuzz_seed();
+        let (message, warnings) = Message::from_bytes_with(&seed, ParseOptions::lenient()).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(message.answers.len(), 13);
+
+        // Every prefix fails in strict mode, and once the header is complete
+        // lenient mode salvages what it can.
+        for len in 0..seed.len() {
+            let input = &seed[..len];
+            parse_all(input);
+            assert!(Message::from_bytes(input).is_err(), "prefix of {}", len);
+            let lenient = Message::from_bytes_with(input, ParseOptions::lenient());
+            if len < 12 {
+                assert!(lenient.is_err());
+            } else {
+                let (_, warnings) = lenient.unwrap();
+                assert!(!warnings.is_empty(), "prefix of {}", len);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_mutated_fuzz() {
+        setup();
+
+        // A fixed xorshift sequence keeps the test deterministic.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let seed = fuzz_seed();
+        for _ in 0..5000 {
+            let mut input = seed.clone();
+            for _ in 0..=next() % 4 {
+                let i = next() as usize % input.len();
+                match next() % 3 {
+                    0 => input[i] = next() as u8,
+                    1 => input[i] ^= 1 << (next() % 8),
+                    _ => input.insert(i, next() as u8),
+                }
+            }
+            parse_all(&input);
+        }
+    }
 }
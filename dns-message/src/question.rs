@@ -1,9 +1,11 @@
-use crate::{encode_str, Result};
+use crate::{DomainName, MessageError, NameCompressor, Result, Writer};
 use std::default::Default;
 use std::fmt;
+use std::str::FromStr;
 
 use tracing::{instrument, trace};
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The question section is used to carry the "question" in most queries, i.e.,
 /// the parameters that define what is being asked.
 pub struct Question {
@@ -12,7 +14,7 @@ pub struct Question {
     /// domain name terminates with the zero length octet for the null label of
     /// the root.  Note that this field may be an odd number of octets; no
     /// padding is used.
-    pub q_name: String,
+    pub q_name: DomainName,
 
     /// RFC1035 - a two octet code which specifies the type of the query. The
     /// values for this field include all codes valid for a ['Type'] field,
@@ -25,11 +27,15 @@ pub struct Question {
 }
 
 impl Question {
-    #[instrument(skip(buf))]
-    pub(crate) fn to_bytes(&self, buf: &mut Vec<u8>) -> Result<usize> {
-        let mut byte_count = encode_str(&self.q_name, buf)?;
-        byte_count += self.q_type.to_bytes(buf);
-        byte_count += self.q_class.to_bytes(buf);
+    #[instrument(skip(buf, names))]
+    pub(crate) fn to_bytes(
+        &self,
+        buf: &mut dyn Writer,
+        names: &mut NameCompressor,
+    ) -> Result<usize> {
+        let mut byte_count = self.q_name.to_bytes(buf, names)?;
+        byte_count += self.q_type.to_bytes(buf)?;
+        byte_count += self.q_class.to_bytes(buf)?;
 
         trace!("Wrote {} bytes", byte_count);
 
@@ -37,10 +43,12 @@ impl Question {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Types used in [`Question`]s.
 pub enum Type {
     /// RFC1035 - (1) a host address.
+    #[default]
     A,
 
     /// RFC1035 - (2) an authoritative name server.
@@ -92,6 +100,60 @@ pub enum Type {
     /// Internet class that stores a single IPv6 address.
     AAAA,
 
+    /// RFC1876 - (29) location information.
+    LOC,
+
+    /// RFC2782 - (33) the location of services.
+    SRV,
+
+    /// RFC3403 - (35) naming authority pointer.
+    NAPTR,
+
+    /// RFC6672 - (39) redirection of a subtree of the domain name space.
+    DNAME,
+
+    /// RFC6891 - (41) the EDNS OPT pseudo-record, see [`crate::Edns`].
+    OPT,
+
+    /// RFC4034 - (43) delegation signer.
+    DS,
+
+    /// RFC4255 - (44) SSH key fingerprints.
+    SSHFP,
+
+    /// RFC4034 - (46) signature over an RRset.
+    RRSIG,
+
+    /// RFC4034 - (47) the next name in the zone and the types at the owner.
+    NSEC,
+
+    /// RFC4034 - (48) a public key used for DNSSEC.
+    DNSKEY,
+
+    /// RFC5155 - (50) hashed authenticated denial of existence.
+    NSEC3,
+
+    /// RFC5155 - (51) the NSEC3 parameters used by an authoritative server.
+    NSEC3PARAM,
+
+    /// RFC6698 - (52) TLS certificate associations (DANE).
+    TLSA,
+
+    /// RFC7344 - (59) child copy of the DS record.
+    CDS,
+
+    /// RFC7344 - (60) child copy of the DNSKEY record.
+    CDNSKEY,
+
+    /// RFC9460 - (64) general purpose service binding.
+    SVCB,
+
+    /// RFC9460 - (65) service binding for HTTP.
+    HTTPS,
+
+    /// RFC4408 - (99) Sender Policy Framework, in the same format as TXT.
+    SPF,
+
     /// RFC1035 - (252) A request for a transfer of an entire zone.
     AXFR,
 
@@ -104,43 +166,25 @@ pub enum Type {
     /// RFC1035 - (255) A request for all records.
     STAR,
 
+    /// RFC7553 - (256) a mapping from a hostname to a URI.
+    URI,
+
+    /// RFC8659 - (257) certification authority authorization.
+    CAA,
+
     /// An unknown [`Type`] - the value is contained within.
     Unknown(u16),
 }
 
 impl Type {
     #[instrument(skip(buf))]
-    fn to_bytes(&self, buf: &mut Vec<u8>) -> usize {
-        let val = match self {
-            Self::A => 1u16.to_be_bytes(),
-            Self::NS => 2u16.to_be_bytes(),
-            Self::MD => 3u16.to_be_bytes(),
-            Self::MF => 4u16.to_be_bytes(),
-            Self::CNAME => 5u16.to_be_bytes(),
-            Self::SOA => 6u16.to_be_bytes(),
-            Self::MB => 7u16.to_be_bytes(),
-            Self::MG => 8u16.to_be_bytes(),
-            Self::MR => 9u16.to_be_bytes(),
-            Self::NULL => 10u16.to_be_bytes(),
-            Self::WKS => 11u16.to_be_bytes(),
-            Self::PTR => 12u16.to_be_bytes(),
-            Self::HINFO => 13u16.to_be_bytes(),
-            Self::MINFO => 14u16.to_be_bytes(),
-            Self::MX => 15u16.to_be_bytes(),
-            Self::TXT => 16u16.to_be_bytes(),
-            Self::AAAA => 28u16.to_be_bytes(),
-            Self::AXFR => 252u16.to_be_bytes(),
-            Self::MAILB => 253u16.to_be_bytes(),
-            Self::MAILA => 254u16.to_be_bytes(),
-            Self::STAR => 255u16.to_be_bytes(),
-            Self::Unknown(i) => i.to_be_bytes(),
-        };
-        buf.push(val[0]);
-        buf.push(val[1]);
+    fn to_bytes(&self, buf: &mut dyn Writer) -> Result<usize> {
+        let val = u16::from(*self).to_be_bytes();
+        buf.write(&val)?;
 
         trace!("Wrote 2 bytes");
 
-        2
+        Ok(2)
     }
 }
 
@@ -164,12 +208,32 @@ impl fmt::Display for Type {
             Self::MX => "MX",
             Self::TXT => "TXT",
             Self::AAAA => "AAAA",
+            Self::LOC => "LOC",
+            Self::SRV => "SRV",
+            Self::NAPTR => "NAPTR",
+            Self::DNAME => "DNAME",
+            Self::OPT => "OPT",
+            Self::DS => "DS",
+            Self::SSHFP => "SSHFP",
+            Self::RRSIG => "RRSIG",
+            Self::NSEC => "NSEC",
+            Self::DNSKEY => "DNSKEY",
+            Self::NSEC3 => "NSEC3",
+            Self::NSEC3PARAM => "NSEC3PARAM",
+            Self::TLSA => "TLSA",
+            Self::CDS => "CDS",
+            Self::CDNSKEY => "CDNSKEY",
+            Self::SVCB => "SVCB",
+            Self::HTTPS => "HTTPS",
+            Self::SPF => "SPF",
             Self::AXFR => "AXFR",
             Self::MAILB => "MAILB",
             Self::MAILA => "MAILA",
             Self::STAR => "*",
+            Self::URI => "URI",
+            Self::CAA => "CAA",
             Self::Unknown(i) => {
-                write!(f, "Unknown({})", i)?;
+                write!(f, "TYPE{}", i)?;
                 return Ok(());
             }
         };
@@ -177,9 +241,60 @@ impl fmt::Display for Type {
     }
 }
 
-impl Default for Type {
-    fn default() -> Self {
-        Self::A
+impl FromStr for Type {
+    type Err = MessageError;
+
+    /// Parses a type mnemonic in either case, or the RFC3597 `TYPEnnn` form.
+    fn from_str(s: &str) -> Result<Self> {
+        let t = match s.to_ascii_uppercase().as_str() {
+            "A" => Type::A,
+            "NS" => Type::NS,
+            "MD" => Type::MD,
+            "MF" => Type::MF,
+            "CNAME" => Type::CNAME,
+            "SOA" => Type::SOA,
+            "MB" => Type::MB,
+            "MG" => Type::MG,
+            "MR" => Type::MR,
+            "NULL" => Type::NULL,
+            "WKS" => Type::WKS,
+            "PTR" => Type::PTR,
+            "HINFO" => Type::HINFO,
+            "MINFO" => Type::MINFO,
+            "MX" => Type::MX,
+            "TXT" => Type::TXT,
+            "AAAA" => Type::AAAA,
+            "LOC" => Type::LOC,
+            "SRV" => Type::SRV,
+            "NAPTR" => Type::NAPTR,
+            "DNAME" => Type::DNAME,
+            "OPT" => Type::OPT,
+            "DS" => Type::DS,
+            "SSHFP" => Type::SSHFP,
+            "RRSIG" => Type::RRSIG,
+            "NSEC" => Type::NSEC,
+            "DNSKEY" => Type::DNSKEY,
+            "NSEC3" => Type::NSEC3,
+            "NSEC3PARAM" => Type::NSEC3PARAM,
+            "TLSA" => Type::TLSA,
+            "CDS" => Type::CDS,
+            "CDNSKEY" => Type::CDNSKEY,
+            "SVCB" => Type::SVCB,
+            "HTTPS" => Type::HTTPS,
+            "SPF" => Type::SPF,
+            "AXFR" => Type::AXFR,
+            "MAILB" => Type::MAILB,
+            "MAILA" => Type::MAILA,
+            "*" | "ANY" => Type::STAR,
+            "URI" => Type::URI,
+            "CAA" => Type::CAA,
+            t => t
+                .strip_prefix("TYPE")
+                .and_then(|n| n.parse::<u16>().ok())
+                .map(Type::from)
+                .ok_or_else(|| MessageError::ParsingError(format!("Unknown type {}", s)))?,
+        };
+        Ok(t)
     }
 }
 
@@ -203,10 +318,30 @@ impl From<Type> for u16 {
             Type::MX => 15,
             Type::TXT => 16,
             Type::AAAA => 28,
+            Type::LOC => 29,
+            Type::SRV => 33,
+            Type::NAPTR => 35,
+            Type::DNAME => 39,
+            Type::OPT => 41,
+            Type::DS => 43,
+            Type::SSHFP => 44,
+            Type::RRSIG => 46,
+            Type::NSEC => 47,
+            Type::DNSKEY => 48,
+            Type::NSEC3 => 50,
+            Type::NSEC3PARAM => 51,
+            Type::TLSA => 52,
+            Type::CDS => 59,
+            Type::CDNSKEY => 60,
+            Type::SVCB => 64,
+            Type::HTTPS => 65,
+            Type::SPF => 99,
             Type::AXFR => 252,
             Type::MAILB => 253,
             Type::MAILA => 254,
             Type::STAR => 255,
+            Type::URI => 256,
+            Type::CAA => 257,
             Type::Unknown(i) => i,
         }
     }
@@ -233,20 +368,42 @@ impl From<u16> for Type {
             15 => Type::MX,
             16 => Type::TXT,
             28 => Type::AAAA,
+            29 => Type::LOC,
+            33 => Type::SRV,
+            35 => Type::NAPTR,
+            39 => Type::DNAME,
+            41 => Type::OPT,
+            43 => Type::DS,
+            44 => Type::SSHFP,
+            46 => Type::RRSIG,
+            47 => Type::NSEC,
+            48 => Type::DNSKEY,
+            50 => Type::NSEC3,
+            51 => Type::NSEC3PARAM,
+            52 => Type::TLSA,
+            59 => Type::CDS,
+            60 => Type::CDNSKEY,
+            64 => Type::SVCB,
+            65 => Type::HTTPS,
+            99 => Type::SPF,
             252 => Type::AXFR,
             253 => Type::MAILB,
             254 => Type::MAILA,
             255 => Type::STAR,
+            256 => Type::URI,
+            257 => Type::CAA,
             _ => Type::Unknown(val),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The class of the query - you will want [`Class::IN`] (the default) 99.99% of
 /// the time.
 pub enum Class {
     /// RFC1035 - 1 the Internet.
+    #[default]
     IN,
 
     /// RFC1035 - 2 the CSNET class (Obsolete - used only for examples in some
@@ -259,6 +416,10 @@ pub enum Class {
     /// RFC1035 - 4 Hesiod [Dyer 87].
     HS,
 
+    /// RFC2136 - 254 none, used in the prerequisites and updates of a dynamic
+    /// update.
+    NONE,
+
     /// RFC1035 - 255 any class.
     STAR,
 
@@ -268,27 +429,64 @@ pub enum Class {
 
 impl Class {
     #[instrument(skip(buf))]
-    pub(crate) fn to_bytes(&self, buf: &mut Vec<u8>) -> usize {
-        let val = match self {
-            Class::IN => 1u16.to_be_bytes(),
-            Class::CS => 2u16.to_be_bytes(),
-            Class::CH => 3u16.to_be_bytes(),
-            Class::HS => 4u16.to_be_bytes(),
-            Class::STAR => 255u16.to_be_bytes(),
-            Class::Unknown(i) => i.to_be_bytes(),
-        };
-        buf.push(val[0]);
-        buf.push(val[1]);
+    pub(crate) fn to_bytes(&self, buf: &mut dyn Writer) -> Result<usize> {
+        let val = u16::from(*self).to_be_bytes();
+        buf.write(&val)?;
 
         trace!("Wrote 2 bytes");
 
-        2
+        Ok(2)
     }
 }
 
-impl Default for Class {
-    fn default() -> Self {
-        Class::IN
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            Self::IN => write!(f, "IN"),
+            Self::CS => write!(f, "CS"),
+            Self::CH => write!(f, "CH"),
+            Self::HS => write!(f, "HS"),
+            Self::NONE => write!(f, "NONE"),
+            Self::STAR => write!(f, "ANY"),
+            Self::Unknown(i) => write!(f, "CLASS{}", i),
+        }
+    }
+}
+
+impl FromStr for Class {
+    type Err = MessageError;
+
+    /// Parses a class mnemonic in either case, or the RFC3597 `CLASSnnn`
+    /// form.
+    fn from_str(s: &str) -> Result<Self> {
+        let c = match s.to_ascii_uppercase().as_str() {
+            "IN" => Class::IN,
+            "CS" => Class::CS,
+            "CH" => Class::CH,
+            "HS" => Class::HS,
+            "NONE" => Class::NONE,
+            "*" | "ANY" => Class::STAR,
+            c => c
+                .strip_prefix("CLASS")
+                .and_then(|n| n.parse::<u16>().ok())
+                .map(Class::from)
+                .ok_or_else(|| MessageError::ParsingError(format!("Unknown class {}", s)))?,
+        };
+        Ok(c)
+    }
+}
+
+impl From<Class> for u16 {
+    fn from(c: Class) -> u16 {
+        match c {
+            Class::IN => 1,
+            Class::CS => 2,
+            Class::CH => 3,
+            Class::HS => 4,
+            Class::NONE => 254,
+            Class::STAR => 255,
+            Class::Unknown(i) => i,
+        }
     }
 }
 
@@ -300,6 +498,7 @@ impl From<u16> for Class {
             2 => Class::CS,
             3 => Class::CH,
             4 => Class::HS,
+            254 => Class::NONE,
             255 => Class::STAR,
             _ => Class::Unknown(val),
         }
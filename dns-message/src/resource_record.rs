@@ -1,16 +1,22 @@
-use crate::{encode_str, Class, Result};
+use crate::encoding::{base32hex_encode, base64_encode};
+use crate::{
+    Class, DomainName, MessageError, NameCompressor, Result, SvcParams, Type, Writer, ZoneParser,
+};
+use std::cmp::Ordering;
 use std::fmt;
 use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
 use tracing::{instrument, trace};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The answer, authority and additional sections all share the same format,
 /// that is a variable number of [`ResourceRecord`]s.
 ///
 /// These can be constructed with a [`crate::ResourceRecordBuilder`].
 pub struct ResourceRecord {
     /// A domain name to which this resource record pertains.
-    pub name: String,
+    pub name: DomainName,
 
     /// The type and data of the resource record.
     pub data: RData,
@@ -26,63 +32,109 @@ pub struct ResourceRecord {
 }
 
 impl ResourceRecord {
-    #[instrument(skip(buf))]
-    pub(crate) fn to_bytes(&self, buf: &mut Vec<u8>) -> Result<usize> {
-        // TODO here is where we would implement the Message Compression -
-        // though we will need to wire through a map of the strings and
-        // locations. It is perfectly fine with the spec to not implement this.
-        let mut byte_count = encode_str(&self.name, buf)?;
+    #[instrument(skip(buf, names))]
+    pub(crate) fn to_bytes(
+        &self,
+        buf: &mut dyn Writer,
+        names: &mut NameCompressor,
+    ) -> Result<usize> {
+        let mut byte_count = self.name.to_bytes(buf, names)?;
 
         let r_type = self.data.as_u16().to_be_bytes();
-        buf.push(r_type[0]);
-        buf.push(r_type[1]);
+        buf.write(&r_type)?;
         byte_count += 2;
 
-        byte_count += self.class.to_bytes(buf);
+        byte_count += self.class.to_bytes(buf)?;
 
         let ttl = self.ttl.to_be_bytes();
-        buf.push(ttl[0]);
-        buf.push(ttl[1]);
-        buf.push(ttl[2]);
-        buf.push(ttl[3]);
+        buf.write(&ttl)?;
         byte_count += 4;
 
-        // We need this temp (rdata) buffer here as we don't know how long the
-        // rdata will be until we convert it to bytes. We need this to get the
-        // length of it, before we write the length.
-        let mut rdata: Vec<u8> = Vec::with_capacity(255);
-        let rdlength = self.data.to_bytes(&mut rdata)?;
+        // We don't know how long the rdata will be until we write it, so
+        // reserve the rdlength and fill it in afterwards. The rdata has to be
+        // written in place so that any compression pointers it records point
+        // at the correct offsets.
+        let rdlength_pos = buf.len();
+        buf.write(&[0, 0])?;
+        byte_count += 2;
+
+        let rdlength = self.data.to_bytes(buf, names)?;
         byte_count += rdlength;
 
-        let rdlength = (rdlength as u16).to_be_bytes();
-        buf.push(rdlength[0]);
-        buf.push(rdlength[1]);
-        byte_count += 2;
-        buf.extend(rdata);
+        if rdlength > u16::MAX as usize {
+            return Err(MessageError::EncodingError(
+                format!("RDATA of {} bytes is too long", rdlength).into(),
+            ));
+        }
+        buf.write_at(rdlength_pos, &(rdlength as u16).to_be_bytes());
 
         trace!("Wrote {} bytes", byte_count);
 
         Ok(byte_count)
     }
+
+    /// RFC4034 - The canonical form of the record, as used for DNSSEC
+    /// signatures and to compare records.
+    ///
+    /// The owner name is lowercased and no names are compressed, and the
+    /// RDATA is in its canonical form, see [`RData::to_canonical_bytes`]. The
+    /// TTL is written as it is, when signing it must be the original TTL of
+    /// the RRSIG.
+    pub fn to_canonical_bytes(&self) -> Result<Vec<u8>> {
+        let record = ResourceRecord {
+            name: self.name.to_lowercase(),
+            data: self.data.to_canonical(),
+            class: self.class,
+            ttl: self.ttl,
+        };
+        let mut buf = Vec::new();
+        record.to_bytes(&mut buf, &mut NameCompressor::disabled())?;
+        Ok(buf)
+    }
+
+    /// RFC4034 - Compares records in the canonical order, by the owner name
+    /// (see [`DomainName::canonical_cmp`]), then the class, type and the
+    /// canonical form of the RDATA. The TTL isn't compared.
+    ///
+    /// Fails if the RDATA has to be compared but can't be written, such as a
+    /// name that is too long.
+    pub fn canonical_cmp(&self, other: &ResourceRecord) -> Result<Ordering> {
+        let ordering = self
+            .name
+            .canonical_cmp(&other.name)
+            .then_with(|| u16::from(self.class).cmp(&u16::from(other.class)))
+            .then_with(|| self.data.as_u16().cmp(&other.data.as_u16()));
+        if ordering != Ordering::Equal {
+            return Ok(ordering);
+        }
+        self.data.canonical_cmp(&other.data)
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The [`ResourceRecord`] data.
 pub enum RData {
     /// RFC1035 - (1) a host address.
     A(Ipv4Addr),
 
     /// RFC1035 - (2) an authoritative name server.
-    NS(String),
+    NS(DomainName),
 
     /// RFC1035 - (3) a mail destination (Obsolete - use MX).
-    MD,
+    ///
+    /// A <domain-name> which specifies a host which has a mail agent for the
+    /// domain which should be able to deliver mail for the domain.
+    MD(DomainName),
 
     /// RFC1035 - (4) a mail forwarder (Obsolete - use MX).
-    MF,
+    ///
+    /// A <domain-name> which specifies a host which has a mail agent for the
+    /// domain which will accept mail for forwarding to the domain.
+    MF(DomainName),
 
     /// RFC1035 - (5) the canonical name for an alias.
-    CNAME(String),
+    CNAME(DomainName),
 
     /// RFC1035 - (6) marks the start of a zone of authority.
     ///
@@ -102,109 +154,590 @@ pub enum RData {
     ///   authoritative.
     /// - MINIMUM - The unsigned 32 bit minimum TTL field that should be
     ///   exported with any RR from this zone.
-    SOA(String, String, u32, u32, u32, u32, u32),
+    SOA(DomainName, DomainName, u32, u32, u32, u32, u32),
 
     /// RFC1035 - (7) a mailbox domain name (EXPERIMENTAL).
-    MB,
+    ///
+    /// A <domain-name> which specifies a host which has the specified mailbox.
+    MB(DomainName),
 
     /// RFC1035 - (8) a mail group member (EXPERIMENTAL).
-    MG,
+    ///
+    /// A <domain-name> which specifies a mailbox which is a member of the mail
+    /// group specified by the domain name.
+    MG(DomainName),
 
     /// RFC1035 - (9) a mail rename domain name (EXPERIMENTAL).
-    MR,
+    ///
+    /// A <domain-name> which specifies a mailbox which is the proper rename of
+    /// the specified mailbox.
+    MR(DomainName),
 
     /// RFC1035 - (10) a null RR (EXPERIMENTAL).
-    NULL,
+    ///
+    /// Anything at all may be in the RDATA field so long as it is 65535 octets
+    /// or less.
+    NULL(Vec<u8>),
 
     /// RFC1035 - (11) a well known service description.
-    WKS,
+    ///
+    /// The components consist of:
+    /// - ADDRESS - An 32 bit Internet address.
+    /// - PROTOCOL - An 8 bit IP protocol number.
+    /// - BIT MAP - A variable length bit map, where bit N (counting from the
+    ///   most significant bit of the first octet) is set if port N is served.
+    WKS(Ipv4Addr, u8, Vec<u8>),
 
     /// RFC1035 - (12) a domain name pointer.
-    PTR,
+    ///
+    /// A <domain-name> which points to some location in the domain name space.
+    PTR(DomainName),
 
     /// RFC1035 - (13) host information.
-    HINFO,
+    ///
+    /// The components consist of:
+    /// - CPU - A <character-string> which specifies the CPU type.
+    /// - OS - A <character-string> which specifies the operating system type.
+    HINFO(Vec<u8>, Vec<u8>),
 
     /// RFC1035 - (14) mailbox or mail list information.
-    MINFO,
+    ///
+    /// The components consist of:
+    /// - RMAILBX - A <domain-name> which specifies a mailbox which is
+    ///   responsible for the mailing list or mailbox.
+    /// - EMAILBX - A <domain-name> which specifies a mailbox which is to
+    ///   receive error messages related to the mailing list or mailbox.
+    MINFO(DomainName, DomainName),
 
     /// RFC1035 - (15) mail exchange.
-    MX,
+    ///
+    /// The components consist of:
+    /// - PREFERENCE - A 16 bit integer which specifies the preference given to
+    ///   this RR among others at the same owner. Lower values are preferred.
+    /// - EXCHANGE - A <domain-name> which specifies a host willing to act as a
+    ///   mail exchange for the owner name.
+    MX(u16, DomainName),
 
     /// RFC1035 - (16) text strings.
-    TXT(String),
+    ///
+    /// One or more <character-string>s. Strings longer than 255 octets are
+    /// split into multiple <character-string>s when serialized, see
+    /// [`RData::txt`] and [`RData::text`].
+    TXT(Vec<Vec<u8>>),
 
     /// RFC3596 - The AAAA resource record type is a record specific to the
     /// Internet class that stores a single IPv6 address.
     AAAA(Ipv6Addr),
 
+    /// RFC1876 - (29) location information.
+    ///
+    /// The components consist of:
+    /// - VERSION - The version number of the representation, must be zero.
+    /// - SIZE - The diameter of a sphere enclosing the described entity, in
+    ///   centimeters, as a pair of four bit values - the base and power of
+    ///   ten.
+    /// - HORIZ PRE - The horizontal precision of the data, in centimeters,
+    ///   encoded the same as SIZE.
+    /// - VERT PRE - The vertical precision of the data, in centimeters,
+    ///   encoded the same as SIZE.
+    /// - LATITUDE - The latitude in thousandths of a second of arc, with 2^31
+    ///   being the equator.
+    /// - LONGITUDE - The longitude in thousandths of a second of arc, with
+    ///   2^31 being the prime meridian.
+    /// - ALTITUDE - The altitude in centimeters, from a base of 100,000m below
+    ///   the WGS 84 reference spheroid.
+    LOC(u8, u8, u8, u8, u32, u32, u32),
+
+    /// RFC2782 - (33) the location of services.
+    ///
+    /// The components consist of:
+    /// - PRIORITY - The priority of this target host, lower values are
+    ///   preferred.
+    /// - WEIGHT - A relative weight for entries with the same priority.
+    /// - PORT - The port on this target host of this service.
+    /// - TARGET - The <domain-name> of the target host.
+    SRV(u16, u16, u16, DomainName),
+
+    /// RFC3403 - (35) naming authority pointer.
+    ///
+    /// The components consist of:
+    /// - ORDER - The order in which the records must be processed.
+    /// - PREFERENCE - The order in which records with equal ORDER should be
+    ///   processed.
+    /// - FLAGS - A <character-string> containing flags to control the
+    ///   rewriting and interpretation of the fields.
+    /// - SERVICES - A <character-string> that specifies the services
+    ///   available down this rewrite path.
+    /// - REGEXP - A <character-string> containing a substitution expression.
+    /// - REPLACEMENT - The next <domain-name> to query for.
+    NAPTR(u16, u16, Vec<u8>, Vec<u8>, Vec<u8>, DomainName),
+
+    /// RFC6672 - (39) redirection of a subtree of the domain name space.
+    ///
+    /// A <domain-name> which is the target of the redirection.
+    DNAME(DomainName),
+
+    /// RFC4034 - (43) delegation signer, which refers to a DNSKEY in the
+    /// child zone.
+    ///
+    /// The components consist of:
+    /// - KEY TAG - The key tag of the referenced DNSKEY.
+    /// - ALGORITHM - The algorithm of the referenced DNSKEY.
+    /// - DIGEST TYPE - The algorithm used to construct the digest.
+    /// - DIGEST - The digest of the owner name and RDATA of the DNSKEY.
+    DS(u16, u8, u8, Vec<u8>),
+
+    /// RFC4255 - (44) SSH key fingerprints.
+    ///
+    /// The components consist of:
+    /// - ALGORITHM - The algorithm of the public key.
+    /// - FP TYPE - The message-digest algorithm used for the fingerprint.
+    /// - FINGERPRINT - The fingerprint of the public key.
+    SSHFP(u8, u8, Vec<u8>),
+
+    /// RFC4034 - (46) a signature over an RRset.
+    ///
+    /// The components consist of:
+    /// - TYPE COVERED - The type of the RRset that is covered.
+    /// - ALGORITHM - The algorithm used to create the signature.
+    /// - LABELS - The number of labels in the owner name of the RRset, not
+    ///   counting the root or a leading wildcard.
+    /// - ORIGINAL TTL - The TTL of the RRset in the authoritative zone.
+    /// - SIGNATURE EXPIRATION - The end of the validity period, in seconds
+    ///   since the epoch using serial number arithmetic.
+    /// - SIGNATURE INCEPTION - The start of the validity period, in seconds
+    ///   since the epoch using serial number arithmetic.
+    /// - KEY TAG - The key tag of the DNSKEY that validates the signature.
+    /// - SIGNER'S NAME - The <domain-name> of the zone of the DNSKEY, which is
+    ///   never compressed.
+    /// - SIGNATURE - The signature.
+    RRSIG(Type, u8, u8, u32, u32, u32, u16, DomainName, Vec<u8>),
+
+    /// RFC4034 - (47) proof of the non-existence of names and types.
+    ///
+    /// The components consist of:
+    /// - NEXT DOMAIN NAME - The next owner name in the canonical ordering of
+    ///   the zone, which is never compressed.
+    /// - TYPE BIT MAPS - The types present at the owner name.
+    NSEC(DomainName, Vec<Type>),
+
+    /// RFC4034 - (48) a public key used for DNSSEC.
+    ///
+    /// The components consist of:
+    /// - FLAGS - Bit 7 is the Zone Key flag, bit 15 is the Secure Entry
+    ///   Point flag.
+    /// - PROTOCOL - Must be 3.
+    /// - ALGORITHM - The algorithm of the public key.
+    /// - PUBLIC KEY - The public key material.
+    DNSKEY(u16, u8, u8, Vec<u8>),
+
+    /// RFC5155 - (50) hashed authenticated denial of existence.
+    ///
+    /// The components consist of:
+    /// - HASH ALGORITHM - The hash used to construct the hashed owner names.
+    /// - FLAGS - Bit 7 is the Opt-Out flag.
+    /// - ITERATIONS - The number of additional times the hash is applied.
+    /// - SALT - The salt appended to the name before hashing.
+    /// - NEXT HASHED OWNER NAME - The next hashed owner name in hash order.
+    /// - TYPE BIT MAPS - The types present at the original owner name.
+    NSEC3(u8, u8, u16, Vec<u8>, Vec<u8>, Vec<Type>),
+
+    /// RFC5155 - (51) the parameters used to calculate hashed owner names.
+    ///
+    /// The components consist of:
+    /// - HASH ALGORITHM - The hash used to construct the hashed owner names.
+    /// - FLAGS - Must be zero.
+    /// - ITERATIONS - The number of additional times the hash is applied.
+    /// - SALT - The salt appended to the name before hashing.
+    NSEC3PARAM(u8, u8, u16, Vec<u8>),
+
+    /// RFC6698 - (52) TLS certificate associations (DANE).
+    ///
+    /// The components consist of:
+    /// - CERTIFICATE USAGE - How the certificate association is used.
+    /// - SELECTOR - Which part of the certificate is matched.
+    /// - MATCHING TYPE - How the certificate association is presented.
+    /// - CERTIFICATE ASSOCIATION DATA - The data to be matched.
+    TLSA(u8, u8, u8, Vec<u8>),
+
+    /// RFC7344 - (59) the child's copy of a DS record, this has the same
+    /// format as the [`RData::DS`] record.
+    CDS(u16, u8, u8, Vec<u8>),
+
+    /// RFC7344 - (60) the child's copy of a DNSKEY record, this has the same
+    /// format as the [`RData::DNSKEY`] record.
+    CDNSKEY(u16, u8, u8, Vec<u8>),
+
+    /// RFC9460 - (64) general purpose service binding.
+    ///
+    /// The components consist of:
+    /// - SVCPRIORITY - The priority of this record, zero is AliasMode and
+    ///   anything else is ServiceMode.
+    /// - TARGETNAME - The <domain-name> of the alias target (AliasMode) or
+    ///   the alternative endpoint (ServiceMode).
+    /// - SVCPARAMS - The [`SvcParams`] for the endpoint.
+    SVCB(u16, DomainName, SvcParams),
+
+    /// RFC9460 - (65) service binding for HTTP, this has the same format as
+    /// the [`RData::SVCB`] record.
+    HTTPS(u16, DomainName, SvcParams),
+
+    /// RFC4408 - (99) Sender Policy Framework, this has the same format as the
+    /// [`RData::TXT`] record.
+    SPF(Vec<Vec<u8>>),
+
+    /// RFC7553 - (256) a mapping from a hostname to a URI.
+    ///
+    /// The components consist of:
+    /// - PRIORITY - The priority of the target URI, lower values are
+    ///   preferred.
+    /// - WEIGHT - A relative weight for entries with the same priority.
+    /// - TARGET - The URI of the target, this is not length prefixed.
+    URI(u16, u16, Vec<u8>),
+
+    /// RFC8659 - (257) certification authority authorization.
+    ///
+    /// The components consist of:
+    /// - FLAGS - The flags, bit 0 (128) is the issuer critical flag.
+    /// - TAG - The property identifier, e.g. `issue`.
+    /// - VALUE - The value of the property, this is not length prefixed.
+    CAA(u8, Vec<u8>, Vec<u8>),
+
     /// Raw rdata - when an unknown type is encountered, they type and bytes will be in a Raw.
     /// The u16 is the rfc1035 type and the Vec<u8> is the bytes.
-    /// TODO: This will only be needed for fallback once all of the types above are implemented.
     Raw(u16, Vec<u8>),
 }
 
 impl RData {
-    fn as_u16(&self) -> u16 {
+    /// Creates a [`RData::TXT`] from a single value, splitting it into as many
+    /// 255 octet <character-string>s as needed.
+    pub fn txt<S: AsRef<[u8]>>(value: S) -> RData {
+        RData::TXT(split_character_strings(value.as_ref()))
+    }
+
+    /// The <character-string>s of a [`RData::TXT`] or [`RData::SPF`] joined
+    /// together as text, any invalid UTF-8 is replaced. Returns [`None`] for
+    /// any other type.
+    ///
+    /// Long values are split across multiple strings, so they are joined
+    /// without a separator as per RFC7208 and RFC6376.
+    pub fn text(&self) -> Option<String> {
+        match self {
+            RData::TXT(strings) | RData::SPF(strings) => {
+                Some(String::from_utf8_lossy(&strings.concat()).to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// RFC4034 - The canonical form of the RDATA, with no names compressed.
+    ///
+    /// The names in the RDATA of the types listed by RFC4034 and still present
+    /// after RFC6840 are lowercased, that is NS, MD, MF, CNAME, SOA, MB, MG,
+    /// MR, PTR, MINFO, MX, SRV, NAPTR, DNAME and RRSIG. Other types are
+    /// written as they are.
+    pub fn to_canonical_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.to_canonical()
+            .to_bytes(&mut buf, &mut NameCompressor::disabled())?;
+        Ok(buf)
+    }
+
+    /// Compares the canonical forms of the RDATA as left justified octet
+    /// strings, failing if either can't be written.
+    pub(crate) fn canonical_cmp(&self, other: &RData) -> Result<Ordering> {
+        Ok(self.to_canonical_bytes()?.cmp(&other.to_canonical_bytes()?))
+    }
+
+    /// A copy with the names lowercased as per [`RData::to_canonical_bytes`].
+    fn to_canonical(&self) -> RData {
+        let lower = DomainName::to_lowercase;
+        match self {
+            RData::NS(name) => RData::NS(lower(name)),
+            RData::MD(name) => RData::MD(lower(name)),
+            RData::MF(name) => RData::MF(lower(name)),
+            RData::CNAME(name) => RData::CNAME(lower(name)),
+            RData::SOA(mname, rname, serial, refresh, retry, expire, minimum) => RData::SOA(
+                lower(mname),
+                lower(rname),
+                *serial,
+                *refresh,
+                *retry,
+                *expire,
+                *minimum,
+            ),
+            RData::MB(name) => RData::MB(lower(name)),
+            RData::MG(name) => RData::MG(lower(name)),
+            RData::MR(name) => RData::MR(lower(name)),
+            RData::PTR(name) => RData::PTR(lower(name)),
+            RData::MINFO(rmailbx, emailbx) => RData::MINFO(lower(rmailbx), lower(emailbx)),
+            RData::MX(preference, exchange) => RData::MX(*preference, lower(exchange)),
+            RData::SRV(priority, weight, port, target) => {
+                RData::SRV(*priority, *weight, *port, lower(target))
+            }
+            RData::NAPTR(order, preference, flags, services, regexp, replacement) => RData::NAPTR(
+                *order,
+                *preference,
+                flags.clone(),
+                services.clone(),
+                regexp.clone(),
+                lower(replacement),
+            ),
+            RData::DNAME(target) => RData::DNAME(lower(target)),
+            RData::RRSIG(
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                signer,
+                signature,
+            ) => RData::RRSIG(
+                *type_covered,
+                *algorithm,
+                *labels,
+                *original_ttl,
+                *expiration,
+                *inception,
+                *key_tag,
+                lower(signer),
+                signature.clone(),
+            ),
+            data => data.clone(),
+        }
+    }
+
+    pub(crate) fn as_u16(&self) -> u16 {
         match self {
             RData::A(_) => 1,
             RData::NS(_) => 2,
-            RData::MD => 3,
-            RData::MF => 4,
+            RData::MD(_) => 3,
+            RData::MF(_) => 4,
             RData::CNAME(_) => 5,
             RData::SOA(_, _, _, _, _, _, _) => 6,
-            RData::MB => 7,
-            RData::MG => 8,
-            RData::MR => 9,
-            RData::NULL => 10,
-            RData::WKS => 11,
-            RData::PTR => 12,
-            RData::HINFO => 13,
-            RData::MINFO => 14,
-            RData::MX => 15,
+            RData::MB(_) => 7,
+            RData::MG(_) => 8,
+            RData::MR(_) => 9,
+            RData::NULL(_) => 10,
+            RData::WKS(_, _, _) => 11,
+            RData::PTR(_) => 12,
+            RData::HINFO(_, _) => 13,
+            RData::MINFO(_, _) => 14,
+            RData::MX(_, _) => 15,
             RData::TXT(_) => 16,
             RData::AAAA(_) => 28,
+            RData::LOC(_, _, _, _, _, _, _) => 29,
+            RData::SRV(_, _, _, _) => 33,
+            RData::NAPTR(_, _, _, _, _, _) => 35,
+            RData::DNAME(_) => 39,
+            RData::DS(_, _, _, _) => 43,
+            RData::SSHFP(_, _, _) => 44,
+            RData::RRSIG(_, _, _, _, _, _, _, _, _) => 46,
+            RData::NSEC(_, _) => 47,
+            RData::DNSKEY(_, _, _, _) => 48,
+            RData::NSEC3(_, _, _, _, _, _) => 50,
+            RData::NSEC3PARAM(_, _, _, _) => 51,
+            RData::TLSA(_, _, _, _) => 52,
+            RData::CDS(_, _, _, _) => 59,
+            RData::CDNSKEY(_, _, _, _) => 60,
+            RData::SVCB(_, _, _) => 64,
+            RData::HTTPS(_, _, _) => 65,
+            RData::SPF(_) => 99,
+            RData::URI(_, _, _) => 256,
+            RData::CAA(_, _, _) => 257,
             RData::Raw(i, _) => *i,
         }
     }
 
-    #[instrument(skip(buf))]
-    fn to_bytes(&self, buf: &mut Vec<u8>) -> Result<usize> {
+    /// Names are compressed for the RFC1035 types only, RFC3597 forbids
+    /// compression in the RDATA of any newer types.
+    #[instrument(skip(buf, names))]
+    pub(crate) fn to_bytes(
+        &self,
+        buf: &mut dyn Writer,
+        names: &mut NameCompressor,
+    ) -> Result<usize> {
         trace!("Writing {}", self);
 
         match self {
             RData::Raw(_, v) => {
-                buf.extend(v);
+                buf.write(v)?;
                 Ok(v.len())
             }
             RData::A(v4) => {
-                buf.extend_from_slice(&v4.octets());
+                buf.write(&v4.octets())?;
                 Ok(4)
             }
-            RData::NS(s) => encode_str(s, buf),
-            RData::CNAME(s) => encode_str(s, buf),
+            RData::NS(name) => name.to_bytes(buf, names),
+            RData::MD(name) => name.to_bytes(buf, names),
+            RData::MF(name) => name.to_bytes(buf, names),
+            RData::CNAME(name) => name.to_bytes(buf, names),
             RData::SOA(mname, rname, serial, refresh, retry, expire, minimum) => {
-                let mut bytes_written = encode_str(mname, buf)?;
-                bytes_written += encode_str(rname, buf)?;
-
-                buf.extend_from_slice(&serial.to_be_bytes());
-                buf.extend_from_slice(&refresh.to_be_bytes());
-                buf.extend_from_slice(&retry.to_be_bytes());
-                buf.extend_from_slice(&expire.to_be_bytes());
-                buf.extend_from_slice(&minimum.to_be_bytes());
+                let mut bytes_written = mname.to_bytes(buf, names)?;
+                bytes_written += rname.to_bytes(buf, names)?;
+
+                buf.write(&serial.to_be_bytes())?;
+                buf.write(&refresh.to_be_bytes())?;
+                buf.write(&retry.to_be_bytes())?;
+                buf.write(&expire.to_be_bytes())?;
+                buf.write(&minimum.to_be_bytes())?;
                 bytes_written += 20;
 
                 Ok(bytes_written)
             }
-            RData::TXT(s) => {
-                buf.extend_from_slice(s.as_bytes());
-                Ok(s.len())
+            RData::MB(name) => name.to_bytes(buf, names),
+            RData::MG(name) => name.to_bytes(buf, names),
+            RData::MR(name) => name.to_bytes(buf, names),
+            RData::NULL(v) => {
+                buf.write(v)?;
+                Ok(v.len())
             }
+            RData::WKS(address, protocol, bitmap) => {
+                buf.write(&address.octets())?;
+                buf.write(&[*protocol])?;
+                buf.write(bitmap)?;
+                Ok(5 + bitmap.len())
+            }
+            RData::PTR(name) => name.to_bytes(buf, names),
+            RData::HINFO(cpu, os) => {
+                let bytes_written = encode_character_string(cpu, buf)?;
+                Ok(bytes_written + encode_character_string(os, buf)?)
+            }
+            RData::MINFO(rmailbx, emailbx) => {
+                let bytes_written = rmailbx.to_bytes(buf, names)?;
+                Ok(bytes_written + emailbx.to_bytes(buf, names)?)
+            }
+            RData::MX(preference, exchange) => {
+                buf.write(&preference.to_be_bytes())?;
+                Ok(2 + exchange.to_bytes(buf, names)?)
+            }
+            RData::TXT(strings) => encode_character_strings(strings, buf),
             RData::AAAA(v6) => {
-                buf.extend_from_slice(&v6.octets());
+                buf.write(&v6.octets())?;
                 Ok(16)
             }
-            _ => todo!(),
+            RData::LOC(version, size, horiz_pre, vert_pre, latitude, longitude, altitude) => {
+                buf.write(&[*version])?;
+                buf.write(&[*size])?;
+                buf.write(&[*horiz_pre])?;
+                buf.write(&[*vert_pre])?;
+                buf.write(&latitude.to_be_bytes())?;
+                buf.write(&longitude.to_be_bytes())?;
+                buf.write(&altitude.to_be_bytes())?;
+                Ok(16)
+            }
+            RData::SRV(priority, weight, port, target) => {
+                buf.write(&priority.to_be_bytes())?;
+                buf.write(&weight.to_be_bytes())?;
+                buf.write(&port.to_be_bytes())?;
+                Ok(6 + target.to_bytes_uncompressed(buf)?)
+            }
+            RData::NAPTR(order, preference, flags, services, regexp, replacement) => {
+                buf.write(&order.to_be_bytes())?;
+                buf.write(&preference.to_be_bytes())?;
+                let mut bytes_written = 4;
+                bytes_written += encode_character_string(flags, buf)?;
+                bytes_written += encode_character_string(services, buf)?;
+                bytes_written += encode_character_string(regexp, buf)?;
+                Ok(bytes_written + replacement.to_bytes_uncompressed(buf)?)
+            }
+            RData::DNAME(target) => target.to_bytes_uncompressed(buf),
+            RData::DS(key_tag, algorithm, digest_type, digest)
+            | RData::CDS(key_tag, algorithm, digest_type, digest) => {
+                buf.write(&key_tag.to_be_bytes())?;
+                buf.write(&[*algorithm])?;
+                buf.write(&[*digest_type])?;
+                buf.write(digest)?;
+                Ok(4 + digest.len())
+            }
+            RData::RRSIG(
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                signer,
+                signature,
+            ) => {
+                buf.write(&u16::from(*type_covered).to_be_bytes())?;
+                buf.write(&[*algorithm])?;
+                buf.write(&[*labels])?;
+                buf.write(&original_ttl.to_be_bytes())?;
+                buf.write(&expiration.to_be_bytes())?;
+                buf.write(&inception.to_be_bytes())?;
+                buf.write(&key_tag.to_be_bytes())?;
+                let bytes_written = 18 + signer.to_bytes_uncompressed(buf)?;
+                buf.write(signature)?;
+                Ok(bytes_written + signature.len())
+            }
+            RData::NSEC(next, types) => {
+                let bytes_written = next.to_bytes_uncompressed(buf)?;
+                Ok(bytes_written + encode_type_bitmap(types, buf)?)
+            }
+            RData::DNSKEY(flags, protocol, algorithm, key)
+            | RData::CDNSKEY(flags, protocol, algorithm, key) => {
+                buf.write(&flags.to_be_bytes())?;
+                buf.write(&[*protocol])?;
+                buf.write(&[*algorithm])?;
+                buf.write(key)?;
+                Ok(4 + key.len())
+            }
+            RData::NSEC3(hash_algorithm, flags, iterations, salt, next, types) => {
+                buf.write(&[*hash_algorithm])?;
+                buf.write(&[*flags])?;
+                buf.write(&iterations.to_be_bytes())?;
+                let mut bytes_written = 4 + encode_character_string(salt, buf)?;
+                if next.is_empty() {
+                    return Err(MessageError::CharacterStringLengthExceeded(next.len()));
+                }
+                bytes_written += encode_character_string(next, buf)?;
+                Ok(bytes_written + encode_type_bitmap(types, buf)?)
+            }
+            RData::NSEC3PARAM(hash_algorithm, flags, iterations, salt) => {
+                buf.write(&[*hash_algorithm])?;
+                buf.write(&[*flags])?;
+                buf.write(&iterations.to_be_bytes())?;
+                Ok(4 + encode_character_string(salt, buf)?)
+            }
+            RData::SSHFP(algorithm, fp_type, fingerprint) => {
+                buf.write(&[*algorithm])?;
+                buf.write(&[*fp_type])?;
+                buf.write(fingerprint)?;
+                Ok(2 + fingerprint.len())
+            }
+            RData::TLSA(usage, selector, matching_type, data) => {
+                buf.write(&[*usage])?;
+                buf.write(&[*selector])?;
+                buf.write(&[*matching_type])?;
+                buf.write(data)?;
+                Ok(3 + data.len())
+            }
+            RData::SVCB(priority, target, params) | RData::HTTPS(priority, target, params) => {
+                buf.write(&priority.to_be_bytes())?;
+                let bytes_written = 2 + target.to_bytes_uncompressed(buf)?;
+                Ok(bytes_written + params.to_bytes(buf)?)
+            }
+            RData::SPF(strings) => encode_character_strings(strings, buf),
+            RData::URI(priority, weight, target) => {
+                buf.write(&priority.to_be_bytes())?;
+                buf.write(&weight.to_be_bytes())?;
+                buf.write(target)?;
+                Ok(4 + target.len())
+            }
+            RData::CAA(flags, tag, value) => {
+                if tag.is_empty() || tag.len() > 255 {
+                    return Err(MessageError::CharacterStringLengthExceeded(tag.len()));
+                }
+                buf.write(&[*flags])?;
+                buf.write(&[tag.len() as u8])?;
+                buf.write(tag)?;
+                buf.write(value)?;
+                Ok(2 + tag.len() + value.len())
+            }
         }
     }
 }
@@ -214,26 +747,795 @@ impl fmt::Display for RData {
         match self {
             Self::A(v4) => write!(f, "A({})", v4),
             Self::NS(s) => write!(f, "NS({})", s),
-            Self::MD => write!(f, "MD"),
-            Self::MF => write!(f, "MF"),
+            Self::MD(name) => write!(f, "MD({})", name),
+            Self::MF(name) => write!(f, "MF({})", name),
             Self::CNAME(s) => write!(f, "CNAME({})", s),
             Self::SOA(mname, rname, serial, refresh, retry, expire, minimum) => write!(
                 f,
                 "SOA({}, {}, {}, {}, {}, {}, {})",
                 mname, rname, serial, refresh, retry, expire, minimum
             ),
-            Self::MB => write!(f, "MB"),
-            Self::MG => write!(f, "MG"),
-            Self::MR => write!(f, "MR"),
-            Self::NULL => write!(f, "NULL"),
-            Self::WKS => write!(f, "WKS"),
-            Self::PTR => write!(f, "PTR"),
-            Self::HINFO => write!(f, "HINFO"),
-            Self::MINFO => write!(f, "MINFO"),
-            Self::MX => write!(f, "MX"),
-            Self::TXT(s) => write!(f, "TXT({})", s),
+            Self::MB(name) => write!(f, "MB({})", name),
+            Self::MG(name) => write!(f, "MG({})", name),
+            Self::MR(name) => write!(f, "MR({})", name),
+            Self::NULL(v) => write!(f, "NULL({:?})", v),
+            Self::WKS(address, protocol, bitmap) => {
+                write!(f, "WKS({}, {}", address, protocol)?;
+                for port in wks_ports(bitmap) {
+                    write!(f, ", {}", port)?;
+                }
+                write!(f, ")")
+            }
+            Self::PTR(name) => write!(f, "PTR({})", name),
+            Self::HINFO(cpu, os) => {
+                write!(f, "HINFO(")?;
+                fmt_character_string(f, cpu)?;
+                write!(f, ", ")?;
+                fmt_character_string(f, os)?;
+                write!(f, ")")
+            }
+            Self::MINFO(rmailbx, emailbx) => write!(f, "MINFO({}, {})", rmailbx, emailbx),
+            Self::MX(preference, exchange) => write!(f, "MX({}, {})", preference, exchange),
+            Self::TXT(strings) => {
+                write!(f, "TXT(")?;
+                fmt_character_strings(f, strings)?;
+                write!(f, ")")
+            }
             Self::AAAA(v6) => write!(f, "AAAA({})", v6),
+            Self::LOC(version, size, horiz_pre, vert_pre, latitude, longitude, altitude) => {
+                if *version != 0 {
+                    return write!(f, "LOC(version {})", version);
+                }
+                write!(f, "LOC(")?;
+                fmt_loc(
+                    f, *size, *horiz_pre, *vert_pre, *latitude, *longitude, *altitude,
+                )?;
+                write!(f, ")")
+            }
+            Self::SRV(priority, weight, port, target) => {
+                write!(f, "SRV({}, {}, {}, {})", priority, weight, port, target)
+            }
+            Self::NAPTR(order, preference, flags, services, regexp, replacement) => {
+                write!(f, "NAPTR({}, {}, ", order, preference)?;
+                fmt_character_string(f, flags)?;
+                write!(f, ", ")?;
+                fmt_character_string(f, services)?;
+                write!(f, ", ")?;
+                fmt_character_string(f, regexp)?;
+                write!(f, ", {})", replacement)
+            }
+            Self::DNAME(target) => write!(f, "DNAME({})", target),
+            Self::DS(key_tag, algorithm, digest_type, digest) => {
+                fmt_ds(f, "DS", *key_tag, *algorithm, *digest_type, digest)
+            }
+            Self::RRSIG(
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                signer,
+                signature,
+            ) => {
+                write!(
+                    f,
+                    "RRSIG({}, {}, {}, {}, ",
+                    type_covered, algorithm, labels, original_ttl
+                )?;
+                fmt_timestamp(f, *expiration)?;
+                write!(f, ", ")?;
+                fmt_timestamp(f, *inception)?;
+                write!(
+                    f,
+                    ", {}, {}, {})",
+                    key_tag,
+                    signer,
+                    base64_encode(signature)
+                )
+            }
+            Self::NSEC(next, types) => {
+                write!(f, "NSEC({}", next)?;
+                fmt_types(f, types)?;
+                write!(f, ")")
+            }
+            Self::DNSKEY(flags, protocol, algorithm, key) => {
+                fmt_dnskey(f, "DNSKEY", *flags, *protocol, *algorithm, key)
+            }
+            Self::NSEC3(hash_algorithm, flags, iterations, salt, next, types) => {
+                write!(f, "NSEC3({}, {}, {}, ", hash_algorithm, flags, iterations)?;
+                fmt_salt(f, salt)?;
+                write!(f, ", {}", base32hex_encode(next))?;
+                fmt_types(f, types)?;
+                write!(f, ")")
+            }
+            Self::NSEC3PARAM(hash_algorithm, flags, iterations, salt) => {
+                write!(
+                    f,
+                    "NSEC3PARAM({}, {}, {}, ",
+                    hash_algorithm, flags, iterations
+                )?;
+                fmt_salt(f, salt)?;
+                write!(f, ")")
+            }
+            Self::SSHFP(algorithm, fp_type, fingerprint) => {
+                write!(f, "SSHFP({}, {}, ", algorithm, fp_type)?;
+                fmt_hex(f, fingerprint)?;
+                write!(f, ")")
+            }
+            Self::TLSA(usage, selector, matching_type, data) => {
+                write!(f, "TLSA({}, {}, {}, ", usage, selector, matching_type)?;
+                fmt_hex(f, data)?;
+                write!(f, ")")
+            }
+            Self::CDS(key_tag, algorithm, digest_type, digest) => {
+                fmt_ds(f, "CDS", *key_tag, *algorithm, *digest_type, digest)
+            }
+            Self::CDNSKEY(flags, protocol, algorithm, key) => {
+                fmt_dnskey(f, "CDNSKEY", *flags, *protocol, *algorithm, key)
+            }
+            Self::SVCB(priority, target, params) => fmt_svcb(f, "SVCB", *priority, target, params),
+            Self::HTTPS(priority, target, params) => {
+                fmt_svcb(f, "HTTPS", *priority, target, params)
+            }
+            Self::SPF(strings) => {
+                write!(f, "SPF(")?;
+                fmt_character_strings(f, strings)?;
+                write!(f, ")")
+            }
+            Self::URI(priority, weight, target) => {
+                write!(f, "URI({}, {}, ", priority, weight)?;
+                fmt_character_string(f, target)?;
+                write!(f, ")")
+            }
+            Self::CAA(flags, tag, value) => {
+                write!(f, "CAA({}, {}, ", flags, String::from_utf8_lossy(tag))?;
+                fmt_character_string(f, value)?;
+                write!(f, ")")
+            }
             Self::Raw(id, v) => write!(f, "Raw({}: {:?})", id, v),
         }
     }
 }
+
+impl RData {
+    /// The rdata in the zone file presentation format.
+    #[cfg(feature = "serde")]
+    pub(crate) fn to_presentation(&self) -> String {
+        struct Presentation<'a>(&'a RData);
+
+        impl fmt::Display for Presentation<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+                self.0.fmt_presentation(f)
+            }
+        }
+
+        Presentation(self).to_string()
+    }
+
+    /// Writes the rdata in the zone file presentation format, names are
+    /// written fully qualified and types without a presentation format use
+    /// the RFC3597 generic format.
+    pub(crate) fn fmt_presentation(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> std::result::Result<(), fmt::Error> {
+        match self {
+            Self::A(v4) => write!(f, "{}", v4),
+            Self::NS(name)
+            | Self::MD(name)
+            | Self::MF(name)
+            | Self::CNAME(name)
+            | Self::MB(name)
+            | Self::MG(name)
+            | Self::MR(name)
+            | Self::PTR(name)
+            | Self::DNAME(name) => write!(f, "{}", name.to_fqdn()),
+            Self::SOA(mname, rname, serial, refresh, retry, expire, minimum) => write!(
+                f,
+                "{} {} {} {} {} {} {}",
+                mname.to_fqdn(),
+                rname.to_fqdn(),
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum
+            ),
+            Self::NULL(data) => fmt_generic(f, data),
+            Self::WKS(address, protocol, bitmap) => {
+                write!(f, "{} {}", address, protocol)?;
+                for port in wks_ports(bitmap) {
+                    write!(f, " {}", port)?;
+                }
+                Ok(())
+            }
+            Self::HINFO(cpu, os) => {
+                fmt_character_string(f, cpu)?;
+                write!(f, " ")?;
+                fmt_character_string(f, os)
+            }
+            Self::MINFO(rmailbx, emailbx) => {
+                write!(f, "{} {}", rmailbx.to_fqdn(), emailbx.to_fqdn())
+            }
+            Self::MX(preference, exchange) => {
+                write!(f, "{} {}", preference, exchange.to_fqdn())
+            }
+            Self::TXT(strings) | Self::SPF(strings) => {
+                if strings.is_empty() {
+                    return write!(f, "\"\"");
+                }
+                fmt_character_strings(f, strings)
+            }
+            Self::AAAA(v6) => write!(f, "{}", v6),
+            Self::LOC(0, size, horiz_pre, vert_pre, latitude, longitude, altitude) => fmt_loc(
+                f, *size, *horiz_pre, *vert_pre, *latitude, *longitude, *altitude,
+            ),
+            Self::LOC(..) => {
+                // Only version 0 has a presentation format.
+                let mut buf = Vec::new();
+                self.to_bytes(&mut buf, &mut NameCompressor::disabled())
+                    .map_err(|_| fmt::Error)?;
+                fmt_generic(f, &buf)
+            }
+            Self::SRV(priority, weight, port, target) => {
+                write!(f, "{} {} {} {}", priority, weight, port, target.to_fqdn())
+            }
+            Self::NAPTR(order, preference, flags, services, regexp, replacement) => {
+                write!(f, "{} {} ", order, preference)?;
+                fmt_character_string(f, flags)?;
+                write!(f, " ")?;
+                fmt_character_string(f, services)?;
+                write!(f, " ")?;
+                fmt_character_string(f, regexp)?;
+                write!(f, " {}", replacement.to_fqdn())
+            }
+            Self::DS(key_tag, algorithm, digest_type, digest)
+            | Self::CDS(key_tag, algorithm, digest_type, digest) => {
+                write!(f, "{} {} {} ", key_tag, algorithm, digest_type)?;
+                fmt_hex(f, digest)
+            }
+            Self::SSHFP(algorithm, fp_type, fingerprint) => {
+                write!(f, "{} {} ", algorithm, fp_type)?;
+                fmt_hex(f, fingerprint)
+            }
+            Self::RRSIG(
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                signer,
+                signature,
+            ) => {
+                write!(
+                    f,
+                    "{} {} {} {} ",
+                    type_covered, algorithm, labels, original_ttl
+                )?;
+                fmt_timestamp(f, *expiration)?;
+                write!(f, " ")?;
+                fmt_timestamp(f, *inception)?;
+                write!(
+                    f,
+                    " {} {} {}",
+                    key_tag,
+                    signer.to_fqdn(),
+                    base64_encode(signature)
+                )
+            }
+            Self::NSEC(next, types) => {
+                write!(f, "{}", next.to_fqdn())?;
+                fmt_types(f, types)
+            }
+            Self::DNSKEY(flags, protocol, algorithm, key)
+            | Self::CDNSKEY(flags, protocol, algorithm, key) => write!(
+                f,
+                "{} {} {} {}",
+                flags,
+                protocol,
+                algorithm,
+                base64_encode(key)
+            ),
+            Self::NSEC3(hash_algorithm, flags, iterations, salt, next, types) => {
+                write!(f, "{} {} {} ", hash_algorithm, flags, iterations)?;
+                fmt_salt(f, salt)?;
+                write!(f, " {}", base32hex_encode(next))?;
+                fmt_types(f, types)
+            }
+            Self::NSEC3PARAM(hash_algorithm, flags, iterations, salt) => {
+                write!(f, "{} {} {} ", hash_algorithm, flags, iterations)?;
+                fmt_salt(f, salt)
+            }
+            Self::TLSA(usage, selector, matching_type, data) => {
+                write!(f, "{} {} {} ", usage, selector, matching_type)?;
+                fmt_hex(f, data)
+            }
+            Self::SVCB(priority, target, params) | Self::HTTPS(priority, target, params) => {
+                write!(f, "{} {}", priority, target.to_fqdn())?;
+                if !params.is_empty() {
+                    write!(f, " {}", params)?;
+                }
+                Ok(())
+            }
+            Self::URI(priority, weight, target) => {
+                write!(f, "{} {} ", priority, weight)?;
+                fmt_character_string(f, target)
+            }
+            Self::CAA(flags, tag, value) => {
+                write!(f, "{} {} ", flags, String::from_utf8_lossy(tag))?;
+                fmt_character_string(f, value)
+            }
+            Self::Raw(_, data) => fmt_generic(f, data),
+        }
+    }
+}
+
+impl fmt::Display for ResourceRecord {
+    /// Writes the record in the zone file presentation format, i.e.
+    /// `www.example.com. 300 IN A 192.0.2.1`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        write!(
+            f,
+            "{} {} {} {} ",
+            self.name.to_fqdn(),
+            self.ttl,
+            self.class,
+            Type::from(self.data.as_u16())
+        )?;
+        self.data.fmt_presentation(f)
+    }
+}
+
+impl FromStr for ResourceRecord {
+    type Err = MessageError;
+
+    /// Parses a single record in the zone file presentation format. All names
+    /// are taken to be fully qualified, and the TTL must be given.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut records = ZoneParser::new().origin(DomainName::root()).parse_str(s)?;
+        match records.len() {
+            1 => Ok(records.remove(0)),
+            n => Err(MessageError::ParsingError(format!(
+                "Expected a single record, found {}",
+                n
+            ))),
+        }
+    }
+}
+
+/// Writes the RFC3597 generic rdata format, `\# <length> <hex>`.
+fn fmt_generic(f: &mut fmt::Formatter, data: &[u8]) -> std::result::Result<(), fmt::Error> {
+    write!(f, "\\# {}", data.len())?;
+    if !data.is_empty() {
+        write!(f, " ")?;
+        fmt_hex(f, data)?;
+    }
+    Ok(())
+}
+
+/// Writes an RFC1035 <character-string> - a single length octet followed by
+/// up to 255 octets.
+fn encode_character_string(s: &[u8], buf: &mut dyn Writer) -> Result<usize> {
+    if s.len() > 255 {
+        return Err(MessageError::CharacterStringLengthExceeded(s.len()));
+    }
+    buf.write(&[s.len() as u8])?;
+    buf.write(s)?;
+    Ok(s.len() + 1)
+}
+
+/// Writes each of the strings as <character-string>s, splitting any longer
+/// than 255 octets. There is always at least one <character-string> written.
+fn encode_character_strings(strings: &[Vec<u8>], buf: &mut dyn Writer) -> Result<usize> {
+    if strings.is_empty() {
+        return encode_character_string(&[], buf);
+    }
+    let mut bytes_written = 0;
+    for s in strings.iter() {
+        // An empty string has no chunks, but is still written.
+        if s.is_empty() {
+            bytes_written += encode_character_string(&[], buf)?;
+        }
+        for chunk in s.chunks(255) {
+            bytes_written += encode_character_string(chunk, buf)?;
+        }
+    }
+    Ok(bytes_written)
+}
+
+/// Splits the value into 255 octet chunks, an empty value is a single empty
+/// chunk.
+fn split_character_strings(value: &[u8]) -> Vec<Vec<u8>> {
+    if value.is_empty() {
+        return vec![Vec::new()];
+    }
+    value.chunks(255).map(|c| c.to_vec()).collect()
+}
+
+/// Writes the strings as space separated, quoted <character-string>s,
+/// splitting any longer than 255 octets as [`encode_character_strings`] does.
+fn fmt_character_strings(
+    f: &mut fmt::Formatter,
+    strings: &[Vec<u8>],
+) -> std::result::Result<(), fmt::Error> {
+    let chunks = strings.iter().flat_map(|s| {
+        // An empty string has no chunks, but is still written.
+        let empty = if s.is_empty() { Some(&[][..]) } else { None };
+        empty.into_iter().chain(s.chunks(255))
+    });
+    for (i, chunk) in chunks.enumerate() {
+        if i != 0 {
+            write!(f, " ")?;
+        }
+        fmt_character_string(f, chunk)?;
+    }
+    Ok(())
+}
+
+/// Writes a <character-string> in the quoted presentation format, escaping
+/// quotes and backslashes, and any non-printable octets as `\DDD`.
+fn fmt_character_string(f: &mut fmt::Formatter, s: &[u8]) -> std::result::Result<(), fmt::Error> {
+    write!(f, "\"")?;
+    for b in s.iter() {
+        match b {
+            b'"' | b'\\' => write!(f, "\\{}", *b as char)?,
+            0x20..=0x7e => write!(f, "{}", *b as char)?,
+            _ => write!(f, "\\{:03}", b)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// The ports that are set in a WKS bit map.
+fn wks_ports(bitmap: &[u8]) -> impl Iterator<Item = usize> + '_ {
+    bitmap.iter().enumerate().flat_map(|(i, b)| {
+        (0..8)
+            .filter(move |bit| b & (0x80 >> bit) != 0)
+            .map(move |bit| i * 8 + bit)
+    })
+}
+
+/// Writes the octets as uppercase hexadecimal.
+pub(crate) fn fmt_hex(f: &mut fmt::Formatter, data: &[u8]) -> std::result::Result<(), fmt::Error> {
+    for b in data.iter() {
+        write!(f, "{:02X}", b)?;
+    }
+    Ok(())
+}
+
+/// Writes the DS or CDS record data.
+fn fmt_ds(
+    f: &mut fmt::Formatter,
+    name: &str,
+    key_tag: u16,
+    algorithm: u8,
+    digest_type: u8,
+    digest: &[u8],
+) -> std::result::Result<(), fmt::Error> {
+    write!(f, "{}({}, {}, {}, ", name, key_tag, algorithm, digest_type)?;
+    fmt_hex(f, digest)?;
+    write!(f, ")")
+}
+
+/// Writes the DNSKEY or CDNSKEY record data, with the key in base64.
+fn fmt_dnskey(
+    f: &mut fmt::Formatter,
+    name: &str,
+    flags: u16,
+    protocol: u8,
+    algorithm: u8,
+    key: &[u8],
+) -> std::result::Result<(), fmt::Error> {
+    write!(
+        f,
+        "{}({}, {}, {}, {})",
+        name,
+        flags,
+        protocol,
+        algorithm,
+        base64_encode(key)
+    )
+}
+
+/// Writes an NSEC3 salt as hex, or "-" if it is empty.
+fn fmt_salt(f: &mut fmt::Formatter, salt: &[u8]) -> std::result::Result<(), fmt::Error> {
+    if salt.is_empty() {
+        return write!(f, "-");
+    }
+    fmt_hex(f, salt)
+}
+
+/// Writes the types of an NSEC or NSEC3 type bitmap, each preceded by a
+/// space.
+fn fmt_types(f: &mut fmt::Formatter, types: &[Type]) -> std::result::Result<(), fmt::Error> {
+    for t in types.iter() {
+        write!(f, " {}", t)?;
+    }
+    Ok(())
+}
+
+/// Writes an RRSIG timestamp as YYYYMMDDHHmmSS in UTC, as per RFC4034.
+fn fmt_timestamp(f: &mut fmt::Formatter, timestamp: u32) -> std::result::Result<(), fmt::Error> {
+    let secs = timestamp % 86_400;
+    let (year, month, day) = civil_from_days((timestamp / 86_400) as i64);
+    write!(
+        f,
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Converts the number of days since 1970-01-01 to a (year, month, day) in
+/// the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// RFC4034 - writes the types as a type bitmap, returning the number of bytes
+/// written.
+///
+/// The types are split into windows of 256 by the high octet, each window is
+/// written as the window number, the length of the bitmap (1-32 octets) and
+/// the bitmap, where bit 0 is the most significant bit of the first octet.
+fn encode_type_bitmap(types: &[Type], buf: &mut dyn Writer) -> Result<usize> {
+    let mut types: Vec<u16> = types.iter().map(|t| u16::from(*t)).collect();
+    types.sort_unstable();
+    types.dedup();
+
+    let start = buf.len();
+    let mut i = 0;
+    while i < types.len() {
+        let window = types[i] >> 8;
+        let mut bitmap = [0u8; 32];
+        let mut len = 0;
+        while i < types.len() && types[i] >> 8 == window {
+            let bit = (types[i] & 0xff) as usize;
+            bitmap[bit / 8] |= 0x80 >> (bit % 8);
+            len = bit / 8 + 1;
+            i += 1;
+        }
+        buf.write(&[window as u8])?;
+        buf.write(&[len as u8])?;
+        buf.write(&bitmap[..len])?;
+    }
+    Ok(buf.len() - start)
+}
+
+/// RFC1876 - Writes the fields of a version 0 LOC record in the
+/// presentation format, the position followed by the size and precisions.
+fn fmt_loc(
+    f: &mut fmt::Formatter,
+    size: u8,
+    horiz_pre: u8,
+    vert_pre: u8,
+    latitude: u32,
+    longitude: u32,
+    altitude: u32,
+) -> std::result::Result<(), fmt::Error> {
+    fmt_loc_coordinate(f, latitude, 'N', 'S')?;
+    write!(f, " ")?;
+    fmt_loc_coordinate(f, longitude, 'E', 'W')?;
+    let altitude = altitude as i64 - 10_000_000;
+    let sign = if altitude < 0 { "-" } else { "" };
+    write!(
+        f,
+        " {}{}.{:02}m",
+        sign,
+        altitude.abs() / 100,
+        altitude.abs() % 100
+    )?;
+    for precision in [size, horiz_pre, vert_pre].iter() {
+        write!(f, " ")?;
+        fmt_loc_precision(f, *precision)?;
+    }
+    Ok(())
+}
+
+/// Writes a LOC latitude or longitude as degrees, minutes and seconds.
+fn fmt_loc_coordinate(
+    f: &mut fmt::Formatter,
+    value: u32,
+    positive: char,
+    negative: char,
+) -> std::result::Result<(), fmt::Error> {
+    let value = value as i64 - (1 << 31);
+    let direction = if value < 0 { negative } else { positive };
+    let value = value.abs();
+    write!(
+        f,
+        "{} {} {}.{:03} {}",
+        value / 3_600_000,
+        (value / 60_000) % 60,
+        (value / 1000) % 60,
+        value % 1000,
+        direction
+    )
+}
+
+/// Writes a LOC size or precision, which is encoded as a base and power of
+/// ten of centimeters, in meters.
+fn fmt_loc_precision(f: &mut fmt::Formatter, value: u8) -> std::result::Result<(), fmt::Error> {
+    let cm = (value >> 4) as u64 * 10u64.pow((value & 0x0f) as u32);
+    if cm.is_multiple_of(100) {
+        write!(f, "{}m", cm / 100)
+    } else {
+        write!(f, "{}.{:02}m", cm / 100, cm % 100)
+    }
+}
+
+/// Writes a SVCB or HTTPS record, the target and params are only separated by
+/// a space as the params are themselves comma separated.
+fn fmt_svcb(
+    f: &mut fmt::Formatter,
+    name: &str,
+    priority: u16,
+    target: &DomainName,
+    params: &SvcParams,
+) -> std::result::Result<(), fmt::Error> {
+    write!(f, "{}({}, {}", name, priority, target)?;
+    if !params.is_empty() {
+        write!(f, " {}", params)?;
+    }
+    write!(f, ")")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::setup;
+
+    #[test]
+    fn test_presentation_round_trip() {
+        setup();
+
+        let records = [
+            "www.example.com. 300 IN A 192.0.2.1",
+            "example.com. 3600 IN SOA ns1.example.com. hostmaster.example.com. 2024010101 3600 900 604800 300",
+            "example.com. 60 IN NS ns1.example.com.",
+            "x.example.com. 60 IN NULL \\# 3 010203",
+            "x.example.com. 60 IN WKS 192.0.2.1 6 25 80",
+            "x.example.com. 60 IN HINFO \"Generic PC\" \"Linux\"",
+            "x.example.com. 60 IN MINFO a.example.com. b.example.com.",
+            "example.com. 60 IN MX 10 mail.example.com.",
+            "x.example.com. 60 IN TXT \"hello \\\"world\\\"\" \"\\255\"",
+            "x.example.com. 60 IN AAAA 2001:db8::1",
+            "x.example.com. 60 IN LOC 52 22 23.000 N 4 53 32.000 E -2.00m 0m 10000m 10m",
+            "_sip._udp.example.com. 60 IN SRV 10 20 5060 sip.example.com.",
+            "x.example.com. 60 IN NAPTR 100 10 \"U\" \"E2U+sip\" \"!^.*$!sip:info@example.com!\" .",
+            "x.example.com. 60 IN DNAME example.net.",
+            "x.example.com. 60 IN DS 60485 5 1 2BB183AF5F22588179A53B0A98631FAD1A292118",
+            "x.example.com. 60 IN SSHFP 1 1 123456789ABCDEF67890123456789ABCDEF67890",
+            "x.example.com. 60 IN RRSIG A 5 3 86400 20030322173103 20030220173103 2642 example.com. AQID",
+            "x.example.com. 60 IN NSEC host.example.com. A MX RRSIG NSEC TYPE1234",
+            "x.example.com. 60 IN DNSKEY 256 3 5 AQPSKmynfzW4kyBv015MUG2DeIQ3",
+            "x.example.com. 60 IN NSEC3 1 1 12 AABBCCDD 2VPTU5TIMAMQTTGL4LUU9KG21E0AOR3S A RRSIG",
+            "x.example.com. 60 IN NSEC3PARAM 1 0 12 -",
+            "_443._tcp.example.com. 60 IN TLSA 3 1 1 0C72AC70B745AC19998811B131D662C9",
+            "x.example.com. 60 IN CDS 0 0 0 00",
+            "x.example.com. 60 IN CDNSKEY 0 3 0 AA==",
+            "x.example.com. 60 IN HTTPS 1 . alpn=\"h2,h3\" port=8443 ipv4hint=192.0.2.1",
+            "x.example.com. 60 IN SVCB 0 svc.example.net.",
+            "x.example.com. 60 IN SPF \"v=spf1 -all\"",
+            "x.example.com. 60 IN URI 10 1 \"ftp://ftp1.example.com/public\"",
+            "x.example.com. 60 IN CAA 0 issue \"ca.example.net\"",
+            "x.example.com. 60 CH TYPE1234 \\# 4 0A000001",
+            "x.example.com. 60 CLASS12 A 192.0.2.1",
+        ];
+        for s in records.iter() {
+            let record: ResourceRecord = s.parse().unwrap();
+            assert_eq!(record.to_string(), *s);
+            assert_eq!(
+                record.to_string().parse::<ResourceRecord>().unwrap(),
+                record
+            );
+        }
+
+        // Names are fully qualified even without the trailing dot, and the
+        // generic format is accepted for any type.
+        let record: ResourceRecord = "www.example.com 300 in cname \\# 5 03666f6f00"
+            .parse()
+            .unwrap();
+        assert_eq!(record.to_string(), "www.example.com. 300 IN CNAME foo.");
+
+        assert!("www.example.com. IN A 192.0.2.1"
+            .parse::<ResourceRecord>()
+            .is_err());
+        assert!("a. 1 A 192.0.2.1\nb. 1 A 192.0.2.2"
+            .parse::<ResourceRecord>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_type_class_from_str() {
+        setup();
+
+        assert_eq!("aaaa".parse::<Type>().unwrap(), Type::AAAA);
+        assert_eq!("TYPE28".parse::<Type>().unwrap(), Type::AAAA);
+        assert_eq!("TYPE1234".parse::<Type>().unwrap(), Type::Unknown(1234));
+        assert_eq!(Type::Unknown(1234).to_string(), "TYPE1234");
+        assert_eq!("ANY".parse::<Type>().unwrap(), Type::STAR);
+        assert!("TYPE65536".parse::<Type>().is_err());
+        assert!("BOGUS".parse::<Type>().is_err());
+
+        assert_eq!("ch".parse::<Class>().unwrap(), Class::CH);
+        assert_eq!("CLASS1".parse::<Class>().unwrap(), Class::IN);
+        assert_eq!("CLASS12".parse::<Class>().unwrap(), Class::Unknown(12));
+        assert_eq!(Class::Unknown(12).to_string(), "CLASS12");
+        assert!("CLASS".parse::<Class>().is_err());
+    }
+
+    #[test]
+    fn test_canonical_bytes() {
+        setup();
+
+        // The owner and MX exchange are lowercased, and the exchange isn't
+        // compressed against the owner.
+        let record: ResourceRecord = "Example.COM. 60 IN MX 10 Mail.Example.COM."
+            .parse()
+            .unwrap();
+        let mut expected = vec![7];
+        expected.extend_from_slice(b"example");
+        expected.push(3);
+        expected.extend_from_slice(b"com");
+        expected.extend_from_slice(&[0, 0, 15, 0, 1, 0, 0, 0, 60, 0, 20, 0, 10, 4]);
+        expected.extend_from_slice(b"mail");
+        expected.push(7);
+        expected.extend_from_slice(b"example");
+        expected.push(3);
+        expected.extend_from_slice(b"com");
+        expected.push(0);
+        assert_eq!(record.to_canonical_bytes().unwrap(), expected);
+
+        // RFC6840 - the next name of an NSEC keeps its case.
+        let nsec: ResourceRecord = "x.example.com. 60 IN NSEC Host.example.com. A"
+            .parse()
+            .unwrap();
+        let bytes = nsec.data.to_canonical_bytes().unwrap();
+        assert_eq!(&bytes[..5], b"\x04Host");
+
+        let a: ResourceRecord = "a.example.com. 60 IN A 192.0.2.1".parse().unwrap();
+        let b: ResourceRecord = "A.example.com. 300 IN A 192.0.2.2".parse().unwrap();
+        let mx: ResourceRecord = "a.example.com. 60 IN MX 10 mail.example.com."
+            .parse()
+            .unwrap();
+        assert_eq!(a.canonical_cmp(&b).unwrap(), Ordering::Less);
+        assert_eq!(b.canonical_cmp(&mx).unwrap(), Ordering::Less);
+        assert_eq!(record.canonical_cmp(&a).unwrap(), Ordering::Less);
+        assert_eq!(a.canonical_cmp(&a.clone()).unwrap(), Ordering::Equal);
+
+        // Records whose RDATA can't be written can't be ordered, rather than
+        // comparing as equal.
+        let ns = |target: &str| ResourceRecord {
+            name: "example.com".into(),
+            data: RData::NS(DomainName::from(target)),
+            class: Class::IN,
+            ttl: 60,
+        };
+        let x = ns(&"x".repeat(64));
+        let y = ns(&"y".repeat(64));
+        assert!(matches!(
+            x.canonical_cmp(&y),
+            Err(MessageError::NameLengthExceeded(64, _))
+        ));
+        // The owner names differ, so the RDATA isn't needed.
+        assert_eq!(x.canonical_cmp(&a).unwrap(), Ordering::Less);
+    }
+}
@@ -0,0 +1,663 @@
+//! RFC8427 - Representing DNS Messages in JSON.
+
+use crate::encoding::{hex_decode, hex_encode};
+use crate::parser::read_rdata;
+use crate::{
+    Class, DomainName, Edns, Header, Message, MessageError, NameCompressor, OpCode, Question,
+    RCode, RData, ResourceRecord, Result, Type,
+};
+use serde::de::{self, IgnoredAny, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::TryFrom;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A [`Message`] with the member names of RFC8427, for exporting messages as
+/// JSON (or any other serde format) and reading them back.
+///
+/// Each record carries its rdata as `RDATAHEX` along with the presentation
+/// format in an `rdata<TYPE>` member, and the EDNS information is written as
+/// an OPT record in the additional section, so converting back to a
+/// [`Message`] is lossless. RFC8427 has no member for the reserved Z flag,
+/// so it's written as `Z` only when it's set.
+///
+/// ```
+/// use dns_message::{Message, MessageBuilder, QuestionBuilder, Rfc8427Message};
+/// use std::convert::TryFrom;
+///
+/// let message = MessageBuilder::new()
+///     .id(1234)
+///     .question(QuestionBuilder::new().name("example.com").build())
+///     .build();
+/// let json = Rfc8427Message::try_from(&message).unwrap();
+/// assert_eq!(Message::try_from(json).unwrap(), message);
+/// ```
+pub struct Rfc8427Message {
+    #[serde(rename = "ID")]
+    id: u16,
+    #[serde(rename = "QR", with = "flag")]
+    qr: bool,
+    #[serde(rename = "Opcode")]
+    opcode: u8,
+    #[serde(rename = "AA", with = "flag")]
+    aa: bool,
+    #[serde(rename = "TC", with = "flag")]
+    tc: bool,
+    #[serde(rename = "RD", with = "flag")]
+    rd: bool,
+    #[serde(rename = "RA", with = "flag")]
+    ra: bool,
+    #[serde(
+        rename = "Z",
+        with = "flag",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    z: bool,
+    #[serde(rename = "AD", with = "flag")]
+    ad: bool,
+    #[serde(rename = "CD", with = "flag")]
+    cd: bool,
+    #[serde(rename = "RCODE")]
+    rcode: u8,
+    #[serde(rename = "QDCOUNT", default)]
+    qd_count: u16,
+    #[serde(rename = "ANCOUNT", default)]
+    an_count: u16,
+    #[serde(rename = "NSCOUNT", default)]
+    ns_count: u16,
+    #[serde(rename = "ARCOUNT", default)]
+    ar_count: u16,
+
+    #[serde(rename = "QNAME", default, skip_serializing_if = "Option::is_none")]
+    qname: Option<String>,
+    #[serde(rename = "QTYPE", default, skip_serializing_if = "Option::is_none")]
+    qtype: Option<u16>,
+    #[serde(rename = "QTYPEname", default, skip_serializing_if = "Option::is_none")]
+    qtype_name: Option<String>,
+    #[serde(rename = "QCLASS", default, skip_serializing_if = "Option::is_none")]
+    qclass: Option<u16>,
+    #[serde(
+        rename = "QCLASSname",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    qclass_name: Option<String>,
+
+    #[serde(rename = "questionRRs", default, skip_serializing_if = "Vec::is_empty")]
+    questions: Vec<Rfc8427Record>,
+    #[serde(rename = "answerRRs", default, skip_serializing_if = "Vec::is_empty")]
+    answers: Vec<Rfc8427Record>,
+    #[serde(
+        rename = "authorityRRs",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    name_servers: Vec<Rfc8427Record>,
+    #[serde(
+        rename = "additionalRRs",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    additional_records: Vec<Rfc8427Record>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+/// A question or resource record with the member names of RFC8427.
+///
+/// Only `NAME`, `TYPE` (or `TYPEname`) and `CLASS` (or `CLASSname`) are
+/// needed for a question. Records also need a `TTL` and either `RDATAHEX` or
+/// the presentation format of the rdata in an `rdata<TYPE>` member, e.g.
+/// `"rdataA": "192.0.2.1"`.
+pub struct Rfc8427Record {
+    name: String,
+    rtype: Option<u16>,
+    type_name: Option<String>,
+    class: Option<u16>,
+    class_name: Option<String>,
+    ttl: Option<u32>,
+    rdlength: Option<u16>,
+    rdata_hex: Option<String>,
+    rdata: Option<(String, String)>,
+}
+
+impl TryFrom<&Message> for Rfc8427Message {
+    type Error = MessageError;
+
+    fn try_from(message: &Message) -> Result<Self> {
+        let header = &message.header;
+        let rcode = header.rcode.as_u16();
+        if rcode > 0xf && message.edns.is_none() {
+            return Err(MessageError::ExtendedRCodeWithoutEdns(rcode));
+        }
+
+        let mut additional_records = records(&message.additional_records)?;
+        if let Some(edns) = message.edns.as_ref() {
+            additional_records.push(opt_record(edns, &header.rcode)?);
+        }
+
+        let mut json = Rfc8427Message {
+            id: header.id,
+            qr: header.qr,
+            opcode: header.opcode.as_u8()?,
+            aa: header.aa,
+            tc: header.tc,
+            rd: header.rd,
+            ra: header.ra,
+            z: header.z,
+            ad: header.ad,
+            cd: header.cd,
+            rcode: (rcode & 0xf) as u8,
+            qd_count: count(message.questions.len())?,
+            an_count: count(message.answers.len())?,
+            ns_count: count(message.name_servers.len())?,
+            ar_count: count(additional_records.len())?,
+            qname: None,
+            qtype: None,
+            qtype_name: None,
+            qclass: None,
+            qclass_name: None,
+            questions: message.questions.iter().map(Rfc8427Record::from).collect(),
+            answers: records(&message.answers)?,
+            name_servers: records(&message.name_servers)?,
+            additional_records,
+        };
+
+        // The members for a single question are a convenience, the question
+        // is always in questionRRs as well.
+        if let [q] = message.questions.as_slice() {
+            json.qname = Some(q.q_name.to_fqdn());
+            json.qtype = Some(q.q_type.into());
+            json.qtype_name = Some(q.q_type.to_string());
+            json.qclass = Some(q.q_class.into());
+            json.qclass_name = Some(q.q_class.to_string());
+        }
+        Ok(json)
+    }
+}
+
+impl TryFrom<Rfc8427Message> for Message {
+    type Error = MessageError;
+
+    fn try_from(json: Rfc8427Message) -> Result<Self> {
+        let questions = if !json.questions.is_empty() {
+            json.questions
+                .iter()
+                .map(Rfc8427Record::to_question)
+                .collect::<Result<_>>()?
+        } else if let Some(qname) = json.qname {
+            let q = Rfc8427Record {
+                name: qname,
+                rtype: json.qtype,
+                type_name: json.qtype_name,
+                class: json.qclass,
+                class_name: json.qclass_name,
+                ..Default::default()
+            };
+            vec![q.to_question()?]
+        } else {
+            Vec::new()
+        };
+
+        let mut rcode = json.rcode as u16;
+        let mut edns = None;
+        let mut additional_records = Vec::new();
+        for record in json.additional_records.iter() {
+            if record.rtype()? != Type::OPT {
+                additional_records.push(record.to_record()?);
+                continue;
+            }
+            if edns.is_some() {
+                return Err(MessageError::InvalidOpt("Multiple OPT records".to_string()));
+            }
+            let rdata = record.rdata_bytes()?.unwrap_or_default();
+            let (e, extended_rcode) =
+                Edns::from_opt(record.class()?.into(), record.ttl()?, &rdata)?;
+            rcode |= (extended_rcode as u16) << 4;
+            edns = Some(e);
+        }
+
+        Ok(Message {
+            header: Header {
+                id: json.id,
+                qr: json.qr,
+                opcode: OpCode::from(json.opcode),
+                aa: json.aa,
+                tc: json.tc,
+                rd: json.rd,
+                ra: json.ra,
+                z: json.z,
+                ad: json.ad,
+                cd: json.cd,
+                rcode: RCode::from(rcode),
+            },
+            questions,
+            answers: to_records(&json.answers)?,
+            name_servers: to_records(&json.name_servers)?,
+            additional_records,
+            edns,
+        })
+    }
+}
+
+impl From<&Question> for Rfc8427Record {
+    fn from(q: &Question) -> Self {
+        Rfc8427Record {
+            name: q.q_name.to_fqdn(),
+            rtype: Some(q.q_type.into()),
+            type_name: Some(q.q_type.to_string()),
+            class: Some(q.q_class.into()),
+            class_name: Some(q.q_class.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+impl TryFrom<&ResourceRecord> for Rfc8427Record {
+    type Error = MessageError;
+
+    fn try_from(record: &ResourceRecord) -> Result<Self> {
+        let rtype = Type::from(record.data.as_u16());
+        let mut rdata = Vec::new();
+        record
+            .data
+            .to_bytes(&mut rdata, &mut NameCompressor::disabled())?;
+
+        Ok(Rfc8427Record {
+            name: record.name.to_fqdn(),
+            rtype: Some(rtype.into()),
+            type_name: Some(rtype.to_string()),
+            class: Some(record.class.into()),
+            class_name: Some(record.class.to_string()),
+            ttl: Some(record.ttl),
+            rdlength: Some(u16::try_from(rdata.len()).map_err(|_| {
+                MessageError::EncodingError(
+                    format!("RDATA of {} bytes is too long", rdata.len()).into(),
+                )
+            })?),
+            rdata_hex: Some(hex_encode(&rdata)),
+            // Unknown types only have the generic format, which RDATAHEX
+            // already covers.
+            rdata: match record.data {
+                RData::Raw(..) => None,
+                _ => Some((format!("rdata{}", rtype), record.data.to_presentation())),
+            },
+        })
+    }
+}
+
+impl Rfc8427Record {
+    fn rtype(&self) -> Result<Type> {
+        match (self.rtype, self.type_name.as_ref()) {
+            (Some(t), _) => Ok(Type::from(t)),
+            (None, Some(name)) => name.parse(),
+            (None, None) => Err(self.missing("TYPE")),
+        }
+    }
+
+    fn class(&self) -> Result<Class> {
+        match (self.class, self.class_name.as_ref()) {
+            (Some(c), _) => Ok(Class::from(c)),
+            (None, Some(name)) => name.parse(),
+            (None, None) => Err(self.missing("CLASS")),
+        }
+    }
+
+    fn ttl(&self) -> Result<u32> {
+        self.ttl.ok_or_else(|| self.missing("TTL"))
+    }
+
+    fn name(&self) -> Result<DomainName> {
+        self.name.parse()
+    }
+
+    /// The RDATAHEX, checked against the RDLENGTH when it's present.
+    fn rdata_bytes(&self) -> Result<Option<Vec<u8>>> {
+        let hex = match self.rdata_hex.as_ref() {
+            Some(hex) => hex,
+            None => return Ok(None),
+        };
+        let rdata = hex_decode(hex).ok_or_else(|| {
+            MessageError::ParsingError(format!("Invalid RDATAHEX for {}", self.name))
+        })?;
+        if self.rdlength.is_some_and(|len| len as usize != rdata.len()) {
+            return Err(MessageError::ParsingError(format!(
+                "RDLENGTH doesn't match RDATAHEX for {}",
+                self.name
+            )));
+        }
+        Ok(Some(rdata))
+    }
+
+    fn to_question(&self) -> Result<Question> {
+        Ok(Question {
+            q_name: self.name()?,
+            q_type: self.rtype()?,
+            q_class: self.class()?,
+        })
+    }
+
+    fn to_record(&self) -> Result<ResourceRecord> {
+        let rtype = self.rtype()?;
+        let data = match (self.rdata_bytes()?, self.rdata.as_ref()) {
+            (Some(rdata), _) => read_rdata(&rdata, rtype, &rdata)?,
+            (None, Some((_, presentation))) => {
+                // The presentation format is parsed as a whole record.
+                let record: ResourceRecord = format!(
+                    "{} {} {} {} {}",
+                    self.name,
+                    self.ttl()?,
+                    self.class()?,
+                    rtype,
+                    presentation
+                )
+                .parse()?;
+                record.data
+            }
+            (None, None) => return Err(self.missing("RDATAHEX")),
+        };
+        Ok(ResourceRecord {
+            name: self.name()?,
+            data,
+            class: self.class()?,
+            ttl: self.ttl()?,
+        })
+    }
+
+    fn missing(&self, member: &str) -> MessageError {
+        MessageError::ParsingError(format!("Missing {} for {}", member, self.name))
+    }
+}
+
+impl Serialize for Rfc8427Record {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("NAME", &self.name)?;
+        if let Some(rtype) = self.rtype {
+            map.serialize_entry("TYPE", &rtype)?;
+        }
+        if let Some(type_name) = self.type_name.as_ref() {
+            map.serialize_entry("TYPEname", type_name)?;
+        }
+        if let Some(class) = self.class {
+            map.serialize_entry("CLASS", &class)?;
+        }
+        if let Some(class_name) = self.class_name.as_ref() {
+            map.serialize_entry("CLASSname", class_name)?;
+        }
+        if let Some(ttl) = self.ttl {
+            map.serialize_entry("TTL", &ttl)?;
+        }
+        if let Some(rdlength) = self.rdlength {
+            map.serialize_entry("RDLENGTH", &rdlength)?;
+        }
+        if let Some(rdata_hex) = self.rdata_hex.as_ref() {
+            map.serialize_entry("RDATAHEX", rdata_hex)?;
+        }
+        if let Some((member, rdata)) = self.rdata.as_ref() {
+            map.serialize_entry(member, rdata)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Rfc8427Record {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_map(RecordVisitor)
+    }
+}
+
+/// Reads the members of a record, the `rdata<TYPE>` member can't be derived
+/// as its name depends on the type.
+struct RecordVisitor;
+
+impl<'de> Visitor<'de> for RecordVisitor {
+    type Value = Rfc8427Record;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "an RFC8427 resource record object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(
+        self,
+        mut map: A,
+    ) -> std::result::Result<Self::Value, A::Error> {
+        let mut record = Rfc8427Record::default();
+        let mut name = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "NAME" => name = Some(map.next_value()?),
+                "TYPE" => record.rtype = Some(map.next_value()?),
+                "TYPEname" => record.type_name = Some(map.next_value()?),
+                "CLASS" => record.class = Some(map.next_value()?),
+                "CLASSname" => record.class_name = Some(map.next_value()?),
+                "TTL" => record.ttl = Some(map.next_value()?),
+                "RDLENGTH" => record.rdlength = Some(map.next_value()?),
+                "RDATAHEX" => record.rdata_hex = Some(map.next_value()?),
+                _ if key.starts_with("rdata") => {
+                    let value = map.next_value()?;
+                    record.rdata = Some((key, value));
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        record.name = name.ok_or_else(|| de::Error::missing_field("NAME"))?;
+        Ok(record)
+    }
+}
+
+/// RFC8427 flags are the integers 0 and 1, though booleans are accepted too.
+mod flag {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(
+        flag: &bool,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*flag as u8)
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<bool, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Flag {
+            Int(u8),
+            Bool(bool),
+        }
+        match Flag::deserialize(deserializer)? {
+            Flag::Int(0) | Flag::Bool(false) => Ok(false),
+            Flag::Int(1) | Flag::Bool(true) => Ok(true),
+            Flag::Int(n) => Err(serde::de::Error::custom(format!(
+                "flags must be 0 or 1, not {}",
+                n
+            ))),
+        }
+    }
+}
+
+/// The OPT pseudo-record as an ordinary record, the rdata is everything after
+/// the fixed 11 octets of the OPT record.
+fn opt_record(edns: &Edns, rcode: &RCode) -> Result<Rfc8427Record> {
+    let mut buf = Vec::new();
+    edns.to_bytes(rcode, &mut buf)?;
+    let rdata = &buf[11..];
+    Ok(Rfc8427Record {
+        name: DomainName::root().to_fqdn(),
+        rtype: Some(Type::OPT.into()),
+        type_name: Some(Type::OPT.to_string()),
+        class: Some(edns.udp_payload_size),
+        ttl: Some(u32::from_be_bytes([buf[5], buf[6], buf[7], buf[8]])),
+        rdlength: Some(rdata.len() as u16),
+        rdata_hex: Some(hex_encode(rdata)),
+        ..Default::default()
+    })
+}
+
+fn records(records: &[ResourceRecord]) -> Result<Vec<Rfc8427Record>> {
+    records.iter().map(Rfc8427Record::try_from).collect()
+}
+
+fn to_records(records: &[Rfc8427Record]) -> Result<Vec<ResourceRecord>> {
+    records.iter().map(Rfc8427Record::to_record).collect()
+}
+
+fn count(len: usize) -> Result<u16> {
+    u16::try_from(len).map_err(|_| {
+        MessageError::EncodingError(format!("{} records don't fit in a count", len).into())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::setup;
+    use crate::{EdnsOption, MessageBuilder, QuestionBuilder};
+
+    #[test]
+    fn test_rfc8427_round_trip() {
+        setup();
+
+        let mut message = MessageBuilder::new()
+            .id(1234)
+            .qr(true)
+            .rd(true)
+            .ra(true)
+            .rcode(RCode::BadVers)
+            .question(QuestionBuilder::new().name("www.example.com").build())
+            .answer(
+                "www.example.com. 300 IN CNAME example.com."
+                    .parse()
+                    .unwrap(),
+            )
+            .answer("example.com. 300 IN A 192.0.2.1".parse().unwrap())
+            .name_server(
+                "example.com. 3600 IN SOA ns1.example.com. hostmaster.example.com. 1 2 3 4 5"
+                    .parse()
+                    .unwrap(),
+            )
+            .additional_record("x.example.com. 60 IN TYPE1234 \\# 2 0102".parse().unwrap())
+            .edns(Edns {
+                dnssec_ok: true,
+                options: vec![EdnsOption::Nsid(b"ns1".to_vec())],
+                ..Default::default()
+            })
+            .build();
+
+        let json = serde_json::to_value(Rfc8427Message::try_from(&message).unwrap()).unwrap();
+        assert!(json.get("Z").is_none());
+
+        message.header.z = true;
+        let json = serde_json::to_value(Rfc8427Message::try_from(&message).unwrap()).unwrap();
+        assert_eq!(json["ID"], 1234);
+        assert_eq!(json["QR"], 1);
+        assert_eq!(json["Z"], 1);
+        assert_eq!(json["RCODE"], 0);
+        assert_eq!(json["QNAME"], "www.example.com.");
+        assert_eq!(json["QTYPEname"], "A");
+        assert_eq!(json["ARCOUNT"], 2);
+        assert_eq!(
+            json["answerRRs"][1],
+            serde_json::json!({
+                "NAME": "example.com.",
+                "TYPE": 1,
+                "TYPEname": "A",
+                "CLASS": 1,
+                "CLASSname": "IN",
+                "TTL": 300,
+                "RDLENGTH": 4,
+                "RDATAHEX": "C0000201",
+                "rdataA": "192.0.2.1",
+            })
+        );
+        // The SOA names aren't compressed in the RDATAHEX.
+        assert_eq!(
+            json["authorityRRs"][0]["rdataSOA"],
+            "ns1.example.com. hostmaster.example.com. 1 2 3 4 5"
+        );
+        assert_eq!(json["authorityRRs"][0]["RDLENGTH"], 61);
+        assert!(json["additionalRRs"][0].get("rdataTYPE1234").is_none());
+        assert_eq!(json["additionalRRs"][1]["TYPE"], 41);
+        assert_eq!(json["additionalRRs"][1]["CLASS"], 1232);
+        assert_eq!(json["additionalRRs"][1]["TTL"], 0x0100_8000);
+
+        let text = serde_json::to_string(&json).unwrap();
+        let json: Rfc8427Message = serde_json::from_str(&text).unwrap();
+        assert_eq!(Message::try_from(json).unwrap(), message);
+    }
+
+    #[test]
+    fn test_rfc8427_input() {
+        setup();
+
+        // RFC8427 section 6.1 - a query with only the single question members.
+        let json: Rfc8427Message = serde_json::from_str(
+            r#"{ "ID": 19678, "QR": 0, "Opcode": 0, "AA": 0, "TC": 0, "RD": 0,
+                 "RA": 0, "AD": 0, "CD": 0, "RCODE": 0, "QDCOUNT": 1,
+                 "ANCOUNT": 0, "NSCOUNT": 0, "ARCOUNT": 0,
+                 "QNAME": "example.com", "QTYPE": 1, "QCLASS": 1 }"#,
+        )
+        .unwrap();
+        let message = Message::try_from(json).unwrap();
+        assert_eq!(message.header.id, 19678);
+        assert_eq!(
+            message.questions,
+            vec![QuestionBuilder::new().name("example.com").build()]
+        );
+
+        // Records can be given with only the presentation format, and flags as
+        // booleans.
+        let json: Rfc8427Message = serde_json::from_str(
+            r#"{ "ID": 1, "QR": true, "Opcode": 0, "AA": true, "TC": 0, "RD": 0,
+                 "RA": 0, "AD": 0, "CD": 0, "RCODE": 3,
+                 "answerRRs": [ { "NAME": "example.com.", "TYPEname": "MX",
+                                  "CLASSname": "IN", "TTL": 60,
+                                  "rdataMX": "10 mail.example.com." } ] }"#,
+        )
+        .unwrap();
+        let message = Message::try_from(json).unwrap();
+        assert_eq!(message.header.rcode, RCode::NameError);
+        assert!(message.header.aa);
+        assert_eq!(
+            message.answers[0].to_string(),
+            "example.com. 60 IN MX 10 mail.example.com."
+        );
+
+        let json: Rfc8427Message = serde_json::from_str(
+            r#"{ "ID": 1, "QR": 0, "Opcode": 0, "AA": 0, "TC": 0, "RD": 0,
+                 "RA": 0, "AD": 0, "CD": 0, "RCODE": 0,
+                 "answerRRs": [ { "NAME": "example.com.", "TYPE": 1, "CLASS": 1,
+                                  "TTL": 60, "RDLENGTH": 5, "RDATAHEX": "C0000201" } ] }"#,
+        )
+        .unwrap();
+        assert!(Message::try_from(json).is_err());
+        assert!(serde_json::from_str::<Rfc8427Message>(r#"{ "ID": 1, "QR": 2 }"#).is_err());
+    }
+
+    #[test]
+    fn test_serde_derive() {
+        setup();
+
+        let message = MessageBuilder::new()
+            .id(1)
+            .question(QuestionBuilder::new().name("www.example.com").build())
+            .answer(
+                "www.example.com. 60 IN HTTPS 1 . alpn=h2 ipv6hint=2001:db8::1"
+                    .parse()
+                    .unwrap(),
+            )
+            .answer(
+                "www.example.com. 60 CLASS12 TYPE1234 \\# 1 00"
+                    .parse()
+                    .unwrap(),
+            )
+            .edns(Edns::default())
+            .build();
+        let text = serde_json::to_string(&message).unwrap();
+        assert_eq!(serde_json::from_str::<Message>(&text).unwrap(), message);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{Class, DomainName, RData, ResourceRecord, Result, Type};
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+/// RFC2181 - A resource record set, the records that share an owner name,
+/// class and type.
+///
+/// The records of a set share a single TTL, which is the lowest of the TTLs
+/// they were grouped with. The RDATA is kept in the RFC4034 canonical order,
+/// with any duplicates removed, so two sets holding the same records compare
+/// equal whatever order the records arrived in.
+pub struct RRset {
+    name: DomainName,
+    class: Class,
+    rtype: Type,
+    ttl: u32,
+    data: Vec<RData>,
+}
+
+impl RRset {
+    /// Groups the records into sets, in the order that each set first
+    /// appears.
+    ///
+    /// Fails if the RDATA of a record can't be written in its canonical form,
+    /// such as a name that is too long, as the set couldn't then be ordered.
+    pub fn group<'a, I>(records: I) -> Result<Vec<RRset>>
+    where
+        I: IntoIterator<Item = &'a ResourceRecord>,
+    {
+        let mut sets: Vec<RRset> = Vec::new();
+        for record in records {
+            let rtype = Type::from(record.data.as_u16());
+            match sets
+                .iter_mut()
+                .find(|s| s.rtype == rtype && s.class == record.class && s.name == record.name)
+            {
+                Some(set) => {
+                    set.ttl = set.ttl.min(record.ttl);
+                    set.data.push(record.data.clone());
+                }
+                None => sets.push(RRset {
+                    name: record.name.clone(),
+                    class: record.class,
+                    rtype,
+                    ttl: record.ttl,
+                    data: vec![record.data.clone()],
+                }),
+            }
+        }
+
+        for set in sets.iter_mut() {
+            let mut keyed = set
+                .data
+                .drain(..)
+                .map(|d| Ok((d.to_canonical_bytes()?, d)))
+                .collect::<Result<Vec<_>>>()?;
+            keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+            keyed.dedup_by(|(a, _), (b, _)| a == b);
+            set.data = keyed.into_iter().map(|(_, d)| d).collect();
+        }
+        Ok(sets)
+    }
+
+    /// The owner name of the records.
+    pub fn name(&self) -> &DomainName {
+        &self.name
+    }
+
+    /// The [`Class`] of the records.
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// The [`Type`] of the records.
+    pub fn rtype(&self) -> Type {
+        self.rtype
+    }
+
+    /// The TTL shared by the records.
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    /// The RDATA of each record, in the canonical order.
+    pub fn data(&self) -> &[RData] {
+        &self.data
+    }
+
+    /// The records of the set, in the canonical order.
+    pub fn records(&self) -> impl Iterator<Item = ResourceRecord> + '_ {
+        self.data.iter().map(move |data| ResourceRecord {
+            name: self.name.clone(),
+            data: data.clone(),
+            class: self.class,
+            ttl: self.ttl,
+        })
+    }
+
+    /// RFC4034 - The canonical form of every record in the set, in the
+    /// canonical order, as covered by an RRSIG. See
+    /// [`ResourceRecord::to_canonical_bytes`].
+    pub fn to_canonical_bytes(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        for record in self.records() {
+            buf.extend_from_slice(&record.to_canonical_bytes()?);
+        }
+        Ok(buf)
+    }
+
+    /// Compares sets in the canonical order of their owner names, then by
+    /// class and type, as a zone is sorted.
+    pub fn canonical_cmp(&self, other: &RRset) -> Ordering {
+        self.name
+            .canonical_cmp(&other.name)
+            .then_with(|| u16::from(self.class).cmp(&u16::from(other.class)))
+            .then_with(|| u16::from(self.rtype).cmp(&u16::from(other.rtype)))
+    }
+}
+
+impl fmt::Display for RRset {
+    /// Writes each record on its own line in the zone file format.
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        for (i, record) in self.records().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", record)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::setup;
+    use crate::MessageError;
+
+    fn records(records: &[&str]) -> Vec<ResourceRecord> {
+        records.iter().map(|r| r.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_group() {
+        setup();
+
+        let records = records(&[
+            "www.example.com. 300 IN A 192.0.2.2",
+            "example.com. 60 IN MX 10 mail.example.com.",
+            "WWW.example.com. 200 IN A 192.0.2.1",
+            "www.example.com. 300 IN AAAA 2001:db8::1",
+            "www.example.com. 300 IN A 192.0.2.2",
+            "www.example.com. 300 CH A 192.0.2.3",
+        ]);
+        let sets = RRset::group(records.iter()).unwrap();
+        assert_eq!(sets.len(), 4);
+
+        // The duplicate is dropped, and the lowest TTL is kept.
+        assert_eq!(sets[0].name(), "www.example.com");
+        assert_eq!(sets[0].rtype(), Type::A);
+        assert_eq!(sets[0].ttl(), 200);
+        assert_eq!(
+            sets[0].to_string(),
+            "www.example.com. 200 IN A 192.0.2.1\nwww.example.com. 200 IN A 192.0.2.2"
+        );
+        assert_eq!(sets[1].rtype(), Type::MX);
+        assert_eq!(sets[2].rtype(), Type::AAAA);
+        assert_eq!(sets[3].class(), Class::CH);
+
+        // The order the records arrive in doesn't matter.
+        let reversed: Vec<ResourceRecord> = records.into_iter().rev().collect();
+        let mut other = RRset::group(reversed.iter()).unwrap();
+        assert_eq!(other.len(), 4);
+        other.sort_by(|a, b| a.canonical_cmp(b));
+        let mut sets = sets;
+        sets.sort_by(|a, b| a.canonical_cmp(b));
+        assert_eq!(sets, other);
+        assert_eq!(
+            sets.iter().map(|s| s.rtype()).collect::<Vec<_>>(),
+            vec![Type::MX, Type::A, Type::AAAA, Type::A]
+        );
+    }
+
+    #[test]
+    fn test_canonical_rrset() {
+        setup();
+
+        // RFC4034 section 6.3 - the RDATA is ordered as octet strings of the
+        // lowercased and uncompressed names, so the one octet first label of
+        // a.ns.example.com sorts first.
+        let records = records(&[
+            "example.com. 3600 IN NS NS2.example.com.",
+            "example.com. 3600 IN NS ns1.example.com.",
+            "example.com. 3600 IN NS a.ns.example.com.",
+        ]);
+        let sets = RRset::group(records.iter()).unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(
+            sets[0].data(),
+            &[
+                RData::NS("a.ns.example.com".into()),
+                RData::NS("ns1.example.com".into()),
+                RData::NS("NS2.example.com".into()),
+            ]
+        );
+
+        let bytes = sets[0].to_canonical_bytes().unwrap();
+        let mut expected = Vec::new();
+        for record in sets[0].records() {
+            expected.extend(record.to_canonical_bytes().unwrap());
+        }
+        assert_eq!(bytes, expected);
+        assert!(bytes.windows(3).any(|w| w == b"ns2"));
+        assert!(!bytes.windows(3).any(|w| w == b"NS2"));
+    }
+
+    #[test]
+    fn test_group_unwritable() {
+        setup();
+
+        // A lenient conversion leaves a label too long to be written, so the
+        // set can't be put in the canonical order.
+        let mut records = records(&["example.com. 3600 IN NS ns1.example.com."]);
+        records.push(ResourceRecord {
+            name: "example.com".into(),
+            data: RData::NS(DomainName::from("a".repeat(64).as_str())),
+            class: Class::IN,
+            ttl: 3600,
+        });
+        assert!(matches!(
+            RRset::group(records.iter()),
+            Err(MessageError::NameLengthExceeded(64, _))
+        ));
+    }
+}
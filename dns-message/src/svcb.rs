@@ -0,0 +1,582 @@
+use crate::{MessageError, Result, Writer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// RFC9460 - the SvcParamKeys registered for SVCB and HTTPS records.
+const MANDATORY: u16 = 0;
+const ALPN: u16 = 1;
+const NO_DEFAULT_ALPN: u16 = 2;
+const PORT: u16 = 3;
+const IPV4HINT: u16 = 4;
+const ECH: u16 = 5;
+const IPV6HINT: u16 = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// A single SVCB/HTTPS service parameter (RFC9460).
+pub enum SvcParam {
+    /// (0) The keys that a client must understand to use this record.
+    Mandatory(Vec<u16>),
+
+    /// (1) The Application-Layer Protocol Negotiation protocol ids that are
+    /// supported.
+    Alpn(Vec<Vec<u8>>),
+
+    /// (2) The default ALPN protocol of the scheme is not supported.
+    NoDefaultAlpn,
+
+    /// (3) The TCP or UDP port to connect to.
+    Port(u16),
+
+    /// (4) Addresses the client may use to reach the service.
+    Ipv4Hint(Vec<Ipv4Addr>),
+
+    /// (5) An encrypted ClientHello configuration list.
+    Ech(Vec<u8>),
+
+    /// (6) Addresses the client may use to reach the service.
+    Ipv6Hint(Vec<Ipv6Addr>),
+
+    /// A key that isn't known - the key and raw value are contained within.
+    Unknown(u16, Vec<u8>),
+}
+
+impl SvcParam {
+    /// The SvcParamKey of this parameter.
+    pub fn key(&self) -> u16 {
+        match self {
+            SvcParam::Mandatory(_) => MANDATORY,
+            SvcParam::Alpn(_) => ALPN,
+            SvcParam::NoDefaultAlpn => NO_DEFAULT_ALPN,
+            SvcParam::Port(_) => PORT,
+            SvcParam::Ipv4Hint(_) => IPV4HINT,
+            SvcParam::Ech(_) => ECH,
+            SvcParam::Ipv6Hint(_) => IPV6HINT,
+            SvcParam::Unknown(key, _) => *key,
+        }
+    }
+
+    /// Decodes the wire format value of the parameter with the given key.
+    pub(crate) fn from_bytes(key: u16, value: &[u8]) -> Result<SvcParam> {
+        let invalid = || MessageError::InvalidSvcParams(format!("Invalid value for key{}", key));
+
+        let param = match key {
+            MANDATORY => {
+                if value.is_empty() || !value.len().is_multiple_of(2) {
+                    return Err(invalid());
+                }
+                let keys: Vec<u16> = value
+                    .chunks(2)
+                    .map(|k| u16::from_be_bytes([k[0], k[1]]))
+                    .collect();
+                if keys.windows(2).any(|w| w[0] >= w[1]) {
+                    return Err(MessageError::InvalidSvcParams(
+                        "Mandatory keys must be in strictly increasing order".to_string(),
+                    ));
+                }
+                SvcParam::Mandatory(keys)
+            }
+            ALPN => {
+                let mut ids = Vec::new();
+                let mut i = value;
+                while let Some((len, rest)) = i.split_first() {
+                    let len = *len as usize;
+                    if len == 0 || rest.len() < len {
+                        return Err(invalid());
+                    }
+                    ids.push(rest[..len].to_vec());
+                    i = &rest[len..];
+                }
+                if ids.is_empty() {
+                    return Err(invalid());
+                }
+                SvcParam::Alpn(ids)
+            }
+            NO_DEFAULT_ALPN => {
+                if !value.is_empty() {
+                    return Err(invalid());
+                }
+                SvcParam::NoDefaultAlpn
+            }
+            PORT => {
+                if value.len() != 2 {
+                    return Err(invalid());
+                }
+                SvcParam::Port(u16::from_be_bytes([value[0], value[1]]))
+            }
+            IPV4HINT => {
+                if value.is_empty() || !value.len().is_multiple_of(4) {
+                    return Err(invalid());
+                }
+                SvcParam::Ipv4Hint(
+                    value
+                        .chunks(4)
+                        .map(|a| Ipv4Addr::new(a[0], a[1], a[2], a[3]))
+                        .collect(),
+                )
+            }
+            ECH => SvcParam::Ech(value.to_vec()),
+            IPV6HINT => {
+                if value.is_empty() || !value.len().is_multiple_of(16) {
+                    return Err(invalid());
+                }
+                SvcParam::Ipv6Hint(
+                    value
+                        .chunks(16)
+                        .map(|a| {
+                            let mut v6 = [0u8; 16];
+                            v6.copy_from_slice(a);
+                            Ipv6Addr::from(v6)
+                        })
+                        .collect(),
+                )
+            }
+            _ => SvcParam::Unknown(key, value.to_vec()),
+        };
+        Ok(param)
+    }
+
+    /// Parses the presentation format of a parameter (RFC9460 appendix A),
+    /// the value has already had its <character-string> escapes removed.
+    pub(crate) fn from_presentation(key: &str, value: Option<&[u8]>) -> Result<SvcParam> {
+        let invalid = || MessageError::InvalidSvcParams(format!("Invalid value for {}", key));
+        let unknown = |k: &str| MessageError::InvalidSvcParams(format!("Unknown key {}", k));
+
+        let code = parse_key(key).ok_or_else(|| unknown(key))?;
+        let text = || {
+            value
+                .and_then(|v| std::str::from_utf8(v).ok())
+                .filter(|v| !v.is_empty())
+                .ok_or_else(invalid)
+        };
+
+        let param = match code {
+            MANDATORY => {
+                let mut keys = text()?
+                    .split(',')
+                    .map(|k| parse_key(k).ok_or_else(|| unknown(k)))
+                    .collect::<Result<Vec<u16>>>()?;
+                keys.sort_unstable();
+                SvcParam::Mandatory(keys)
+            }
+            ALPN => {
+                // A second level of escaping allows commas within an id.
+                let value = value.filter(|v| !v.is_empty()).ok_or_else(invalid)?;
+                let mut ids = vec![Vec::new()];
+                let mut i = value.iter();
+                while let Some(b) = i.next() {
+                    match b {
+                        b'\\' => ids.last_mut().unwrap().push(*i.next().ok_or_else(invalid)?),
+                        b',' => ids.push(Vec::new()),
+                        b => ids.last_mut().unwrap().push(*b),
+                    }
+                }
+                if ids.iter().any(|id| id.is_empty() || id.len() > 255) {
+                    return Err(invalid());
+                }
+                SvcParam::Alpn(ids)
+            }
+            NO_DEFAULT_ALPN => {
+                if value.is_some() {
+                    return Err(invalid());
+                }
+                SvcParam::NoDefaultAlpn
+            }
+            PORT => SvcParam::Port(text()?.parse().map_err(|_| invalid())?),
+            IPV4HINT => SvcParam::Ipv4Hint(
+                text()?
+                    .split(',')
+                    .map(|a| a.parse().map_err(|_| invalid()))
+                    .collect::<Result<_>>()?,
+            ),
+            ECH => SvcParam::Ech(crate::encoding::base64_decode(text()?).ok_or_else(invalid)?),
+            IPV6HINT => SvcParam::Ipv6Hint(
+                text()?
+                    .split(',')
+                    .map(|a| a.parse().map_err(|_| invalid()))
+                    .collect::<Result<_>>()?,
+            ),
+            _ => SvcParam::Unknown(code, value.unwrap_or_default().to_vec()),
+        };
+        Ok(param)
+    }
+
+    /// Writes the value of the parameter (without the key and length).
+    fn value_to_bytes(&self, buf: &mut dyn Writer) -> Result<()> {
+        match self {
+            SvcParam::Mandatory(keys) => {
+                for k in keys.iter() {
+                    buf.write(&k.to_be_bytes())?;
+                }
+            }
+            SvcParam::Alpn(ids) => {
+                for id in ids.iter() {
+                    if id.is_empty() || id.len() > 255 {
+                        return Err(MessageError::CharacterStringLengthExceeded(id.len()));
+                    }
+                    buf.write(&[id.len() as u8])?;
+                    buf.write(id)?;
+                }
+            }
+            SvcParam::NoDefaultAlpn => {}
+            SvcParam::Port(port) => buf.write(&port.to_be_bytes())?,
+            SvcParam::Ipv4Hint(addrs) => {
+                for a in addrs.iter() {
+                    buf.write(&a.octets())?;
+                }
+            }
+            SvcParam::Ech(config) => buf.write(config)?,
+            SvcParam::Ipv6Hint(addrs) => {
+                for a in addrs.iter() {
+                    buf.write(&a.octets())?;
+                }
+            }
+            SvcParam::Unknown(_, value) => buf.write(value)?,
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SvcParam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        fmt_key(f, self.key())?;
+        match self {
+            SvcParam::Mandatory(keys) => {
+                write!(f, "=")?;
+                for (i, k) in keys.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    fmt_key(f, *k)?;
+                }
+                Ok(())
+            }
+            SvcParam::Alpn(ids) => {
+                write!(f, "=\"")?;
+                for (i, id) in ids.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    // Commas within an id are escaped as they separate the
+                    // list, and the backslash of that escape is escaped again
+                    // for the quoted string.
+                    for b in id.iter() {
+                        match b {
+                            b',' => write!(f, "\\\\,")?,
+                            b'\\' => write!(f, "\\\\\\\\")?,
+                            b'"' => write!(f, "\\\"")?,
+                            0x20..=0x7e => write!(f, "{}", *b as char)?,
+                            _ => write!(f, "\\{:03}", b)?,
+                        }
+                    }
+                }
+                write!(f, "\"")
+            }
+            SvcParam::NoDefaultAlpn => Ok(()),
+            SvcParam::Port(port) => write!(f, "={}", port),
+            SvcParam::Ipv4Hint(addrs) => {
+                write!(f, "=")?;
+                for (i, a) in addrs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", a)?;
+                }
+                Ok(())
+            }
+            SvcParam::Ech(config) => write!(f, "={}", crate::encoding::base64_encode(config)),
+            SvcParam::Ipv6Hint(addrs) => {
+                write!(f, "=")?;
+                for (i, a) in addrs.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", a)?;
+                }
+                Ok(())
+            }
+            SvcParam::Unknown(_, value) => {
+                if value.is_empty() {
+                    return Ok(());
+                }
+                write!(f, "=\"")?;
+                for b in value.iter() {
+                    match b {
+                        b'"' | b'\\' => write!(f, "\\{}", *b as char)?,
+                        0x20..=0x7e => write!(f, "{}", *b as char)?,
+                        _ => write!(f, "\\{:03}", b)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+        }
+    }
+}
+
+/// Reads the presentation name of a SvcParamKey, either its registered name
+/// or `key` followed by the number.
+fn parse_key(s: &str) -> Option<u16> {
+    match s {
+        "mandatory" => Some(MANDATORY),
+        "alpn" => Some(ALPN),
+        "no-default-alpn" => Some(NO_DEFAULT_ALPN),
+        "port" => Some(PORT),
+        "ipv4hint" => Some(IPV4HINT),
+        "ech" => Some(ECH),
+        "ipv6hint" => Some(IPV6HINT),
+        _ => s.strip_prefix("key")?.parse().ok(),
+    }
+}
+
+/// Writes the presentation name of a SvcParamKey.
+fn fmt_key(f: &mut fmt::Formatter, key: u16) -> std::result::Result<(), fmt::Error> {
+    match key {
+        MANDATORY => write!(f, "mandatory"),
+        ALPN => write!(f, "alpn"),
+        NO_DEFAULT_ALPN => write!(f, "no-default-alpn"),
+        PORT => write!(f, "port"),
+        IPV4HINT => write!(f, "ipv4hint"),
+        ECH => write!(f, "ech"),
+        IPV6HINT => write!(f, "ipv6hint"),
+        k => write!(f, "key{}", k),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// The SvcParams of a SVCB or HTTPS record, ordered by their key.
+///
+/// The parameters are validated against the rules of RFC9460 when they are
+/// read from and written to the wire, see [`SvcParams::validate`].
+pub struct SvcParams {
+    params: BTreeMap<u16, SvcParam>,
+}
+
+impl SvcParams {
+    /// Creates an empty set of SvcParams.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds the parameter, returning the parameter it replaced with the same
+    /// key (if any).
+    pub fn insert(&mut self, param: SvcParam) -> Option<SvcParam> {
+        self.params.insert(param.key(), param)
+    }
+
+    /// Removes and returns the parameter for the key.
+    pub fn remove(&mut self, key: u16) -> Option<SvcParam> {
+        self.params.remove(&key)
+    }
+
+    /// The parameter for the key.
+    pub fn get(&self, key: u16) -> Option<&SvcParam> {
+        self.params.get(&key)
+    }
+
+    /// Iterates over the parameters in key order.
+    pub fn iter(&self) -> impl Iterator<Item = &SvcParam> {
+        self.params.values()
+    }
+
+    /// The number of parameters.
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Returns true if there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    /// Checks the parameters are self-consistent as per RFC9460:
+    /// - `mandatory` must not list itself, must list its keys in strictly
+    ///   increasing order, as they are written, and every key it lists must
+    ///   be present.
+    /// - `no-default-alpn` requires `alpn` to be present.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| Err(MessageError::InvalidSvcParams(reason.to_string()));
+
+        if let Some(SvcParam::Mandatory(keys)) = self.get(MANDATORY) {
+            if keys.is_empty() {
+                return invalid("Mandatory must list at least one key");
+            }
+            for (i, k) in keys.iter().enumerate() {
+                if *k == MANDATORY {
+                    return invalid("Mandatory must not list itself");
+                }
+                if keys[..i].contains(k) {
+                    return invalid("Mandatory must not list a key more than once");
+                }
+                if i > 0 && keys[i - 1] > *k {
+                    return invalid("Mandatory keys must be in strictly increasing order");
+                }
+                if self.get(*k).is_none() {
+                    return invalid("Mandatory lists a key that is not present");
+                }
+            }
+        }
+        if self.get(NO_DEFAULT_ALPN).is_some() && self.get(ALPN).is_none() {
+            return invalid("No-default-alpn requires alpn");
+        }
+        Ok(())
+    }
+
+    /// Reads the SvcParams from the remainder of the rdata, the keys must be
+    /// in strictly increasing order.
+    pub(crate) fn from_bytes(input: &[u8]) -> Result<SvcParams> {
+        let truncated = || MessageError::InvalidSvcParams("Truncated SvcParams".to_string());
+
+        let mut params = SvcParams::new();
+        let mut last_key = None;
+        let mut i = input;
+        while !i.is_empty() {
+            if i.len() < 4 {
+                return Err(truncated());
+            }
+            let key = u16::from_be_bytes([i[0], i[1]]);
+            let len = u16::from_be_bytes([i[2], i[3]]) as usize;
+            if i.len() < 4 + len {
+                return Err(truncated());
+            }
+            if last_key.is_some_and(|last| key <= last) {
+                return Err(MessageError::InvalidSvcParams(
+                    "SvcParamKeys must be in strictly increasing order".to_string(),
+                ));
+            }
+            last_key = Some(key);
+            params.insert(SvcParam::from_bytes(key, &i[4..4 + len])?);
+            i = &i[4 + len..];
+        }
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Writes the SvcParams in key order, returning the number of bytes
+    /// written.
+    pub(crate) fn to_bytes(&self, buf: &mut dyn Writer) -> Result<usize> {
+        self.validate()?;
+
+        let start = buf.len();
+        for param in self.iter() {
+            buf.write(&param.key().to_be_bytes())?;
+            let len_pos = buf.len();
+            buf.write(&[0, 0])?;
+            param.value_to_bytes(buf)?;
+            let len = buf.len() - len_pos - 2;
+            if len > u16::MAX as usize {
+                return Err(MessageError::InvalidSvcParams(format!(
+                    "Value for key{} is too long",
+                    param.key()
+                )));
+            }
+            buf.write_at(len_pos, &(len as u16).to_be_bytes());
+        }
+        Ok(buf.len() - start)
+    }
+}
+
+impl fmt::Display for SvcParams {
+    /// Writes the space separated presentation format of the parameters.
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        for (i, param) in self.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", param)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::iter::FromIterator<SvcParam> for SvcParams {
+    fn from_iter<I: IntoIterator<Item = SvcParam>>(iter: I) -> Self {
+        let mut params = SvcParams::new();
+        for p in iter {
+            params.insert(p);
+        }
+        params
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let params: SvcParams = vec![
+            SvcParam::Ipv6Hint(vec!["2001:db8::1".parse().unwrap()]),
+            SvcParam::Alpn(vec![b"h2".to_vec(), b"h3".to_vec()]),
+            SvcParam::Mandatory(vec![ALPN, PORT]),
+            SvcParam::Port(8443),
+            SvcParam::Ipv4Hint(vec![Ipv4Addr::new(192, 0, 2, 1)]),
+            SvcParam::Ech(vec![1, 2, 3]),
+            SvcParam::NoDefaultAlpn,
+            SvcParam::Unknown(65000, b"x\"y".to_vec()),
+        ]
+        .into_iter()
+        .collect();
+
+        let mut buf = Vec::new();
+        let len = params.to_bytes(&mut buf).unwrap();
+        assert_eq!(len, buf.len());
+        // Written in key order.
+        assert_eq!(&buf[0..4], &[0, 0, 0, 4]);
+
+        let params2 = SvcParams::from_bytes(&buf).unwrap();
+        assert_eq!(params, params2);
+        assert_eq!(
+            params2.to_string(),
+            "mandatory=alpn,port alpn=\"h2,h3\" no-default-alpn port=8443 ipv4hint=192.0.2.1 \
+             ech=AQID ipv6hint=2001:db8::1 key65000=\"x\\\"y\""
+        );
+    }
+
+    #[test]
+    fn test_wire_ordering() {
+        // port then alpn.
+        let input = &[0, 3, 0, 2, 1, 187, 0, 1, 0, 3, 2, b'h', b'2'];
+        assert!(SvcParams::from_bytes(input).is_err());
+
+        // Duplicate keys.
+        let input = &[0, 3, 0, 2, 1, 187, 0, 3, 0, 2, 1, 187];
+        assert!(SvcParams::from_bytes(input).is_err());
+
+        // Mandatory keys out of order.
+        let input = &[
+            0, 0, 0, 4, 0, 3, 0, 1, 0, 1, 0, 3, 2, b'h', b'2', 0, 3, 0, 2, 1, 187,
+        ];
+        assert!(SvcParams::from_bytes(input).is_err());
+    }
+
+    #[test]
+    fn test_mandatory_rules() {
+        let mut params = SvcParams::new();
+        params.insert(SvcParam::Mandatory(vec![PORT]));
+        assert!(params.validate().is_err());
+
+        params.insert(SvcParam::Port(53));
+        assert!(params.validate().is_ok());
+
+        params.insert(SvcParam::Mandatory(vec![PORT, MANDATORY]));
+        assert!(params.validate().is_err());
+
+        params.insert(SvcParam::Mandatory(vec![PORT, PORT]));
+        assert!(params.validate().is_err());
+
+        // The keys are written as listed, and must be read back in order.
+        params.insert(SvcParam::Alpn(vec![b"h2".to_vec()]));
+        params.insert(SvcParam::Mandatory(vec![ALPN, PORT]));
+        assert!(params.validate().is_ok());
+        params.insert(SvcParam::Mandatory(vec![PORT, ALPN]));
+        assert!(params.validate().is_err());
+        assert!(params.to_bytes(&mut Vec::new()).is_err());
+
+        let mut params = SvcParams::new();
+        params.insert(SvcParam::NoDefaultAlpn);
+        assert!(params.validate().is_err());
+        assert!(params.to_bytes(&mut Vec::new()).is_err());
+    }
+}
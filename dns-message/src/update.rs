@@ -0,0 +1,390 @@
+use crate::{
+    Class, DomainName, Message, MessageError, OpCode, Question, RData, ResourceRecord, Result, Type,
+};
+use std::fmt;
+use tracing::instrument;
+
+#[derive(Debug, Clone, Copy)]
+/// RFC2136 - A dynamic update, viewed over the [`Message`] that holds it.
+///
+/// An UPDATE uses the same four sections as any other message, but they hold
+/// the Zone, Prerequisite, Update and Additional Data sections. The zone is
+/// the single question, and the prerequisites and updates are the answers and
+/// name servers, each encoded in the class, TTL and RDATA of a record.
+///
+/// Build an update with the [`UpdateBuilder`](crate::UpdateBuilder).
+pub struct UpdateMessage<'a> {
+    message: &'a Message,
+}
+
+impl<'a> UpdateMessage<'a> {
+    /// Views the message as an update, it must have the UPDATE [`OpCode`] and
+    /// a single zone of type SOA.
+    pub fn new(message: &'a Message) -> Result<Self> {
+        if message.header.opcode != OpCode::Update {
+            return Err(MessageError::InvalidUpdate(format!(
+                "OpCode is {}",
+                message.header.opcode
+            )));
+        }
+        if message.questions.len() != 1 {
+            return Err(MessageError::InvalidUpdate(format!(
+                "{} zones",
+                message.questions.len()
+            )));
+        }
+        if message.questions[0].q_type != Type::SOA {
+            return Err(MessageError::InvalidUpdate(format!(
+                "Zone type is {}",
+                message.questions[0].q_type
+            )));
+        }
+        Ok(UpdateMessage { message })
+    }
+
+    /// The message being viewed.
+    pub fn message(&self) -> &'a Message {
+        self.message
+    }
+
+    /// The zone being updated, the ZNAME, ZTYPE and ZCLASS of the Zone
+    /// Section.
+    pub fn zone(&self) -> &'a Question {
+        &self.message.questions[0]
+    }
+
+    /// The prerequisites that must hold for the updates to be made, decoded
+    /// from the Prerequisite Section.
+    pub fn prerequisites(&self) -> Result<Vec<Prerequisite>> {
+        let class = self.zone().q_class;
+        self.message
+            .answers
+            .iter()
+            .map(|record| Prerequisite::from_record(record, class))
+            .collect()
+    }
+
+    /// The updates to make to the zone, decoded from the Update Section.
+    pub fn updates(&self) -> Result<Vec<Update>> {
+        let class = self.zone().q_class;
+        self.message
+            .name_servers
+            .iter()
+            .map(|record| Update::from_record(record, class))
+            .collect()
+    }
+
+    /// The records of the Additional Data Section, which relate to the
+    /// updates or are needed to make them.
+    pub fn additional_records(&self) -> &'a [ResourceRecord] {
+        &self.message.additional_records
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// RFC2136 - A prerequisite of an update, a condition on the contents of the
+/// zone that must hold before any of the updates are made.
+pub enum Prerequisite {
+    /// RRset exists (value independent) - at least one record of the type
+    /// exists at the name.
+    ///
+    /// Encoded with a class of ANY, a TTL of 0 and no RDATA.
+    RRsetExists(DomainName, Type),
+
+    /// RRset exists (value dependent) - a record with the RDATA exists at the
+    /// name. Together the records of the same name and type must match the
+    /// whole RRset in the zone.
+    ///
+    /// Encoded with the class of the zone and a TTL of 0.
+    RecordExists(DomainName, RData),
+
+    /// RRset does not exist - no records of the type exist at the name.
+    ///
+    /// Encoded with a class of NONE, a TTL of 0 and no RDATA.
+    RRsetDoesNotExist(DomainName, Type),
+
+    /// Name is in use - at least one record of any type exists at the name.
+    ///
+    /// Encoded with a class and type of ANY, a TTL of 0 and no RDATA.
+    NameInUse(DomainName),
+
+    /// Name is not in use - no records of any type exist at the name.
+    ///
+    /// Encoded with a class of NONE, a type of ANY, a TTL of 0 and no RDATA.
+    NameNotInUse(DomainName),
+}
+
+impl Prerequisite {
+    /// Encodes the prerequisite as a record for a zone of the class.
+    pub(crate) fn to_record(&self, zone_class: Class) -> ResourceRecord {
+        let (name, class, data) = match self {
+            Prerequisite::RRsetExists(name, rtype) => (name, Class::STAR, empty(*rtype)),
+            Prerequisite::RecordExists(name, data) => (name, zone_class, data.clone()),
+            Prerequisite::RRsetDoesNotExist(name, rtype) => (name, Class::NONE, empty(*rtype)),
+            Prerequisite::NameInUse(name) => (name, Class::STAR, empty(Type::STAR)),
+            Prerequisite::NameNotInUse(name) => (name, Class::NONE, empty(Type::STAR)),
+        };
+        ResourceRecord {
+            name: name.clone(),
+            data,
+            class,
+            ttl: 0,
+        }
+    }
+
+    /// Decodes the prerequisite from a record for a zone of the class.
+    #[instrument(skip(record))]
+    pub(crate) fn from_record(record: &ResourceRecord, zone_class: Class) -> Result<Self> {
+        if record.ttl != 0 {
+            return Err(invalid("Prerequisite", record, "has a TTL"));
+        }
+        let name = record.name.clone();
+        let rtype = Type::from(record.data.as_u16());
+        let prerequisite = match record.class {
+            Class::STAR | Class::NONE if !is_empty(&record.data) => {
+                return Err(invalid("Prerequisite", record, "has RDATA"));
+            }
+            Class::STAR if rtype == Type::STAR => Prerequisite::NameInUse(name),
+            Class::STAR => Prerequisite::RRsetExists(name, rtype),
+            Class::NONE if rtype == Type::STAR => Prerequisite::NameNotInUse(name),
+            Class::NONE => Prerequisite::RRsetDoesNotExist(name, rtype),
+            class if class == zone_class => Prerequisite::RecordExists(name, record.data.clone()),
+            _ => return Err(invalid("Prerequisite", record, "has the wrong class")),
+        };
+        Ok(prerequisite)
+    }
+}
+
+impl fmt::Display for Prerequisite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            Prerequisite::RRsetExists(name, rtype) => write!(f, "{} {} exists", name, rtype),
+            Prerequisite::RecordExists(name, data) => write!(f, "{} {} exists", name, data),
+            Prerequisite::RRsetDoesNotExist(name, rtype) => {
+                write!(f, "{} {} does not exist", name, rtype)
+            }
+            Prerequisite::NameInUse(name) => write!(f, "{} is in use", name),
+            Prerequisite::NameNotInUse(name) => write!(f, "{} is not in use", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// RFC2136 - An update to the zone.
+pub enum Update {
+    /// Add to an RRset - the record is added, unless it's already there.
+    ///
+    /// Encoded with the class of the zone, the class of the record is
+    /// ignored.
+    Add(ResourceRecord),
+
+    /// Delete an RRset - all records of the type are removed from the name.
+    ///
+    /// Encoded with a class of ANY, a TTL of 0 and no RDATA.
+    DeleteRRset(DomainName, Type),
+
+    /// Delete all RRsets from a name - all records of any type are removed
+    /// from the name.
+    ///
+    /// Encoded with a class and type of ANY, a TTL of 0 and no RDATA.
+    DeleteAllRRsets(DomainName),
+
+    /// Delete an RR from an RRset - the record with the RDATA is removed from
+    /// the name.
+    ///
+    /// Encoded with a class of NONE and a TTL of 0.
+    Delete(DomainName, RData),
+}
+
+impl Update {
+    /// Encodes the update as a record for a zone of the class.
+    pub(crate) fn to_record(&self, zone_class: Class) -> ResourceRecord {
+        let (name, class, ttl, data) = match self {
+            Update::Add(record) => (&record.name, zone_class, record.ttl, record.data.clone()),
+            Update::DeleteRRset(name, rtype) => (name, Class::STAR, 0, empty(*rtype)),
+            Update::DeleteAllRRsets(name) => (name, Class::STAR, 0, empty(Type::STAR)),
+            Update::Delete(name, data) => (name, Class::NONE, 0, data.clone()),
+        };
+        ResourceRecord {
+            name: name.clone(),
+            data,
+            class,
+            ttl,
+        }
+    }
+
+    /// Decodes the update from a record for a zone of the class.
+    #[instrument(skip(record))]
+    pub(crate) fn from_record(record: &ResourceRecord, zone_class: Class) -> Result<Self> {
+        let name = record.name.clone();
+        let rtype = Type::from(record.data.as_u16());
+        let meta = matches!(rtype, Type::AXFR | Type::MAILA | Type::MAILB);
+        if meta || (rtype == Type::STAR && record.class != Class::STAR) {
+            return Err(invalid("Update", record, "has a meta type"));
+        }
+        let update = match record.class {
+            Class::STAR | Class::NONE if record.ttl != 0 => {
+                return Err(invalid("Update", record, "has a TTL"));
+            }
+            Class::STAR if !is_empty(&record.data) => {
+                return Err(invalid("Update", record, "has RDATA"));
+            }
+            Class::STAR if rtype == Type::STAR => Update::DeleteAllRRsets(name),
+            Class::STAR => Update::DeleteRRset(name, rtype),
+            Class::NONE => Update::Delete(name, record.data.clone()),
+            class if class == zone_class => Update::Add(ResourceRecord {
+                name,
+                data: record.data.clone(),
+                class,
+                ttl: record.ttl,
+            }),
+            _ => return Err(invalid("Update", record, "has the wrong class")),
+        };
+        Ok(update)
+    }
+}
+
+impl fmt::Display for Update {
+    fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), fmt::Error> {
+        match self {
+            Update::Add(record) => write!(f, "add {}", record),
+            Update::DeleteRRset(name, rtype) => write!(f, "delete {} {}", name, rtype),
+            Update::DeleteAllRRsets(name) => write!(f, "delete {}", name),
+            Update::Delete(name, data) => write!(f, "delete {} {}", name, data),
+        }
+    }
+}
+
+/// The empty RDATA of a prerequisite or update that only has a type.
+fn empty(rtype: Type) -> RData {
+    RData::Raw(rtype.into(), Vec::new())
+}
+
+fn is_empty(data: &RData) -> bool {
+    matches!(data, RData::Raw(_, rdata) if rdata.is_empty())
+}
+
+fn invalid(what: &str, record: &ResourceRecord, reason: &str) -> MessageError {
+    MessageError::InvalidUpdate(format!("{} for {} {}", what, record.name, reason))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test::setup, MessageRef, UpdateBuilder};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_update_round_trip() {
+        setup();
+
+        let a = RData::A(Ipv4Addr::new(192, 0, 2, 10));
+        let prerequisites = vec![
+            Prerequisite::RRsetExists(DomainName::from("www.example.com"), Type::A),
+            Prerequisite::RecordExists(DomainName::from("www.example.com"), a.clone()),
+            Prerequisite::RRsetDoesNotExist(DomainName::from("host.example.com"), Type::AAAA),
+            Prerequisite::NameInUse(DomainName::from("www.example.com")),
+            Prerequisite::NameNotInUse(DomainName::from("host.example.com")),
+        ];
+        let updates = vec![
+            Update::Add(ResourceRecord {
+                name: DomainName::from("host.example.com"),
+                data: a.clone(),
+                class: Class::IN,
+                ttl: 300,
+            }),
+            Update::DeleteRRset(DomainName::from("old.example.com"), Type::TXT),
+            Update::DeleteAllRRsets(DomainName::from("gone.example.com")),
+            Update::Delete(DomainName::from("www.example.com"), a),
+        ];
+
+        let mut builder = UpdateBuilder::new("example.com").id(99);
+        for p in prerequisites.iter() {
+            builder = builder.prerequisite(p.clone());
+        }
+        for u in updates.iter() {
+            builder = builder.update(u.clone());
+        }
+        let message = builder.build();
+        assert_eq!(message.header.opcode, OpCode::Update);
+
+        let mut buf = Vec::new();
+        message.to_bytes(&mut buf).unwrap();
+        let parsed = Message::from_bytes(&buf).unwrap();
+        assert_eq!(parsed, message);
+        let lazy = MessageRef::from_bytes(&buf).unwrap();
+        assert_eq!(lazy.to_message().unwrap(), message);
+        assert_eq!(
+            lazy.answers().next().unwrap().data().unwrap(),
+            RData::Raw(1, Vec::new())
+        );
+
+        let update = UpdateMessage::new(&parsed).unwrap();
+        assert_eq!(update.zone().q_name, "example.com");
+        assert_eq!(update.zone().q_class, Class::IN);
+        assert_eq!(update.prerequisites().unwrap(), prerequisites);
+        assert_eq!(update.updates().unwrap(), updates);
+        assert!(update.additional_records().is_empty());
+
+        // The records are encoded with the class, TTL and RDATA RFC2136 gives
+        // them.
+        let records: Vec<String> = parsed
+            .answers
+            .iter()
+            .chain(parsed.name_servers.iter())
+            .map(|r| r.to_string())
+            .collect();
+        assert_eq!(
+            records,
+            vec![
+                "www.example.com. 0 ANY A \\# 0",
+                "www.example.com. 0 IN A 192.0.2.10",
+                "host.example.com. 0 NONE AAAA \\# 0",
+                "www.example.com. 0 ANY * \\# 0",
+                "host.example.com. 0 NONE * \\# 0",
+                "host.example.com. 300 IN A 192.0.2.10",
+                "old.example.com. 0 ANY TXT \\# 0",
+                "gone.example.com. 0 ANY * \\# 0",
+                "www.example.com. 0 NONE A 192.0.2.10",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_update() {
+        setup();
+
+        let message = Message::from_bytes(&[0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        assert!(matches!(
+            UpdateMessage::new(&message),
+            Err(MessageError::InvalidUpdate(_))
+        ));
+
+        let record = |class, ttl, data| ResourceRecord {
+            name: DomainName::from("www.example.com"),
+            data,
+            class,
+            ttl,
+        };
+        let a = RData::A(Ipv4Addr::new(192, 0, 2, 10));
+
+        // Prerequisites never have a TTL, and only have RDATA in the class of
+        // the zone.
+        assert!(Prerequisite::from_record(&record(Class::IN, 60, a.clone()), Class::IN).is_err());
+        assert!(Prerequisite::from_record(&record(Class::STAR, 0, a.clone()), Class::IN).is_err());
+        assert!(Prerequisite::from_record(&record(Class::CH, 0, a.clone()), Class::IN).is_err());
+
+        // Deletes have no TTL, an RRset is deleted without RDATA, and meta
+        // types can't be added.
+        assert!(Update::from_record(&record(Class::NONE, 60, a.clone()), Class::IN).is_err());
+        assert!(Update::from_record(&record(Class::STAR, 0, a), Class::IN).is_err());
+        assert!(Update::from_record(&record(Class::IN, 60, empty(Type::STAR)), Class::IN).is_err());
+        assert!(
+            Update::from_record(&record(Class::STAR, 0, empty(Type::AXFR)), Class::IN).is_err()
+        );
+        assert_eq!(
+            Update::from_record(&record(Class::STAR, 0, empty(Type::STAR)), Class::IN).unwrap(),
+            Update::DeleteAllRRsets(DomainName::from("www.example.com"))
+        );
+    }
+}
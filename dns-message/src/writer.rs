@@ -0,0 +1,160 @@
+use crate::{MessageError, Result};
+use bytes::BytesMut;
+
+/// A buffer that messages are serialized into.
+///
+/// Messages are only ever appended to the buffer, apart from lengths and
+/// counts which are back-patched once they are known. Implementations are
+/// provided for `Vec<u8>`, [`bytes::BytesMut`] and a fixed `&mut [u8]` through
+/// [`SliceWriter`].
+pub trait Writer {
+    /// The number of bytes that have been written.
+    fn len(&self) -> usize;
+
+    /// Returns true if nothing has been written.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends the bytes, failing if there is no room for all of them.
+    fn write(&mut self, bytes: &[u8]) -> Result<()>;
+
+    /// Overwrites bytes that have already been written, starting at `pos`.
+    ///
+    /// This panics if any of the bytes haven't been written yet.
+    fn write_at(&mut self, pos: usize, bytes: &[u8]);
+
+    /// Discards everything written after the first `len` bytes.
+    fn truncate(&mut self, len: usize);
+
+    /// The bytes that have been written.
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl Writer for Vec<u8> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_at(&mut self, pos: usize, bytes: &[u8]) {
+        self[pos..pos + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Writer for BytesMut {
+    fn len(&self) -> usize {
+        BytesMut::len(self)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_at(&mut self, pos: usize, bytes: &[u8]) {
+        self[pos..pos + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        BytesMut::truncate(self, len)
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+#[derive(Debug)]
+/// A [`Writer`] for a fixed size buffer, which fails with a `BufferTooSmall`
+/// error once the buffer is full.
+pub struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    /// Creates a writer that writes from the start of the buffer.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        SliceWriter { buf, len: 0 }
+    }
+
+    /// The number of bytes that can still be written.
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.len
+    }
+
+    /// Consumes the writer, returning the part of the buffer that was written.
+    pub fn into_written(self) -> &'a mut [u8] {
+        &mut self.buf[..self.len]
+    }
+}
+
+impl Writer for SliceWriter<'_> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        if bytes.len() > self.remaining() {
+            return Err(MessageError::BufferTooSmall(self.buf.len()));
+        }
+        self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    fn write_at(&mut self, pos: usize, bytes: &[u8]) {
+        assert!(
+            pos + bytes.len() <= self.len,
+            "write_at past the written bytes"
+        );
+        self.buf[pos..pos + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_slice_writer() {
+        let mut buf = [0u8; 4];
+        let mut writer = SliceWriter::new(&mut buf);
+        writer.write(&[1, 2, 3]).unwrap();
+        writer.write_at(0, &[9]);
+        assert_eq!(writer.as_bytes(), &[9, 2, 3]);
+        assert_eq!(writer.remaining(), 1);
+
+        // Nothing is written if it doesn't all fit.
+        assert!(matches!(
+            writer.write(&[4, 5]),
+            Err(MessageError::BufferTooSmall(4))
+        ));
+        assert_eq!(writer.len(), 3);
+
+        writer.truncate(1);
+        writer.write(&[7]).unwrap();
+        assert_eq!(writer.into_written(), &[9, 7]);
+    }
+}
@@ -0,0 +1,1415 @@
+//! RFC1035 - the master (zone) file format.
+
+use crate::encoding::{base32hex_decode, base64_decode, hex_decode};
+use crate::parser::read_rdata;
+use crate::{
+    Class, DomainName, MessageError, RData, ResourceRecord, Result, SvcParam, SvcParams, Type,
+};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{instrument, trace};
+
+/// Files can only include each other this deep, which stops a file that
+/// includes itself from recursing forever.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// The most records a single `$GENERATE` can add, so that a huge range can't
+/// exhaust memory. BIND caps the range in the same way.
+const MAX_GENERATE: u32 = 65536;
+
+/// RFC1876 - the default LOC size (1m), horizontal precision (10000m) and
+/// vertical precision (10m).
+const LOC_DEFAULT_PRECISIONS: [u8; 3] = [0x12, 0x16, 0x13];
+
+#[derive(Debug, Clone, Default)]
+/// Parses the zone file format of RFC1035 section 5 into [`ResourceRecord`]s.
+///
+/// The `$ORIGIN`, `$TTL`, `$INCLUDE` and BIND's `$GENERATE` directives are
+/// supported, as is the RFC3597 `\#` generic format for the rdata of any type.
+/// Records without an owner, TTL or class take them from the previous record,
+/// and records without a TTL use the `$TTL` default when there is one.
+///
+/// Errors are reported as a `ZoneFile` error with the line and column they
+/// occurred at.
+///
+/// ```
+/// use dns_message::{DomainName, ZoneParser};
+///
+/// let records = ZoneParser::new()
+///     .origin(DomainName::from("example.com"))
+///     .parse_str("$TTL 1h\n@ IN A 192.0.2.1\nwww CNAME @\n")
+///     .unwrap();
+/// assert_eq!(records[1].name, "www.example.com");
+/// assert_eq!(records[1].ttl, 3600);
+/// ```
+pub struct ZoneParser {
+    origin: Option<DomainName>,
+    ttl: Option<u32>,
+}
+
+impl ZoneParser {
+    /// Creates a parser without an origin or a default TTL.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The origin that relative names and `@` are resolved against, until a
+    /// `$ORIGIN` directive changes it.
+    pub fn origin(mut self, origin: DomainName) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// The TTL of records that don't have one, until a `$TTL` directive
+    /// changes it.
+    pub fn ttl(mut self, ttl: u32) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Parses the records of the zone, `$INCLUDE` paths are relative to the
+    /// current directory.
+    #[instrument(skip(self, zone))]
+    pub fn parse_str(&self, zone: &str) -> Result<Vec<ResourceRecord>> {
+        let mut state = State::new(self);
+        state.parse(zone, None, 0)?;
+
+        trace!("Parsed {} records", state.records.len());
+
+        Ok(state.records)
+    }
+
+    /// Reads and parses the zone file, `$INCLUDE` paths are relative to the
+    /// directory of the file that includes them.
+    #[instrument(skip(self, path))]
+    pub fn parse_file<P: AsRef<Path>>(&self, path: P) -> Result<Vec<ResourceRecord>> {
+        let path = path.as_ref();
+        let zone = fs::read_to_string(path)?;
+
+        let mut state = State::new(self);
+        state.parse(&zone, path.parent(), 0)?;
+
+        trace!(
+            "Parsed {} records from {}",
+            state.records.len(),
+            path.display()
+        );
+
+        Ok(state.records)
+    }
+}
+
+/// The values carried from one entry of the zone to the next.
+struct State {
+    origin: Option<DomainName>,
+    default_ttl: Option<u32>,
+    last_ttl: Option<u32>,
+    last_owner: Option<DomainName>,
+    last_class: Option<Class>,
+    records: Vec<ResourceRecord>,
+}
+
+impl State {
+    fn new(parser: &ZoneParser) -> Self {
+        State {
+            origin: parser.origin.clone(),
+            default_ttl: parser.ttl,
+            last_ttl: None,
+            last_owner: None,
+            last_class: None,
+            records: Vec::new(),
+        }
+    }
+
+    /// Parses every entry of a file, `dir` is where included files are
+    /// relative to.
+    fn parse(&mut self, zone: &str, dir: Option<&Path>, depth: usize) -> Result<()> {
+        for entry in tokenize(zone)? {
+            let first = &entry.tokens[0];
+            if entry.blank_owner || first.quoted || !first.text.starts_with('$') {
+                let record = self.parse_record(&entry)?;
+                self.records.push(record);
+                continue;
+            }
+
+            let mut fields = Fields::new(&entry);
+            fields.pos = 1;
+            match first.text.to_ascii_uppercase().as_str() {
+                "$ORIGIN" => {
+                    let origin = self.name(fields.next("origin")?)?;
+                    fields.finish()?;
+                    self.origin = Some(origin);
+                }
+                "$TTL" => {
+                    let ttl = parse_ttl(fields.next("TTL")?)?;
+                    fields.finish()?;
+                    self.default_ttl = Some(ttl);
+                }
+                "$INCLUDE" => self.include(fields, dir, depth)?,
+                "$GENERATE" => self.generate(fields)?,
+                _ => return Err(first.error(format!("Unknown directive {}", first.text))),
+            }
+        }
+        Ok(())
+    }
+
+    /// `$INCLUDE <file-name> [<domain-name>]` - the origin and owner revert
+    /// once the included file has been parsed.
+    fn include(&mut self, mut fields: Fields, dir: Option<&Path>, depth: usize) -> Result<()> {
+        let token = fields.next("file name")?;
+        let origin = match fields.peek() {
+            Some(origin) => {
+                fields.pos += 1;
+                Some(self.name(origin)?)
+            }
+            None => None,
+        };
+        fields.finish()?;
+
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(token.error("Too many nested includes"));
+        }
+        let file = String::from_utf8(unescape(token)?)
+            .map_err(|_| token.error(format!("Invalid file name {}", token.text)))?;
+        let path = match dir {
+            Some(dir) => dir.join(file),
+            None => PathBuf::from(file),
+        };
+        let zone = fs::read_to_string(&path)
+            .map_err(|e| token.error(format!("Unable to read {}: {}", path.display(), e)))?;
+
+        trace!("Including {}", path.display());
+
+        let origin = origin.or_else(|| self.origin.clone());
+        let saved = (
+            std::mem::replace(&mut self.origin, origin),
+            self.last_owner.take(),
+        );
+        let result = self.parse(&zone, path.parent(), depth + 1);
+        (self.origin, self.last_owner) = saved;
+
+        result.map_err(|e| match e {
+            MessageError::ZoneFile(line, column, reason) => {
+                MessageError::ZoneFile(line, column, format!("{}: {}", path.display(), reason))
+            }
+            e => e,
+        })
+    }
+
+    /// `$GENERATE <start>-<stop>[/<step>] <lhs> [<ttl>] [<class>] <type> <rhs>`
+    ///
+    /// A record is added for each value in the range, with `$` in the lhs and
+    /// rhs replaced by the value.
+    fn generate(&mut self, mut fields: Fields) -> Result<()> {
+        let token = fields.next("range")?;
+        let invalid = || token.error(format!("Invalid range {}", token.text));
+        let (range, step): (_, u32) = match token.text.split_once('/') {
+            Some((range, step)) => (range, step.parse().map_err(|_| invalid())?),
+            None => (token.text.as_str(), 1),
+        };
+        let (start, stop) = range.split_once('-').ok_or_else(invalid)?;
+        let start: u32 = start.parse().map_err(|_| invalid())?;
+        let stop: u32 = stop.parse().map_err(|_| invalid())?;
+        if start > stop || step == 0 {
+            return Err(invalid());
+        }
+        if (stop - start) / step >= MAX_GENERATE {
+            return Err(token.error(format!(
+                "Range {} generates more than {} records",
+                token.text, MAX_GENERATE
+            )));
+        }
+
+        let template = fields.rest();
+        if template.is_empty() {
+            return Err(fields.expected("owner name"));
+        }
+        for value in (start..=stop).step_by(step as usize) {
+            let entry = Entry {
+                blank_owner: false,
+                tokens: template
+                    .iter()
+                    .map(|t| substitute(t, value))
+                    .collect::<Result<_>>()?,
+            };
+            let record = self.parse_record(&entry)?;
+            self.records.push(record);
+        }
+        Ok(())
+    }
+
+    /// `<domain-name> [<TTL>] [<class>] <type> <RDATA>`, where the TTL and
+    /// class may be in either order and the name is omitted when the entry
+    /// starts with a blank.
+    fn parse_record(&mut self, entry: &Entry) -> Result<ResourceRecord> {
+        let mut fields = Fields::new(entry);
+        let name = if entry.blank_owner {
+            self.last_owner
+                .clone()
+                .ok_or_else(|| entry.tokens[0].error("No previous owner name"))?
+        } else {
+            self.name(fields.next("owner name")?)?
+        };
+
+        let mut ttl = None;
+        let mut class = None;
+        while let Some(token) = fields.peek() {
+            if ttl.is_none() && token.text.starts_with(|c: char| c.is_ascii_digit()) {
+                ttl = Some(parse_ttl(token)?);
+            } else if class.is_none() && token.text.parse::<Class>().is_ok() {
+                class = token.text.parse().ok();
+            } else {
+                break;
+            }
+            fields.pos += 1;
+        }
+
+        let token = fields.next("type")?;
+        let rtype = token
+            .text
+            .parse::<Type>()
+            .map_err(|_| token.error(format!("Unknown type {}", token.text)))?;
+        let data = self.parse_rdata(rtype, &mut fields)?;
+        fields.finish()?;
+
+        let ttl = match ttl {
+            Some(ttl) => {
+                self.last_ttl = Some(ttl);
+                ttl
+            }
+            None => match (self.default_ttl.or(self.last_ttl), &data) {
+                (Some(ttl), _) => ttl,
+                // RFC2308 - the SOA minimum was the default before $TTL.
+                (None, RData::SOA(_, _, _, _, _, _, minimum)) => {
+                    self.last_ttl = Some(*minimum);
+                    *minimum
+                }
+                (None, _) => return Err(token.error("No TTL and no $TTL default")),
+            },
+        };
+        let class = class.or(self.last_class).unwrap_or_default();
+
+        self.last_owner = Some(name.clone());
+        self.last_class = Some(class);
+
+        Ok(ResourceRecord {
+            name,
+            data,
+            class,
+            ttl,
+        })
+    }
+
+    /// Parses the rdata of the type from the remaining fields.
+    fn parse_rdata(&self, rtype: Type, fields: &mut Fields) -> Result<RData> {
+        if let Some(token) = fields.peek().filter(|t| !t.quoted && t.text == "\\#") {
+            fields.pos += 1;
+            let len: u16 = fields.parse("rdata length")?;
+            let rdata = if len == 0 {
+                Vec::new()
+            } else {
+                decode_rest(fields, "rdata", hex_decode)?
+            };
+            if rdata.len() != len as usize {
+                return Err(token.error(format!(
+                    "Rdata length is {} but {} octets were given",
+                    len,
+                    rdata.len()
+                )));
+            }
+            return read_rdata(&rdata, rtype, &rdata)
+                .map_err(|e| token.error(format!("Invalid {} rdata: {}", rtype, e)));
+        }
+
+        let data = match rtype {
+            Type::A => RData::A(fields.parse("address")?),
+            Type::NS => RData::NS(self.name(fields.next("name server")?)?),
+            Type::MD => RData::MD(self.name(fields.next("mail destination")?)?),
+            Type::MF => RData::MF(self.name(fields.next("mail forwarder")?)?),
+            Type::CNAME => RData::CNAME(self.name(fields.next("canonical name")?)?),
+            Type::SOA => RData::SOA(
+                self.name(fields.next("primary name server")?)?,
+                self.name(fields.next("mailbox")?)?,
+                fields.parse("serial")?,
+                parse_ttl(fields.next("refresh")?)?,
+                parse_ttl(fields.next("retry")?)?,
+                parse_ttl(fields.next("expire")?)?,
+                parse_ttl(fields.next("minimum")?)?,
+            ),
+            Type::MB => RData::MB(self.name(fields.next("mailbox")?)?),
+            Type::MG => RData::MG(self.name(fields.next("mailbox")?)?),
+            Type::MR => RData::MR(self.name(fields.next("mailbox")?)?),
+            Type::WKS => {
+                let address = fields.parse("address")?;
+                let token = fields.next("protocol")?;
+                let protocol = match token.text.to_ascii_lowercase().as_str() {
+                    "tcp" => 6,
+                    "udp" => 17,
+                    _ => token.parse("protocol")?,
+                };
+                let mut bitmap = Vec::new();
+                for token in fields.rest() {
+                    let port: u16 = token.parse("port")?;
+                    let i = port as usize / 8;
+                    if bitmap.len() <= i {
+                        bitmap.resize(i + 1, 0);
+                    }
+                    bitmap[i] |= 0x80 >> (port % 8);
+                }
+                RData::WKS(address, protocol, bitmap)
+            }
+            Type::PTR => RData::PTR(self.name(fields.next("name")?)?),
+            Type::HINFO => RData::HINFO(
+                character_string(fields.next("CPU")?)?,
+                character_string(fields.next("OS")?)?,
+            ),
+            Type::MINFO => RData::MINFO(
+                self.name(fields.next("responsible mailbox")?)?,
+                self.name(fields.next("error mailbox")?)?,
+            ),
+            Type::MX => RData::MX(
+                fields.parse("preference")?,
+                self.name(fields.next("exchange")?)?,
+            ),
+            Type::TXT | Type::SPF => {
+                let strings = fields
+                    .rest()
+                    .iter()
+                    .map(character_string)
+                    .collect::<Result<Vec<_>>>()?;
+                if strings.is_empty() {
+                    return Err(fields.expected("text"));
+                }
+                if rtype == Type::TXT {
+                    RData::TXT(strings)
+                } else {
+                    RData::SPF(strings)
+                }
+            }
+            Type::AAAA => RData::AAAA(fields.parse("address")?),
+            Type::LOC => parse_loc(fields)?,
+            Type::SRV => RData::SRV(
+                fields.parse("priority")?,
+                fields.parse("weight")?,
+                fields.parse("port")?,
+                self.name(fields.next("target")?)?,
+            ),
+            Type::NAPTR => RData::NAPTR(
+                fields.parse("order")?,
+                fields.parse("preference")?,
+                character_string(fields.next("flags")?)?,
+                character_string(fields.next("services")?)?,
+                character_string(fields.next("regexp")?)?,
+                self.name(fields.next("replacement")?)?,
+            ),
+            Type::DNAME => RData::DNAME(self.name(fields.next("target")?)?),
+            Type::DS | Type::CDS => {
+                let key_tag = fields.parse("key tag")?;
+                let algorithm = fields.parse("algorithm")?;
+                let digest_type = fields.parse("digest type")?;
+                let digest = decode_rest(fields, "digest", hex_decode)?;
+                if rtype == Type::DS {
+                    RData::DS(key_tag, algorithm, digest_type, digest)
+                } else {
+                    RData::CDS(key_tag, algorithm, digest_type, digest)
+                }
+            }
+            Type::SSHFP => RData::SSHFP(
+                fields.parse("algorithm")?,
+                fields.parse("fingerprint type")?,
+                decode_rest(fields, "fingerprint", hex_decode)?,
+            ),
+            Type::RRSIG => {
+                let token = fields.next("type covered")?;
+                let type_covered = token
+                    .text
+                    .parse::<Type>()
+                    .map_err(|_| token.error(format!("Unknown type {}", token.text)))?;
+                RData::RRSIG(
+                    type_covered,
+                    fields.parse("algorithm")?,
+                    fields.parse("labels")?,
+                    parse_ttl(fields.next("original TTL")?)?,
+                    parse_timestamp(fields.next("signature expiration")?)?,
+                    parse_timestamp(fields.next("signature inception")?)?,
+                    fields.parse("key tag")?,
+                    self.name(fields.next("signer's name")?)?,
+                    decode_rest(fields, "signature", base64_decode)?,
+                )
+            }
+            Type::NSEC => RData::NSEC(
+                self.name(fields.next("next domain name")?)?,
+                parse_types(fields.rest())?,
+            ),
+            Type::DNSKEY | Type::CDNSKEY => {
+                let flags = fields.parse("flags")?;
+                let protocol = fields.parse("protocol")?;
+                let algorithm = fields.parse("algorithm")?;
+                let key = decode_rest(fields, "public key", base64_decode)?;
+                if rtype == Type::DNSKEY {
+                    RData::DNSKEY(flags, protocol, algorithm, key)
+                } else {
+                    RData::CDNSKEY(flags, protocol, algorithm, key)
+                }
+            }
+            Type::NSEC3 => {
+                let hash_algorithm = fields.parse("hash algorithm")?;
+                let flags = fields.parse("flags")?;
+                let iterations = fields.parse("iterations")?;
+                let salt = parse_salt(fields.next("salt")?)?;
+                let token = fields.next("next hashed owner name")?;
+                let next = base32hex_decode(&token.text)
+                    .ok_or_else(|| token.error(format!("Invalid base32hex {}", token.text)))?;
+                let types = parse_types(fields.rest())?;
+                RData::NSEC3(hash_algorithm, flags, iterations, salt, next, types)
+            }
+            Type::NSEC3PARAM => RData::NSEC3PARAM(
+                fields.parse("hash algorithm")?,
+                fields.parse("flags")?,
+                fields.parse("iterations")?,
+                parse_salt(fields.next("salt")?)?,
+            ),
+            Type::TLSA => RData::TLSA(
+                fields.parse("certificate usage")?,
+                fields.parse("selector")?,
+                fields.parse("matching type")?,
+                decode_rest(fields, "certificate association data", hex_decode)?,
+            ),
+            Type::SVCB | Type::HTTPS => {
+                let priority = fields.parse("priority")?;
+                let target = self.name(fields.next("target")?)?;
+                let params = parse_svc_params(fields.rest())?;
+                if rtype == Type::SVCB {
+                    RData::SVCB(priority, target, params)
+                } else {
+                    RData::HTTPS(priority, target, params)
+                }
+            }
+            Type::URI => RData::URI(
+                fields.parse("priority")?,
+                fields.parse("weight")?,
+                unescape(fields.next("target")?)?,
+            ),
+            Type::CAA => RData::CAA(
+                fields.parse("flags")?,
+                character_string(fields.next("tag")?)?,
+                unescape(fields.next("value")?)?,
+            ),
+            _ => {
+                return Err(fields.expected(&format!("\\# generic rdata for {}", rtype)));
+            }
+        };
+        Ok(data)
+    }
+
+    /// Resolves a name in the zone, which is relative to the origin unless it
+    /// ends with a dot. `@` is the origin itself.
+    fn name(&self, token: &Token) -> Result<DomainName> {
+        let origin = || {
+            self.origin
+                .clone()
+                .ok_or_else(|| token.error(format!("No origin for {}", token.text)))
+        };
+        if token.text == "@" {
+            return origin();
+        }
+
+        let invalid = |e: MessageError| token.error(format!("Invalid name {}: {}", token.text, e));
+        let name = DomainName::from_str(&token.text).map_err(invalid)?;
+        if is_absolute(&token.text) {
+            return Ok(name);
+        }
+        DomainName::from_labels(name.labels().iter().chain(origin()?.labels())).map_err(invalid)
+    }
+}
+
+#[derive(Debug, Default)]
+/// A single entry of the zone, which may span multiple lines in parentheses.
+struct Entry {
+    /// The entry started with a blank, so the owner is omitted.
+    blank_owner: bool,
+    tokens: Vec<Token>,
+}
+
+#[derive(Debug)]
+/// A field of an entry, quotes are removed but escapes are left for the field
+/// to interpret.
+struct Token {
+    text: String,
+    quoted: bool,
+    line: usize,
+    column: usize,
+    end: usize,
+}
+
+impl Token {
+    fn new(line: usize, column: usize) -> Self {
+        Token {
+            text: String::new(),
+            quoted: false,
+            line,
+            column,
+            end: column,
+        }
+    }
+
+    fn error<S: Into<String>>(&self, reason: S) -> MessageError {
+        MessageError::ZoneFile(self.line, self.column, reason.into())
+    }
+
+    fn parse<T: FromStr>(&self, what: &str) -> Result<T> {
+        self.text
+            .parse()
+            .map_err(|_| self.error(format!("Invalid {} {}", what, self.text)))
+    }
+}
+
+/// The fields of an entry, which are consumed from the front.
+struct Fields<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(entry: &'a Entry) -> Self {
+        Fields {
+            tokens: &entry.tokens,
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self, what: &str) -> Result<&'a Token> {
+        let token = self.peek().ok_or_else(|| self.expected(what))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn parse<T: FromStr>(&mut self, what: &str) -> Result<T> {
+        self.next(what)?.parse(what)
+    }
+
+    /// Consumes all the remaining fields.
+    fn rest(&mut self) -> &'a [Token] {
+        let rest = &self.tokens[self.pos..];
+        self.pos = self.tokens.len();
+        rest
+    }
+
+    /// An error just after the last field, for when a field is missing.
+    fn expected(&self, what: &str) -> MessageError {
+        let last = &self.tokens[self.tokens.len() - 1];
+        MessageError::ZoneFile(last.line, last.end + 1, format!("Expected {}", what))
+    }
+
+    /// Checks there aren't any fields left over.
+    fn finish(&self) -> Result<()> {
+        match self.peek() {
+            Some(token) => Err(token.error(format!("Unexpected {}", token.text))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Splits the zone into entries of tokens, removing comments and joining the
+/// lines within parentheses.
+fn tokenize(zone: &str) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut entry = Entry::default();
+    let mut token: Option<Token> = None;
+    let mut parens = Vec::new();
+    let (mut line, mut column) = (1, 0);
+
+    let mut chars = zone.chars().peekable();
+    while let Some(c) = chars.next() {
+        column += 1;
+        if column == 1 && parens.is_empty() && (c == ' ' || c == '\t') {
+            entry.blank_owner = true;
+        }
+        match c {
+            '\n' | ' ' | '\t' | '\r' | ';' | '(' | ')' => {
+                entry.tokens.extend(token.take());
+                match c {
+                    '\n' => {
+                        if parens.is_empty() && !entry.tokens.is_empty() {
+                            entries.push(std::mem::take(&mut entry));
+                        }
+                        entry.blank_owner &= !parens.is_empty();
+                        line += 1;
+                        column = 0;
+                    }
+                    ';' => {
+                        while chars.next_if(|c| *c != '\n').is_some() {
+                            column += 1;
+                        }
+                    }
+                    '(' => parens.push((line, column)),
+                    ')' if parens.pop().is_none() => {
+                        return Err(MessageError::ZoneFile(
+                            line,
+                            column,
+                            "Unbalanced parenthesis".to_string(),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            '"' => {
+                let t = token.get_or_insert_with(|| Token::new(line, column));
+                t.quoted = true;
+                let (start_line, start_column) = (line, column);
+                loop {
+                    let c = chars.next().ok_or_else(|| {
+                        MessageError::ZoneFile(
+                            start_line,
+                            start_column,
+                            "Unterminated string".to_string(),
+                        )
+                    })?;
+                    column += 1;
+                    match c {
+                        '"' => break,
+                        '\n' => {
+                            line += 1;
+                            column = 0;
+                        }
+                        '\\' => {
+                            if let Some(escaped) = chars.next_if(|c| *c != '\n') {
+                                column += 1;
+                                t.text.push(c);
+                                t.text.push(escaped);
+                                continue;
+                            }
+                        }
+                        _ => {}
+                    }
+                    t.text.push(c);
+                }
+                t.end = column;
+            }
+            '\\' => {
+                let t = token.get_or_insert_with(|| Token::new(line, column));
+                t.text.push(c);
+                let c = chars.next_if(|c| *c != '\n').ok_or_else(|| {
+                    MessageError::ZoneFile(line, column, "Trailing backslash".to_string())
+                })?;
+                column += 1;
+                t.text.push(c);
+                t.end = column;
+            }
+            c => {
+                let t = token.get_or_insert_with(|| Token::new(line, column));
+                t.text.push(c);
+                t.end = column;
+            }
+        }
+    }
+
+    entry.tokens.extend(token.take());
+    if let Some((line, column)) = parens.pop() {
+        return Err(MessageError::ZoneFile(
+            line,
+            column,
+            "Unbalanced parenthesis".to_string(),
+        ));
+    }
+    if !entry.tokens.is_empty() {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// A name is absolute when it ends with a dot that isn't escaped.
+fn is_absolute(name: &str) -> bool {
+    match name.strip_suffix('.') {
+        Some(rest) => rest.chars().rev().take_while(|c| *c == '\\').count() % 2 == 0,
+        None => false,
+    }
+}
+
+/// Removes the `\X` and `\DDD` escapes from a field.
+fn unescape(token: &Token) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(token.text.len());
+    let mut bytes = token.text.bytes();
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            out.push(b);
+            continue;
+        }
+        // The tokenizer never leaves a trailing backslash.
+        let c = bytes.next().unwrap_or(b'\\');
+        if !c.is_ascii_digit() {
+            out.push(c);
+            continue;
+        }
+        let digits = [Some(c), bytes.next(), bytes.next()];
+        let value = digits.iter().try_fold(0u16, |acc, d| match d {
+            Some(d) if d.is_ascii_digit() => Some(acc * 10 + (d - b'0') as u16),
+            _ => None,
+        });
+        match value {
+            Some(value) if value <= 255 => out.push(value as u8),
+            _ => return Err(token.error(format!("Invalid escape in {}", token.text))),
+        }
+    }
+    Ok(out)
+}
+
+/// A <character-string>, which is limited to 255 octets.
+fn character_string(token: &Token) -> Result<Vec<u8>> {
+    let s = unescape(token)?;
+    if s.len() > 255 {
+        return Err(token.error(format!(
+            "Character string is {} octets, the limit is 255",
+            s.len()
+        )));
+    }
+    Ok(s)
+}
+
+/// Decodes the remaining fields as one value, as base64 and hex may be split
+/// by whitespace.
+fn decode_rest<F>(fields: &mut Fields, what: &str, decode: F) -> Result<Vec<u8>>
+where
+    F: Fn(&str) -> Option<Vec<u8>>,
+{
+    let rest = fields.rest();
+    if rest.is_empty() {
+        return Err(fields.expected(what));
+    }
+    let text: String = rest.iter().map(|t| t.text.as_str()).collect();
+    decode(&text).ok_or_else(|| rest[0].error(format!("Invalid {}", what)))
+}
+
+/// An NSEC3 salt in hex, or `-` for no salt.
+fn parse_salt(token: &Token) -> Result<Vec<u8>> {
+    if token.text == "-" {
+        return Ok(Vec::new());
+    }
+    hex_decode(&token.text).ok_or_else(|| token.error(format!("Invalid salt {}", token.text)))
+}
+
+/// A TTL as either seconds, or a sequence of values with the units `w`, `d`,
+/// `h`, `m` and `s`, e.g. `1h30m`.
+fn parse_ttl(token: &Token) -> Result<u32> {
+    let invalid = || token.error(format!("Invalid TTL {}", token.text));
+
+    let mut total = 0u64;
+    let mut value: Option<u64> = None;
+    for c in token.text.chars() {
+        if let Some(d) = c.to_digit(10) {
+            let v = value.unwrap_or(0) * 10 + d as u64;
+            if v > u32::MAX as u64 {
+                return Err(invalid());
+            }
+            value = Some(v);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            'w' => 604_800,
+            'd' => 86_400,
+            'h' => 3_600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        // The value is at most u32::MAX, so neither of these can overflow
+        // before the total is checked.
+        total += value.take().ok_or_else(invalid)? * unit;
+        if total > u32::MAX as u64 {
+            return Err(invalid());
+        }
+    }
+    if total == 0 && value.is_none() {
+        return Err(invalid());
+    }
+    u32::try_from(total + value.unwrap_or(0)).map_err(|_| invalid())
+}
+
+/// An RRSIG time as either YYYYMMDDHHmmSS in UTC or seconds since the epoch,
+/// the time is modulo 2^32 as per RFC4034 section 3.1.5.
+fn parse_timestamp(token: &Token) -> Result<u32> {
+    let t = &token.text;
+    if t.len() != 14 || !t.bytes().all(|b| b.is_ascii_digit()) {
+        return token.parse("timestamp");
+    }
+
+    let field = |start: usize, end: usize| t[start..end].parse::<i64>().unwrap_or_default();
+    let (year, month, day) = (field(0, 4), field(4, 6), field(6, 8));
+    let (hour, minute, second) = (field(8, 10), field(10, 12), field(12, 14));
+    if year < 1970
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err(token.error(format!("Invalid timestamp {}", t)));
+    }
+    let seconds = days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second;
+    Ok(seconds as u32)
+}
+
+/// Converts a proleptic Gregorian date to days since 1970-01-01.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// The types of an NSEC or NSEC3 type bit map.
+fn parse_types(tokens: &[Token]) -> Result<Vec<Type>> {
+    tokens
+        .iter()
+        .map(|t| {
+            t.text
+                .parse::<Type>()
+                .map_err(|_| t.error(format!("Unknown type {}", t.text)))
+        })
+        .collect()
+}
+
+/// The `key[=value]` SvcParams of a SVCB or HTTPS record.
+fn parse_svc_params(tokens: &[Token]) -> Result<SvcParams> {
+    let mut params = SvcParams::new();
+    for token in tokens {
+        let (key, value) = match token.text.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (token.text.as_str(), None),
+        };
+        let value = match value {
+            Some(value) => Some(unescape(&Token {
+                text: value.to_string(),
+                ..Token::new(token.line, token.column)
+            })?),
+            None => None,
+        };
+        let param = SvcParam::from_presentation(key, value.as_deref())
+            .map_err(|e| token.error(e.to_string()))?;
+        if params.insert(param).is_some() {
+            return Err(token.error(format!("Duplicate key {}", key)));
+        }
+    }
+    if let Some(token) = tokens.first() {
+        params.validate().map_err(|e| token.error(e.to_string()))?;
+    }
+    Ok(params)
+}
+
+/// RFC1876 - `d1 [m1 [s1]] {"N"|"S"} d2 [m2 [s2]] {"E"|"W"} alt["m"]
+/// [siz["m"] [hp["m"] [vp["m"]]]]`.
+fn parse_loc(fields: &mut Fields) -> Result<RData> {
+    let latitude = parse_loc_coordinate(fields, 90, "N", "S")?;
+    let longitude = parse_loc_coordinate(fields, 180, "E", "W")?;
+
+    let token = fields.next("altitude")?;
+    let altitude = parse_meters(&token.text)
+        .map(|cm| cm + 10_000_000)
+        .and_then(|cm| u32::try_from(cm).ok())
+        .ok_or_else(|| token.error(format!("Invalid altitude {}", token.text)))?;
+
+    let mut precisions = LOC_DEFAULT_PRECISIONS;
+    for precision in precisions.iter_mut() {
+        let token = match fields.peek() {
+            Some(token) => token,
+            None => break,
+        };
+        fields.pos += 1;
+        let cm = parse_meters(&token.text)
+            .filter(|cm| (0..=9_000_000_000).contains(cm))
+            .ok_or_else(|| token.error(format!("Invalid precision {}", token.text)))?;
+        let mut exponent = 0;
+        while exponent < 9 && cm >= 10i64.pow(exponent + 1) {
+            exponent += 1;
+        }
+        *precision = ((cm / 10i64.pow(exponent)) as u8) << 4 | exponent as u8;
+    }
+
+    Ok(RData::LOC(
+        0,
+        precisions[0],
+        precisions[1],
+        precisions[2],
+        latitude,
+        longitude,
+        altitude,
+    ))
+}
+
+/// Degrees, optional minutes and seconds and then the direction, encoded as
+/// thousandths of a second of arc offset from 2^31.
+fn parse_loc_coordinate(
+    fields: &mut Fields,
+    max_degrees: u32,
+    positive: &str,
+    negative: &str,
+) -> Result<u32> {
+    let token = fields.next("degrees")?;
+    let degrees: u32 = token.parse("degrees")?;
+    let mut minutes: u32 = 0;
+    let mut millis: u32 = 0;
+
+    let mut token = fields.next(&format!("{} or {}", positive, negative))?;
+    if token.text.starts_with(|c: char| c.is_ascii_digit()) {
+        minutes = token.parse("minutes")?;
+        token = fields.next(&format!("{} or {}", positive, negative))?;
+        if token.text.starts_with(|c: char| c.is_ascii_digit()) {
+            millis = parse_decimal(&token.text, 3)
+                .and_then(|s| u32::try_from(s).ok())
+                .ok_or_else(|| token.error(format!("Invalid seconds {}", token.text)))?;
+            token = fields.next(&format!("{} or {}", positive, negative))?;
+        }
+    }
+
+    let value = (degrees as u64 * 60 + minutes as u64) * 60_000 + millis as u64;
+    if minutes > 59 || millis > 59_999 || value > max_degrees as u64 * 3_600_000 {
+        return Err(token.error("Coordinate out of range"));
+    }
+    let value = value as u32;
+    if token.text.eq_ignore_ascii_case(positive) {
+        Ok((1 << 31) + value)
+    } else if token.text.eq_ignore_ascii_case(negative) {
+        Ok((1 << 31) - value)
+    } else {
+        Err(token.error(format!("Expected {} or {}", positive, negative)))
+    }
+}
+
+/// A distance in meters with an optional `m` suffix, in centimeters.
+fn parse_meters(s: &str) -> Option<i64> {
+    parse_decimal(s.strip_suffix(|c| c == 'm' || c == 'M').unwrap_or(s), 2)
+}
+
+/// A decimal number with up to `places` digits after the point, scaled up by
+/// 10^places.
+fn parse_decimal(s: &str, places: u32) -> Option<i64> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if whole.is_empty()
+        || fraction.len() > places as usize
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+        || whole.len() > 12
+    {
+        return None;
+    }
+    let mut value = whole.parse::<i64>().ok()? * 10i64.pow(places);
+    if !fraction.is_empty() {
+        value += fraction.parse::<i64>().ok()? * 10i64.pow(places - fraction.len() as u32);
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// Replaces `$` and `${offset[,width[,base]]}` with the iterator of a
+/// `$GENERATE`, `\$` is a literal dollar sign.
+fn substitute(token: &Token, value: u32) -> Result<Token> {
+    let invalid = || token.error(format!("Invalid substitution in {}", token.text));
+
+    let mut text = String::with_capacity(token.text.len());
+    let mut chars = token.text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                text.push(c);
+                text.extend(chars.next());
+            }
+            '$' => {
+                let rest = chars.as_str();
+                if !rest.starts_with('{') {
+                    text.push_str(&value.to_string());
+                    continue;
+                }
+                let end = rest.find('}').ok_or_else(invalid)?;
+                let mut spec = rest[1..end].split(',');
+                chars = rest[end + 1..].chars();
+
+                let offset: i64 = spec.next().unwrap_or("0").parse().map_err(|_| invalid())?;
+                let width: usize = spec.next().unwrap_or("0").parse().map_err(|_| invalid())?;
+                let base = spec.next().unwrap_or("d");
+                if spec.next().is_some() {
+                    return Err(invalid());
+                }
+                let v = u32::try_from(value as i64 + offset).map_err(|_| invalid())?;
+                let formatted = match base {
+                    "d" => format!("{:0width$}", v, width = width),
+                    "o" => format!("{:0width$o}", v, width = width),
+                    "x" => format!("{:0width$x}", v, width = width),
+                    "X" => format!("{:0width$X}", v, width = width),
+                    _ => return Err(invalid()),
+                };
+                text.push_str(&formatted);
+            }
+            c => text.push(c),
+        }
+    }
+    Ok(Token {
+        text,
+        quoted: token.quoted,
+        line: token.line,
+        column: token.column,
+        end: token.end,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::setup;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn parse(zone: &str) -> Result<Vec<ResourceRecord>> {
+        ZoneParser::new()
+            .origin(DomainName::from("example.com"))
+            .parse_str(zone)
+    }
+
+    fn error(zone: &str) -> (usize, usize, String) {
+        match parse(zone) {
+            Err(MessageError::ZoneFile(line, column, reason)) => (line, column, reason),
+            r => panic!("Expected a zone file error, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_parse_zone() {
+        setup();
+
+        let zone = r#"
+$TTL 3600 ; the default
+@   IN  SOA ns1 hostmaster.example.com. (
+            2024010101 ; serial
+            1h 15m 1w 300 )
+        NS  ns1
+        NS  ns2.example.net.
+ns1 300 A   192.0.2.1
+        IN 600 AAAA 2001:db8::1
+www     CNAME   @
+$ORIGIN sub
+txt     TXT "hello \"world\"" two\ words \065\066
+*       CH  MX  10 mail
+"#;
+        let records = parse(zone).unwrap();
+        let summary: Vec<(String, u32, Class)> = records
+            .iter()
+            .map(|r| (r.name.to_string(), r.ttl, r.class))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("example.com".to_string(), 3600, Class::IN),
+                ("example.com".to_string(), 3600, Class::IN),
+                ("example.com".to_string(), 3600, Class::IN),
+                ("ns1.example.com".to_string(), 300, Class::IN),
+                ("ns1.example.com".to_string(), 600, Class::IN),
+                ("www.example.com".to_string(), 3600, Class::IN),
+                ("txt.sub.example.com".to_string(), 3600, Class::IN),
+                ("*.sub.example.com".to_string(), 3600, Class::CH),
+            ]
+        );
+        assert_eq!(
+            records[0].data,
+            RData::SOA(
+                DomainName::from("ns1.example.com"),
+                DomainName::from("hostmaster.example.com"),
+                2024010101,
+                3600,
+                900,
+                604800,
+                300
+            )
+        );
+        assert_eq!(
+            records[2].data,
+            RData::NS(DomainName::from("ns2.example.net"))
+        );
+        assert_eq!(records[3].data, RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!(
+            records[4].data,
+            RData::AAAA("2001:db8::1".parse::<Ipv6Addr>().unwrap())
+        );
+        assert_eq!(
+            records[5].data,
+            RData::CNAME(DomainName::from("example.com"))
+        );
+        assert_eq!(
+            records[6].data,
+            RData::TXT(vec![
+                b"hello \"world\"".to_vec(),
+                b"two words".to_vec(),
+                b"AB".to_vec()
+            ])
+        );
+        assert_eq!(
+            records[7].data,
+            RData::MX(10, DomainName::from("mail.sub.example.com"))
+        );
+    }
+
+    #[test]
+    fn test_parse_ttl_inheritance() {
+        setup();
+
+        // Without $TTL the last explicit TTL is used, and the SOA minimum
+        // before that.
+        let records = ZoneParser::new()
+            .parse_str(
+                ". SOA a. b. 1 2 3 4 5\n\
+                 a. A 192.0.2.1\n\
+                 b. 60 A 192.0.2.2\n\
+                 c. A 192.0.2.3\n",
+            )
+            .unwrap();
+        let ttls: Vec<u32> = records.iter().map(|r| r.ttl).collect();
+        assert_eq!(ttls, vec![5, 5, 60, 60]);
+
+        assert!(matches!(
+            ZoneParser::new().parse_str("a. A 192.0.2.1"),
+            Err(MessageError::ZoneFile(1, 4, _))
+        ));
+        let records = ZoneParser::new()
+            .ttl(30)
+            .parse_str("a. A 192.0.2.1")
+            .unwrap();
+        assert_eq!(records[0].ttl, 30);
+    }
+
+    #[test]
+    fn test_parse_rdata() {
+        setup();
+
+        let zone = r#"
+$TTL 60
+a LOC 52 22 23.000 N 4 53 32.000 E -2.00m 0.00m 10000m 10m
+a LOC 42 21 S 71 W 24m 30m
+a SRV 10 20 5060 sip
+a NAPTR 100 10 "U" "E2U+sip" "!^.*$!sip:info@example.com!" .
+a DS 60485 5 1 ( 2BB183AF5F22588179A53B0A
+                 98631FAD1A292118 )
+a DNSKEY 256 3 5 ( AQPSKmynfzW4kyBv015MUG2DeIQ3
+                 Cbl+BBZH4b/0PY1kxkmvHjcZc8no kfzj31GajIQKY+5CptLr3buXA10hWqTkF7H6RfoRqXQeogmMHfpftf6z Mv1LyBUgia7za6ZEzOJBOztyvhjL742iU/TpPSEDhm2SNKLijfUppn1U aNvv4w== )
+a RRSIG A 5 3 86400 20030322173103 20030220173103 2642 example.com. oJB1W6WNGv+ldvQ3WDG0MQkg5IEhjRip8WTrPYGv07h108dUKGMeDPKijVCHX3DDKdfb+v6oB9wfuh3DTJXUAfI/M0zmO/zz8bW0Rznl8O3tGNazPwQKkRN20XPXV6nwwfoXmJQbsLNrLfkGJ5D6fwFm8nN+6pBzeDQfsS3Ap3o=
+a NSEC host.example.com. A MX RRSIG NSEC TYPE1234
+a NSEC3 1 1 12 aabbccdd 2vptu5timamqttgl4luu9kg21e0aor3s A RRSIG
+a NSEC3PARAM 1 0 12 -
+a TLSA 3 1 1 0c72ac70b745ac19998811b131d662c9ac69dbdbe7cb23e5b514b56664c5d3d6
+a SSHFP 1 1 123456789abcdef67890123456789abcdef67890
+a HTTPS 1 . alpn="h2,h3" port=8443 ipv4hint=192.0.2.1,192.0.2.2 key667=hello
+a SVCB 0 svc.example.net.
+a CAA 0 issue "ca.example.net"
+a URI 10 1 "ftp://ftp1.example.com/public"
+a HINFO "Generic PC" "Linux"
+a WKS 192.0.2.1 tcp 25 80
+a TYPE1234 \# 4 0A000001
+a A \# 4 C0000201
+a NULL \# 0
+"#;
+        let records = parse(zone).unwrap();
+        let data: Vec<String> = records.iter().map(|r| r.data.to_string()).collect();
+        assert_eq!(
+            data,
+            vec![
+                "LOC(52 22 23.000 N 4 53 32.000 E -2.00m 0m 10000m 10m)",
+                "LOC(42 21 0.000 S 71 0 0.000 W 24.00m 30m 10000m 10m)",
+                "SRV(10, 20, 5060, sip.example.com)",
+                "NAPTR(100, 10, \"U\", \"E2U+sip\", \"!^.*$!sip:info@example.com!\", .)",
+                "DS(60485, 5, 1, 2BB183AF5F22588179A53B0A98631FAD1A292118)",
+                "DNSKEY(256, 3, 5, AQPSKmynfzW4kyBv015MUG2DeIQ3Cbl+BBZH4b/0PY1kxkmvHjcZc8nokfzj31GajIQKY+5CptLr3buXA10hWqTkF7H6RfoRqXQeogmMHfpftf6zMv1LyBUgia7za6ZEzOJBOztyvhjL742iU/TpPSEDhm2SNKLijfUppn1UaNvv4w==)",
+                "RRSIG(A, 5, 3, 86400, 20030322173103, 20030220173103, 2642, example.com, oJB1W6WNGv+ldvQ3WDG0MQkg5IEhjRip8WTrPYGv07h108dUKGMeDPKijVCHX3DDKdfb+v6oB9wfuh3DTJXUAfI/M0zmO/zz8bW0Rznl8O3tGNazPwQKkRN20XPXV6nwwfoXmJQbsLNrLfkGJ5D6fwFm8nN+6pBzeDQfsS3Ap3o=)",
+                "NSEC(host.example.com A MX RRSIG NSEC TYPE1234)",
+                "NSEC3(1, 1, 12, AABBCCDD, 2VPTU5TIMAMQTTGL4LUU9KG21E0AOR3S A RRSIG)",
+                "NSEC3PARAM(1, 0, 12, -)",
+                "TLSA(3, 1, 1, 0C72AC70B745AC19998811B131D662C9AC69DBDBE7CB23E5B514B56664C5D3D6)",
+                "SSHFP(1, 1, 123456789ABCDEF67890123456789ABCDEF67890)",
+                "HTTPS(1, . alpn=\"h2,h3\" port=8443 ipv4hint=192.0.2.1,192.0.2.2 key667=\"hello\")",
+                "SVCB(0, svc.example.net)",
+                "CAA(0, issue, \"ca.example.net\")",
+                "URI(10, 1, \"ftp://ftp1.example.com/public\")",
+                "HINFO(\"Generic PC\", \"Linux\")",
+                "WKS(192.0.2.1, 6, 25, 80)",
+                "Raw(1234: [10, 0, 0, 1])",
+                "A(192.0.2.1)",
+                "NULL([])",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_generate() {
+        setup();
+
+        let records =
+            parse("$GENERATE 1-5/2 host-$ 300 A 192.0.2.$\n$GENERATE 10-11 ${-10,3,x}.rev PTR \\$-${0,0,X}\n")
+                .unwrap();
+        let summary: Vec<String> = records
+            .iter()
+            .map(|r| format!("{} {} {}", r.name, r.ttl, r.data))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                "host-1.example.com 300 A(192.0.2.1)",
+                "host-3.example.com 300 A(192.0.2.3)",
+                "host-5.example.com 300 A(192.0.2.5)",
+                "000.rev.example.com 300 PTR(\\$-A.example.com)",
+                "001.rev.example.com 300 PTR(\\$-B.example.com)",
+            ]
+        );
+
+        // A range that's too big is rejected before any records are made.
+        assert_eq!(
+            parse("$GENERATE 1-65536 host-$ 60 A 192.0.2.1\n")
+                .unwrap()
+                .len(),
+            65536
+        );
+        assert_eq!(
+            error("$GENERATE 0-4294967295 host-$ 60 A 192.0.2.1\n"),
+            (
+                1,
+                11,
+                "Range 0-4294967295 generates more than 65536 records".to_string()
+            )
+        );
+        assert!(parse("$GENERATE 0-4294967295/65536 host-$ 60 A 192.0.2.1\n").is_ok());
+    }
+
+    #[test]
+    fn test_parse_include() {
+        setup();
+
+        let dir = std::env::temp_dir().join(format!("dns-message-zone-{}", std::process::id()));
+        fs::create_dir_all(dir.join("zones")).unwrap();
+        fs::write(
+            dir.join("main.zone"),
+            "$ORIGIN example.com.\n$TTL 60\nwww A 192.0.2.1\n\
+             $INCLUDE zones/sub.zone sub\n\
+             \tA 192.0.2.3\n\
+             mail A 192.0.2.4\n",
+        )
+        .unwrap();
+        fs::write(dir.join("zones/sub.zone"), "host A 192.0.2.2\n").unwrap();
+        fs::write(dir.join("zones/bad.zone"), "host A 192.0.2\n").unwrap();
+        fs::write(dir.join("loop.zone"), "$INCLUDE loop.zone\n").unwrap();
+
+        let records = ZoneParser::new().parse_file(dir.join("main.zone"));
+        let include_bad = ZoneParser::new().parse_file(dir.join("zones/../loop.zone"));
+        let bad = ZoneParser::new()
+            .origin(DomainName::from("example.com"))
+            .ttl(60)
+            .parse_str(&format!(
+                "$INCLUDE {}\n",
+                dir.join("zones/bad.zone").display()
+            ));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<String> = records
+            .unwrap()
+            .iter()
+            .map(|r| r.name.to_string())
+            .collect();
+        // The owner isn't carried out of the included file.
+        assert_eq!(
+            names,
+            vec![
+                "www.example.com",
+                "host.sub.example.com",
+                "www.example.com",
+                "mail.example.com"
+            ]
+        );
+
+        match include_bad {
+            Err(MessageError::ZoneFile(1, 10, reason)) => {
+                assert!(reason.ends_with("Too many nested includes"), "{}", reason)
+            }
+            r => panic!("Expected an include depth error, got {:?}", r),
+        }
+        match bad {
+            Err(MessageError::ZoneFile(1, 8, reason)) => {
+                assert!(reason.contains("bad.zone: Invalid address"), "{}", reason)
+            }
+            r => panic!("Expected an error from the included file, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        setup();
+
+        assert_eq!(
+            error("$TTL 60\nwww A 192.0.2.300\n"),
+            (2, 7, "Invalid address 192.0.2.300".to_string())
+        );
+        assert_eq!(
+            error("$TTL 60\nwww A\n"),
+            (2, 6, "Expected address".to_string())
+        );
+        assert_eq!(
+            error("$TTL 60\nwww BOGUS 1\n"),
+            (2, 5, "Unknown type BOGUS".to_string())
+        );
+        assert_eq!(
+            error("$TTL 60\nwww A ( 192.0.2.1\n"),
+            (2, 7, "Unbalanced parenthesis".to_string())
+        );
+        assert_eq!(
+            error("$TTL 60\nwww TXT \"unterminated\n"),
+            (2, 9, "Unterminated string".to_string())
+        );
+        assert_eq!(
+            error("$TTL 60\n  A 192.0.2.1\n"),
+            (2, 3, "No previous owner name".to_string())
+        );
+        assert_eq!(
+            error("$TTL 60\nwww A 192.0.2.1 extra\n"),
+            (2, 17, "Unexpected extra".to_string())
+        );
+        assert_eq!(
+            error("$TTL 60\nwww TYPE99 \\# 2 00\n"),
+            (
+                2,
+                12,
+                "Rdata length is 2 but 1 octets were given".to_string()
+            )
+        );
+        assert_eq!(
+            error("$TTL 60\nwww NULL\n"),
+            (2, 9, "Expected \\# generic rdata for NULL".to_string())
+        );
+        assert_eq!(
+            error(&format!("$TTL 60\nwww TXT {}\n", "a".repeat(256))),
+            (
+                2,
+                9,
+                "Character string is 256 octets, the limit is 255".to_string()
+            )
+        );
+        assert_eq!(
+            error("$TTL 60\nwww A \\# 2 0102\n").2,
+            "Invalid A rdata: RDATA is truncated"
+        );
+        assert_eq!(error("$BOGUS\n").2, "Unknown directive $BOGUS");
+        assert_eq!(error("$TTL 1x\n").2, "Invalid TTL 1x");
+        assert_eq!(error("$TTL 4294967295s1s\n").2, "Invalid TTL 4294967295s1s");
+        let long = "4294967295w".repeat(8000);
+        assert_eq!(
+            error(&format!("$TTL {}\n", long)).2,
+            format!("Invalid TTL {}", long)
+        );
+
+        assert!(ZoneParser::new().parse_str("www 60 A 192.0.2.1").is_err());
+    }
+}
@@ -1,5 +1,7 @@
 mod server;
 
+use dns_message::EdnsOption;
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     tracing_subscriber::fmt::init();
@@ -8,11 +10,28 @@ async fn main() -> Result<(), anyhow::Error> {
     let mut server = server::Server::new(local_addr.parse()?);
 
     server.mod_req(|m| {
-        tracing::info!("Message request: {}", m);
+        tracing::info!("Message request: {}", m.dig().compact());
     });
 
     server.mod_resp(|m| {
-        tracing::info!("Message response: {}", m);
+        tracing::info!("Message response: {}", m.dig().compact());
+
+        if let Some(edns) = m.edns.as_ref() {
+            for option in edns.options.iter() {
+                if let EdnsOption::ExtendedError(..) = option {
+                    tracing::warn!("Upstream returned {}", option);
+                }
+            }
+        }
+    });
+
+    // The zones served as a secondary are given as arguments. There's no
+    // zone transfer yet, so a refresh is only logged.
+    for zone in std::env::args().skip(1) {
+        server.secondary(zone.parse()?);
+    }
+    server.refresh(|zone, serial| {
+        tracing::info!("Refreshing secondary zone {} to serial {:?}", zone, serial);
     });
 
     server.run().await
@@ -1,5 +1,8 @@
-use dns_message::Message;
+use anyhow::Context;
+use bytes::{Bytes, BytesMut};
+use dns_message::{DomainName, Header, Message, MessageRef, OpCode, ParseOptions, RCode, Type};
 use futures::prelude::*;
+use futures::stream::SplitSink;
 
 use std::net::SocketAddr;
 
@@ -8,14 +11,22 @@ use tokio::net::UdpSocket;
 use tokio::sync::Mutex;
 use tokio_util::codec::BytesCodec;
 use tokio_util::udp::UdpFramed;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
 type Result<T> = anyhow::Result<T>;
 
+/// The sending half of the UDP socket, shared by the tasks answering requests.
+type Sink = Arc<Mutex<SplitSink<UdpFramed<BytesCodec>, (Bytes, SocketAddr)>>>;
+
+/// RFC1035 - the largest response a client without EDNS can receive.
+const UDP_PAYLOAD_SIZE: usize = 512;
+
 pub(crate) struct Server {
     local_addr: SocketAddr,
     mod_req: Option<fn(&mut Message)>,
     mod_resp: Option<fn(&mut Message)>,
+    secondaries: Vec<DomainName>,
+    refresh: Option<fn(&DomainName, Option<u32>)>,
 }
 
 impl Server {
@@ -24,6 +35,8 @@ impl Server {
             local_addr,
             mod_req: None,
             mod_resp: None,
+            secondaries: Vec::new(),
+            refresh: None,
         }
     }
 
@@ -35,11 +48,25 @@ impl Server {
         self.mod_resp = Some(mod_resp);
     }
 
+    /// Serves the zone as a secondary, so a NOTIFY for it is answered and
+    /// triggers a refresh. A NOTIFY for any other zone is answered with
+    /// NOTAUTH.
+    pub fn secondary(&mut self, zone: DomainName) {
+        self.secondaries.push(zone);
+    }
+
+    /// Called with the zone and the serial of the SOA hint, if there was one,
+    /// when a NOTIFY says a secondary zone has changed.
+    pub fn refresh(&mut self, refresh: fn(&DomainName, Option<u32>)) {
+        self.refresh = Some(refresh);
+    }
+
     pub async fn run(&self) -> Result<()> {
         let listener = UdpSocket::bind(&self.local_addr).await?;
 
         let (sink, mut stream) = UdpFramed::new(listener, BytesCodec::new()).split();
         let sink = Arc::new(Mutex::new(sink));
+        let secondaries = Arc::new(self.secondaries.clone());
 
         loop {
             info!("Waiting to recv...");
@@ -60,53 +87,182 @@ impl Server {
             // Pull these out so that we don't need to worry about referencing self in the spawned task.
             let mod_req = self.mod_req;
             let mod_resp = self.mod_resp;
+            let refresh = self.refresh;
+            let secondaries = secondaries.clone();
 
             tokio::spawn(async move {
-                let mut message = Message::from_bytes(bytes.as_ref()).unwrap();
-                info!("{}: {}", addr, message);
-
-                if let Some(mod_req) = mod_req {
-                    mod_req(&mut message);
-                }
-
-                let mut r_message = match send_dns_request(&message).await {
+                // Only the header and question are needed to forward the request,
+                // so it is only fully decoded when it is to be modified.
+                let request = match MessageRef::from_bytes(bytes.as_ref()) {
                     Ok(r) => r,
                     Err(e) => {
-                        error!("Could not send DNS request: {}", e);
+                        let e = anyhow::Error::new(e);
+                        error!("Could not parse request from {}: {:#}", addr, e);
+
+                        // The header may still be readable, so the client can be
+                        // told rather than left to time out.
+                        if let Some(response) = format_error(bytes.as_ref()) {
+                            send_response(&sink, addr, &response, UDP_PAYLOAD_SIZE).await;
+                        }
                         return;
                     }
                 };
+                info!("{}: {}", addr, request);
 
-                if let Some(mod_resp) = mod_resp {
-                    mod_resp(&mut r_message);
-                }
+                // The response has to fit in what the client can receive over UDP.
+                let max_size = request.max_payload_size();
 
-                let mut buf = Vec::with_capacity(1024);
-                let len = match r_message.to_bytes(&mut buf) {
-                    Ok(len) => len,
-                    Err(e) => {
-                        error!("Could not serialize message: {}", e);
+                // A NOTIFY is for this server, rather than the upstream server.
+                let header = request.header();
+                let r_message = if header.opcode == OpCode::Notify {
+                    if header.qr {
+                        warn!("Ignoring NOTIFY response from {}", addr);
                         return;
                     }
-                };
-                info!("Sending to: {}, length: {}", addr, len);
-                {
-                    match sink.lock().await.send((buf.into(), addr)).await {
-                        Ok(_) => {}
+                    match answer_notify(&request, &secondaries, refresh) {
+                        Ok(r) => r,
                         Err(e) => {
-                            error!("Error sending buffer to client: {}", e);
-                            return;
+                            error!("Could not answer NOTIFY: {:#}", e);
+                            error_response(&request, RCode::ServerFailure)
                         }
                     }
-                }
+                } else {
+                    match forward(&request, mod_req, mod_resp).await {
+                        Ok(r) => r,
+                        Err(e) => {
+                            error!("{:#}", e);
+                            error_response(&request, RCode::ServerFailure)
+                        }
+                    }
+                };
 
-                info!("Sent");
+                send_response(&sink, addr, &r_message, max_size).await;
             });
         }
     }
 }
 
-async fn send_dns_request(msg: &Message) -> Result<Message> {
+/// Writes the response, truncated to fit the largest message the client can
+/// receive, and sends it to the client.
+async fn send_response(sink: &Sink, addr: SocketAddr, response: &Message, max_size: usize) {
+    let mut buf = BytesMut::with_capacity(max_size);
+    let len = match response.to_bytes_with_limit(&mut buf, max_size) {
+        Ok(len) => len,
+        Err(e) => {
+            error!("Could not serialize message: {}", e);
+            return;
+        }
+    };
+    info!("Sending to: {}, length: {}", addr, len);
+    if let Err(e) = sink.lock().await.send((buf.freeze(), addr)).await {
+        error!("Error sending buffer to client: {}", e);
+        return;
+    }
+
+    info!("Sent");
+}
+
+/// The response to a request that couldn't be answered. A request that can't
+/// be fully decoded gets a FORMERR instead.
+fn error_response(request: &MessageRef<'_>, rcode: RCode) -> Message {
+    match request.to_message() {
+        Ok(query) => Message::error_response(&query, rcode),
+        Err(e) => {
+            let e = anyhow::Error::new(e);
+            error!("Could not decode request: {:#}", e);
+            Message::error_response(&header_only(*request.header()), RCode::FormatError)
+        }
+    }
+}
+
+/// RFC1035 - The FORMERR response to a request that couldn't be parsed, if
+/// at least its header could be. A response is never answered, so that two
+/// servers can't answer each other forever.
+fn format_error(input: &[u8]) -> Option<Message> {
+    let header = Header::from_bytes(input).ok().filter(|h| !h.qr)?;
+    Some(Message::error_response(
+        &header_only(header),
+        RCode::FormatError,
+    ))
+}
+
+/// A query with only the header of the request, for when the rest of it
+/// can't be read.
+fn header_only(header: Header) -> Message {
+    Message {
+        header,
+        questions: Vec::new(),
+        answers: Vec::new(),
+        name_servers: Vec::new(),
+        additional_records: Vec::new(),
+        edns: None,
+    }
+}
+
+/// Sends the request to the upstream server, modifying the request and
+/// response on the way.
+async fn forward(
+    request: &MessageRef<'_>,
+    mod_req: Option<fn(&mut Message)>,
+    mod_resp: Option<fn(&mut Message)>,
+) -> Result<Message> {
+    let request = match mod_req {
+        Some(mod_req) => modify_request(request, mod_req).context("Could not modify request")?,
+        None => request.as_bytes().to_vec(),
+    };
+
+    let mut r_message = send_dns_request(&request)
+        .await
+        .context("Could not send DNS request")?;
+
+    if let Some(mod_resp) = mod_resp {
+        mod_resp(&mut r_message);
+    }
+    Ok(r_message)
+}
+
+/// RFC1996 - Answers a NOTIFY, and triggers a refresh when it's for one of
+/// the secondary zones.
+fn answer_notify(
+    request: &MessageRef<'_>,
+    secondaries: &[DomainName],
+    refresh: Option<fn(&DomainName, Option<u32>)>,
+) -> Result<Message> {
+    let request = request.to_message()?;
+    let rcode = match request.questions.as_slice() {
+        [q] if q.q_type == Type::SOA => {
+            if secondaries.contains(&q.q_name) {
+                let serial = request.soa_hint();
+                info!("NOTIFY for {} with serial {:?}", q.q_name, serial);
+                if let Some(refresh) = refresh {
+                    refresh(&q.q_name, serial);
+                }
+                RCode::NoError
+            } else {
+                warn!("NOTIFY for {}, which isn't a secondary zone", q.q_name);
+                RCode::NotAuth
+            }
+        }
+        _ => RCode::FormatError,
+    };
+
+    // The response echoes the question, and is authoritative for a zone
+    // that's served here.
+    let mut response = Message::error_response(&request, rcode);
+    response.header.aa = rcode == RCode::NoError;
+    Ok(response)
+}
+
+fn modify_request(request: &MessageRef, mod_req: fn(&mut Message)) -> Result<Vec<u8>> {
+    let mut message = request.to_message()?;
+    mod_req(&mut message);
+
+    let mut buf = Vec::with_capacity(512);
+    message.to_bytes(&mut buf)?;
+    Ok(buf)
+}
+
+async fn send_dns_request(request: &[u8]) -> Result<Message> {
     // New socket to talk to upstream dns.
     let addr: SocketAddr = "0.0.0.0:0".parse()?;
     let socket = UdpSocket::bind(addr).await?;
@@ -114,22 +270,29 @@ async fn send_dns_request(msg: &Message) -> Result<Message> {
     let remote_addr: SocketAddr = "8.8.8.8:53".parse()?;
     socket.connect(&remote_addr).await?;
 
-    let mut buf = Vec::with_capacity(512);
-    let len = msg.to_bytes(&mut buf)?;
-
     info!("Sending to {}", remote_addr);
-    socket.send(&buf[0..len]).await?;
+    socket.send(request).await?;
 
-    let mut buf = vec![0u8; 1024];
+    let mut buf = vec![0u8; u16::MAX as usize];
     let len = socket.recv(&mut buf).await?;
 
-    let r_message = match Message::from_bytes(&buf[0..len]) {
-        Ok(m) => m,
+    // Whatever the upstream server sent is passed on, even if it's not quite
+    // valid.
+    let r_message = match Message::from_bytes_with(&buf[0..len], ParseOptions::lenient()) {
+        Ok((m, warnings)) => {
+            for warning in warnings {
+                warn!("Response from upstream DNS has: {}", warning);
+            }
+            m
+        }
         Err(e) => {
-            error!("Error parsing response from upstream DNS: {}", e);
-            return Err(anyhow::Error::new(e));
+            // The alternate format includes the cause of the error.
+            let e = anyhow::Error::new(e);
+            error!("Error parsing response from upstream DNS: {:#}", e);
+            return Err(e);
         }
     };
-    info!("Got back: {}", r_message);
+    info!("Got back: {}", r_message.dig().compact());
+    debug!("Response from upstream:\n{}", r_message.dig());
     Ok(r_message)
 }